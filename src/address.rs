@@ -0,0 +1,191 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    network::AnyTransactionReceipt,
+    primitives::{utils::format_units, Address, U256},
+    providers::Provider,
+};
+use serde::Serialize;
+
+use crate::{
+    journal::{Journal, Severity},
+    rpc::RpcClient,
+};
+
+/// Follows the activity of an address, e.g. the sender account of a load generator.
+pub struct AddressWatch {
+    rpc: Arc<RpcClient>,
+    address: Address,
+    blocks: Arc<Mutex<VecDeque<Block>>>,
+    /// Warn when the balance would run out within the duration at the current spend rate.
+    runway_warning: Option<(Journal, Duration)>,
+    /// Whether the runway is below the warning threshold, so it is logged once.
+    runway_low: AtomicBool,
+}
+
+/// What the address did in a block.
+struct Block {
+    number: u64,
+    /// When the block was recorded.
+    recorded: Instant,
+    transactions: usize,
+    gas_used: u64,
+    /// The nonce and balance after the block, filled in once fetched.
+    account: Option<(u64, U256)>,
+}
+
+/// The activity of the watched address in the current window.
+#[derive(Debug, Clone, Serialize)]
+pub struct AddressMetrics {
+    pub address: Address,
+    /// Transactions sent by the address.
+    pub transactions: usize,
+    /// Gas used by the transactions sent by the address.
+    pub gas_used: u64,
+    /// Nonce and balance changes between the first and the last block of the window whose
+    /// account state is known.
+    pub nonce_delta: Option<i64>,
+    pub balance_change_eth: Option<f64>,
+    /// The latest known balance.
+    pub balance_eth: Option<f64>,
+    /// The balance spent per minute over the window, negative if the address is topped up.
+    pub drain_eth_per_minute: Option<f64>,
+    /// The minutes until the balance runs out at the current spend rate, if it is draining.
+    pub runway_minutes: Option<f64>,
+}
+
+impl AddressWatch {
+    pub fn new(rpc: Arc<RpcClient>, address: Address) -> Self {
+        Self {
+            rpc,
+            address,
+            blocks: Arc::new(Mutex::new(VecDeque::new())),
+            runway_warning: None,
+            runway_low: AtomicBool::new(false),
+        }
+    }
+
+    /// Log a warning when the balance would run out within `runway` at the current spend rate.
+    pub fn with_runway_warning(mut self, journal: Journal, runway: Duration) -> Self {
+        self.runway_warning = Some((journal, runway));
+        self
+    }
+
+    /// Record the transactions of the address in a block and fetch its account state after the
+    /// block in the background, keeping the last `window` blocks.
+    pub fn observe(&self, number: u64, receipts: &[AnyTransactionReceipt], window: usize) {
+        let sent = receipts
+            .iter()
+            .filter(|r| r.from == self.address)
+            .collect::<Vec<_>>();
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks.push_back(Block {
+            number,
+            recorded: Instant::now(),
+            transactions: sent.len(),
+            gas_used: sent.iter().map(|r| r.gas_used).sum(),
+            account: None,
+        });
+        while blocks.len() > window {
+            blocks.pop_front();
+        }
+        drop(blocks);
+
+        let (rpc, address, blocks) = (self.rpc.clone(), self.address, self.blocks.clone());
+        tokio::spawn(async move {
+            let nonce = rpc
+                .request("eth_getTransactionCount", |provider| async move {
+                    provider.get_transaction_count(address).number(number).await
+                })
+                .await;
+            let balance = rpc
+                .request("eth_getBalance", |provider| async move {
+                    provider.get_balance(address).number(number).await
+                })
+                .await;
+            let (Ok(nonce), Ok(balance)) = (nonce, balance) else {
+                return;
+            };
+            let mut blocks = blocks.lock().unwrap();
+            if let Some(block) = blocks.iter_mut().find(|b| b.number == number) {
+                block.account = Some((nonce, balance));
+            }
+        });
+    }
+
+    /// Sum up the activity of the address in the recorded blocks.
+    pub fn metrics(&self) -> AddressMetrics {
+        let blocks = self.blocks.lock().unwrap();
+        let mut accounts = blocks.iter().filter_map(|b| Some((b.recorded, b.account?)));
+        let first = accounts.next();
+        let last = accounts.next_back();
+        let eth = |wei: U256| {
+            format_units(wei, "ether")
+                .ok()
+                .and_then(|eth| eth.parse::<f64>().ok())
+                .unwrap_or_default()
+        };
+        let (nonce_delta, balance_change_eth, drain_eth_per_minute) = match (first, last) {
+            (
+                Some((first_recorded, (first_nonce, first_balance))),
+                Some((last_recorded, (last_nonce, last_balance))),
+            ) => {
+                let change = if last_balance >= first_balance {
+                    eth(last_balance - first_balance)
+                } else {
+                    -eth(first_balance - last_balance)
+                };
+                let minutes = (last_recorded - first_recorded).as_secs_f64() / 60.0;
+                (
+                    Some(last_nonce as i64 - first_nonce as i64),
+                    Some(change),
+                    (minutes > 0.0).then(|| -change / minutes),
+                )
+            }
+            _ => (None, None, None),
+        };
+        let balance_eth = blocks
+            .iter()
+            .rev()
+            .find_map(|b| b.account)
+            .map(|(_, balance)| eth(balance));
+        let runway_minutes = match (balance_eth, drain_eth_per_minute) {
+            (Some(balance), Some(drain)) if drain > 0.0 => Some(balance / drain),
+            _ => None,
+        };
+        if let Some((journal, runway)) = &self.runway_warning {
+            let low = runway_minutes.is_some_and(|minutes| minutes * 60.0 < runway.as_secs_f64());
+            if low && !self.runway_low.swap(true, Ordering::Relaxed) {
+                journal.log(
+                    Severity::Warn,
+                    "balance",
+                    format!(
+                        "{} runs out of funds in {:.1} min at {:.4} ETH/min",
+                        self.address,
+                        runway_minutes.unwrap_or_default(),
+                        drain_eth_per_minute.unwrap_or_default()
+                    ),
+                );
+            } else if !low {
+                self.runway_low.store(false, Ordering::Relaxed);
+            }
+        }
+        AddressMetrics {
+            address: self.address,
+            transactions: blocks.iter().map(|b| b.transactions).sum(),
+            gas_used: blocks.iter().map(|b| b.gas_used).sum(),
+            nonce_delta,
+            balance_change_eth,
+            balance_eth,
+            drain_eth_per_minute,
+            runway_minutes,
+        }
+    }
+}
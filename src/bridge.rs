@@ -0,0 +1,64 @@
+use alloy::{
+    consensus::Typed2718,
+    network::{AnyRpcBlock, AnyTransactionReceipt, TransactionResponse},
+    primitives::{address, Address},
+    sol,
+    sol_types::SolEvent,
+};
+use serde::Serialize;
+
+/// The type of deposit transactions submitted through the L1 bridge.
+const DEPOSIT_TX_TYPE: u8 = 0x7e;
+
+/// The sender of the system deposit carrying the L1 attributes at the start of every block.
+const SYSTEM_DEPOSITOR: Address = address!("deaddeaddeaddeaddeaddeaddeaddeaddead0001");
+
+/// The predeploy initiating withdrawals to L1.
+const MESSAGE_PASSER: Address = address!("4200000000000000000000000000000000000016");
+
+sol! {
+    event MessagePassed(
+        uint256 indexed nonce,
+        address indexed sender,
+        address indexed target,
+        uint256 value,
+        uint256 gasLimit,
+        bytes data,
+        bytes32 withdrawalHash
+    );
+}
+
+/// The bridging activity of the current window.
+#[derive(Debug, Clone, Serialize)]
+pub struct BridgeMetrics {
+    /// User deposits from L1 per second.
+    pub deposits_per_second: f64,
+    /// Withdrawals to L1 initiated per second.
+    pub withdrawals_per_second: f64,
+    pub window_deposits: usize,
+    pub window_withdrawals: usize,
+}
+
+/// Count the user deposits in the block, known only when full transactions were fetched.
+#[inline]
+pub fn deposits(block: &AnyRpcBlock) -> Option<usize> {
+    let txs = block.transactions.as_transactions()?;
+    Some(
+        txs.iter()
+            .filter(|tx| tx.ty() == DEPOSIT_TX_TYPE && tx.from() != SYSTEM_DEPOSITOR)
+            .count(),
+    )
+}
+
+/// Count the withdrawals initiated by the receipts' transactions.
+#[inline]
+pub fn withdrawals(receipts: &[AnyTransactionReceipt]) -> usize {
+    receipts
+        .iter()
+        .flat_map(|r| r.inner.inner.logs())
+        .filter(|log| {
+            log.address() == MESSAGE_PASSER
+                && log.topics().first() == Some(&MessagePassed::SIGNATURE_HASH)
+        })
+        .count()
+}
@@ -0,0 +1,71 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use eyre::Result;
+use tokio::sync::OnceCell;
+
+/// A small cache of block data keyed by block hash, so requests for the same block share one
+/// call to the node, including requests made while the first one is still in flight.
+///
+/// Failed and empty responses are not cached; the next request asks the node again.
+pub struct BlockCache<K, T> {
+    capacity: usize,
+    /// The entries from the least to the most recently added.
+    entries: Mutex<VecDeque<(K, Arc<OnceCell<T>>)>>,
+}
+
+impl<K: Copy + Eq, T: Clone> BlockCache<K, T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity + 1)),
+        }
+    }
+
+    /// Get the cached value of `key`, or fetch it, returning whether it was cached.
+    pub async fn get_or_fetch<F, Fut>(&self, key: K, fetch: F) -> Result<(Option<T>, bool)>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<T>>>,
+    {
+        let cell = self.cell(key);
+        if let Some(value) = cell.get() {
+            return Ok((Some(value.clone()), true));
+        }
+        let mut fetched = false;
+        let value = cell
+            .get_or_try_init(|| async {
+                fetched = true;
+                // An absent value is not cached, so it is fetched again next time.
+                fetch().await?.ok_or(None)
+            })
+            .await;
+        match value {
+            Ok(value) => Ok((Some(value.clone()), !fetched)),
+            Err(None) => Ok((None, false)),
+            Err(Some(err)) => Err(err),
+        }
+    }
+
+    /// Cache a value fetched by other means.
+    pub fn insert(&self, key: K, value: T) {
+        let _ = self.cell(key).set(value);
+    }
+
+    /// Get the cell of `key`, adding it and evicting the oldest one if it is new.
+    fn cell(&self, key: K) -> Arc<OnceCell<T>> {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some((_, cell)) = entries.iter().find(|(k, _)| *k == key) {
+            return cell.clone();
+        }
+        let cell = Arc::new(OnceCell::new());
+        entries.push_back((key, cell.clone()));
+        if entries.len() > self.capacity {
+            entries.pop_front();
+        }
+        cell
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    fs::{self, OpenOptions},
+    future::Future,
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use alloy::providers::Provider;
+use clap::{Args as ClapArgs, Parser};
+use eyre::{eyre, Result};
+
+use crate::{
+    config,
+    grafana::Grafana,
+    graphql::GraphQlClient,
+    journal::Journal,
+    node_metrics, proxy,
+    sink::{HistogramBuckets, SinkOptions, SinkSpec},
+    smtp,
+    upload::Uploader,
+    Args, Transport,
+};
+
+/// How long every network check may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Validate a configuration and check that everything it points to is reachable, then exit.
+#[derive(ClapArgs, Debug)]
+pub struct CheckArgs {
+    /// The JSON configuration file to check, as given to `--config`.
+    #[arg(long, value_name = "PATH")]
+    config: PathBuf,
+}
+
+/// The outcomes of the checks, printed as they complete.
+#[derive(Default)]
+struct Checks {
+    passed: usize,
+    failed: usize,
+}
+
+impl Checks {
+    fn report(&mut self, name: impl AsRef<str>, result: Result<String>) {
+        match result {
+            Ok(detail) => {
+                self.passed += 1;
+                println!("ok    {}: {detail}", name.as_ref());
+            }
+            Err(err) => {
+                self.failed += 1;
+                println!("FAIL  {}: {err:#}", name.as_ref());
+            }
+        }
+    }
+}
+
+/// Check the configuration, failing if any check failed.
+pub async fn run(check: CheckArgs) -> Result<()> {
+    let mut checks = Checks::default();
+    let mut options = config::options(&check.config)?;
+    // The secrets the deployment provides through the environment or key files, where named.
+    let key_files = match Args::try_parse_from(argv(&options)) {
+        Ok(args) => args.key_files,
+        Err(_) => Vec::new(),
+    };
+    match config::secret_options(&key_files) {
+        Ok(secrets) => options = [secrets, options].concat(),
+        Err(err) => checks.report("secrets", Err(err)),
+    }
+    let args = match Args::try_parse_from(argv(&options)) {
+        Ok(args) if args.command.is_none() => args,
+        Ok(_) => return Err(eyre!("{} selects a subcommand", check.config.display())),
+        Err(err) => {
+            // Leave out the hint to `--help`, which does not apply to a file.
+            let rendered = err.render().to_string();
+            let message = rendered.split("\n\n").next().unwrap_or_default();
+            checks.report("config", Err(eyre!("{}", message.trim())));
+            return Err(eyre!("the configuration is invalid"));
+        }
+    };
+    checks.report("config", Ok(format!("{} is valid", check.config.display())));
+
+    if args.proxy.is_none() {
+        checks.report("endpoint", resolve(&args.endpoint).await);
+    }
+    checks.report("connection", timed(connect(&args)).await);
+    if let Transport::Graphql = args.transport {
+        let graphql = GraphQlClient::new(args.graphql_endpoint.clone());
+        let block = timed(graphql.get_block_by_number(0)).await;
+        checks.report(
+            format!("graphql {}", args.graphql_endpoint),
+            block.map(|block| match block {
+                Some(_) => "serves blocks".to_string(),
+                None => "reachable, genesis block missing".to_string(),
+            }),
+        );
+    }
+
+    let options = SinkOptions {
+        refresh: false,
+        totals: false,
+        chart: None,
+        diff: false,
+        journal: Journal::new(None, false)?,
+        events: false,
+        resume: true,
+        buckets: HistogramBuckets {
+            transactions: args.histogram_transactions.clone(),
+            gas: args.histogram_gas.clone(),
+            interval_ms: args.histogram_interval_ms.clone(),
+        },
+        queue: 1,
+    };
+    for spec in &args.sinks {
+        let result = match spec {
+            SinkSpec::Stdout => continue,
+            // Opening a recording would truncate it, only make sure it can be written.
+            SinkSpec::Json(path) | SinkSpec::Csv(path) => writable(path),
+            spec => {
+                timed(async {
+                    spec.open(&options).await?;
+                    Ok("connected".to_string())
+                })
+                .await
+            }
+        };
+        checks.report(format!("sink {spec}"), result);
+    }
+
+    for (name, path) in [
+        ("events file", &args.events_file),
+        ("rollup file", &args.rollup_file),
+        ("state file", &args.state_file),
+    ] {
+        if let Some(path) = path {
+            checks.report(format!("{name} {}", path.display()), writable(path));
+        }
+    }
+    for (name, addr) in [
+        ("history address", args.history_addr),
+        ("control address", args.control_addr),
+        ("rebroadcast address", args.rebroadcast),
+    ] {
+        if let Some(addr) = addr {
+            checks.report(format!("{name} {addr}"), bindable(addr));
+        }
+    }
+
+    if let Some(target) = &args.upload {
+        let uploader = Uploader::new(target.clone(), String::new(), options.journal.clone());
+        checks.report(
+            "upload",
+            timed(async {
+                uploader.check().await?;
+                Ok("credentials accepted".to_string())
+            })
+            .await,
+        );
+    }
+    if let Some(url) = &args.grafana_url {
+        let grafana = Grafana::new(
+            url.clone(),
+            args.grafana_token.clone(),
+            options.journal.clone(),
+        );
+        checks.report(
+            format!("grafana {url}"),
+            timed(async {
+                grafana.check().await?;
+                Ok("token accepted".to_string())
+            })
+            .await,
+        );
+    }
+    if let Some(server) = &args.smtp {
+        checks.report(
+            "smtp",
+            timed(async {
+                smtp::check(server).await?;
+                Ok("login accepted, no mail sent".to_string())
+            })
+            .await,
+        );
+    }
+    if let Some(url) = &args.heartbeat_url {
+        // Pinging the heartbeat would report a run that is not happening.
+        checks.report(
+            "heartbeat",
+            reqwest::Url::parse(url)
+                .map(|_| "valid URL, not pinged".to_string())
+                .map_err(Into::into),
+        );
+    }
+    if let Some(url) = &args.node_metrics_url {
+        checks.report(
+            format!("node metrics {url}"),
+            timed(async {
+                let samples = node_metrics::scrape(&reqwest::Client::new(), url).await?;
+                Ok(format!("{} samples", samples.len()))
+            })
+            .await,
+        );
+    }
+
+    match checks.failed {
+        0 => {
+            println!("All {} checks passed", checks.passed);
+            Ok(())
+        }
+        failed => Err(eyre!(
+            "{failed} of {} checks failed",
+            failed + checks.passed
+        )),
+    }
+}
+
+/// Build the command line of a run with the options.
+fn argv(options: &[String]) -> impl Iterator<Item = String> + '_ {
+    std::iter::once("megaeth-telescope".to_string()).chain(options.iter().cloned())
+}
+
+/// Limit a network check to [`TIMEOUT`].
+async fn timed<T>(check: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::time::timeout(TIMEOUT, check)
+        .await
+        .map_err(|_| eyre!("timed out after {} s", TIMEOUT.as_secs()))?
+}
+
+/// Resolve the host of the endpoint.
+async fn resolve(endpoint: &str) -> Result<String> {
+    let (host, port) =
+        proxy::endpoint_host_port(endpoint).ok_or_else(|| eyre!("invalid endpoint {endpoint}"))?;
+    let addrs = tokio::net::lookup_host((host.as_str(), port))
+        .await?
+        .map(|addr| addr.to_string())
+        .collect::<Vec<_>>();
+    Ok(format!("{host}:{port} resolves to {}", addrs.join(", ")))
+}
+
+/// Connect to the node and subscribe to new blocks, as a run would.
+async fn connect(args: &Args) -> Result<String> {
+    let provider = proxy::provider(args.endpoint.clone(), args.proxy.clone()).await?;
+    let chain_id = provider.get_chain_id().await?;
+    let head = provider.get_block_number().await?;
+    let _ = provider.subscribe_blocks().await?;
+    Ok(format!(
+        "chain {chain_id}, head {head}, block subscription accepted"
+    ))
+}
+
+/// Check that a file can be written, without changing it.
+fn writable(path: &Path) -> Result<String> {
+    let existed = path.exists();
+    OpenOptions::new().append(true).create(true).open(path)?;
+    if !existed {
+        fs::remove_file(path)?;
+    }
+    Ok(if existed {
+        "exists, writable"
+    } else {
+        "writable"
+    }
+    .to_string())
+}
+
+/// Check that a local address can be listened on.
+fn bindable(addr: SocketAddr) -> Result<String> {
+    TcpListener::bind(addr)?;
+    Ok("free".to_string())
+}
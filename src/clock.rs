@@ -0,0 +1,81 @@
+use std::{
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+/// Estimates the skew between the local clock and the node's clock.
+///
+/// Like NTP, it relies on the fact that the network delay is never negative: the smallest observed
+/// offset between the local receive time and the header timestamp bounds the skew, and the samples
+/// with the least delay give the tightest bound. No standard RPC exposes the node's wall clock, so
+/// header timestamps are the only time source.
+pub struct ClockDrift {
+    /// Offsets (local receive time minus header timestamp) of the recent blocks in milliseconds.
+    offsets: VecDeque<f64>,
+    capacity: usize,
+    /// Subtract the estimated skew from the propagation latency.
+    correction: bool,
+}
+
+/// The clock metrics of the current window.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockMetrics {
+    /// Estimated local clock skew relative to the node in milliseconds.
+    pub clock_skew_ms: Option<f64>,
+    /// Average time from the header timestamp until the block was received in milliseconds.
+    pub propagation_latency_ms: Option<f64>,
+    /// Whether the skew was subtracted from the propagation latency.
+    pub corrected: bool,
+}
+
+impl ClockDrift {
+    pub fn new(capacity: usize, correction: bool) -> Self {
+        Self {
+            offsets: VecDeque::with_capacity(capacity + 1),
+            capacity,
+            correction,
+        }
+    }
+
+    /// Record the header timestamp (seconds) of a block received at `received_at`.
+    #[inline]
+    pub fn record(&mut self, header_timestamp: u64, received_at: SystemTime) {
+        let received_ms = received_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0;
+        self.offsets
+            .push_back(received_ms - header_timestamp as f64 * 1000.0);
+        if self.offsets.len() > self.capacity {
+            self.offsets.pop_front();
+        }
+    }
+
+    /// Get the estimated skew; positive values mean the local clock is ahead of the node.
+    #[inline]
+    pub fn skew_ms(&self) -> Option<f64> {
+        self.offsets.iter().copied().reduce(f64::min)
+    }
+
+    /// Collect the clock metrics; the values are unknown until a block has been received.
+    #[inline]
+    pub fn metrics(&self) -> ClockMetrics {
+        let skew = self.skew_ms();
+        let latency = skew.map(|skew| {
+            let mean = self.offsets.iter().sum::<f64>() / self.offsets.len() as f64;
+            if self.correction {
+                mean - skew
+            } else {
+                mean
+            }
+        });
+        ClockMetrics {
+            clock_skew_ms: skew,
+            propagation_latency_ms: latency,
+            corrected: self.correction,
+        }
+    }
+}
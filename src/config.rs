@@ -0,0 +1,170 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result, WrapErr};
+use serde_json::{Map, Value};
+
+/// The options holding secrets or long URLs, and the environment variables setting them.
+///
+/// A variable named with a `_FILE` suffix, e.g. `TELESCOPE_GRAFANA_TOKEN_FILE`, names a file the
+/// value is read from instead, as orchestrators mount their secrets. The variables of the options
+/// in [`REPEATED`] hold several values separated by commas.
+pub const ENVIRONMENT: [(&str, &str); 11] = [
+    ("endpoint", "TELESCOPE_ENDPOINT"),
+    ("proxy", "TELESCOPE_PROXY"),
+    ("graphql-endpoint", "TELESCOPE_GRAPHQL_ENDPOINT"),
+    ("upload", "TELESCOPE_UPLOAD"),
+    ("grafana-url", "TELESCOPE_GRAFANA_URL"),
+    ("grafana-token", "TELESCOPE_GRAFANA_TOKEN"),
+    ("smtp", "TELESCOPE_SMTP"),
+    ("heartbeat-url", "TELESCOPE_HEARTBEAT_URL"),
+    ("price-url", "TELESCOPE_PRICE_URL"),
+    ("node-metrics-url", "TELESCOPE_NODE_METRICS_URL"),
+    ("extra-endpoint", "TELESCOPE_EXTRA_ENDPOINT"),
+];
+
+/// The options of [`ENVIRONMENT`] which can be given multiple times.
+const REPEATED: [&str; 1] = ["extra-endpoint"];
+
+/// A built-in combination of options for a common use, applied before the configuration file and
+/// the command line, which override its values.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Preset {
+    /// A short, quickly reacting window on a refreshed line, with the workload breakdown.
+    Demo,
+    /// A long-running monitor: clock, data quality, gaps and telescope's own health.
+    Operator,
+    /// Following a load test: a long window with the throughput trends, the best sustained rates
+    /// and reverted gas, and checks that telescope keeps up.
+    Loadtest,
+}
+
+impl Preset {
+    /// Get the command-line options of the preset.
+    pub fn options(self) -> Vec<String> {
+        let options: &[&str] = match self {
+            Self::Demo => &[
+                "--window",
+                "20",
+                "--refresh",
+                "--totals",
+                "--workload",
+                "--events",
+            ],
+            Self::Operator => &[
+                "--window",
+                "100",
+                "--totals",
+                "--clock-drift",
+                "--data-quality",
+                "--gap-classes",
+                "500,2000",
+                "--stages",
+                "--self-load",
+            ],
+            Self::Loadtest => &[
+                "--window",
+                "500",
+                "--totals",
+                "--ramp-report",
+                "--sustained",
+                "10,60",
+                "--failed-gas",
+                "--gap-classes",
+                "500,2000",
+                "--stages",
+                "--self-load",
+            ],
+        };
+        options.iter().map(|option| option.to_string()).collect()
+    }
+}
+
+/// Read a JSON configuration file as command-line options.
+///
+/// The keys are the long option names (`rpc-retries` or `rpc_retries`), the values their
+/// arguments: `true` for a flag, an array for an option given several times (e.g. `sink`), and
+/// `false` or `null` to leave an option out.
+pub fn options(path: &Path) -> Result<Vec<String>> {
+    let json = fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    let config: Map<String, Value> = serde_json::from_slice(&json)
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+    let mut options = Vec::new();
+    for (key, value) in config {
+        let option = format!("--{}", key.replace('_', "-"));
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Bool(true) => options.push(option.clone()),
+                Value::Bool(false) | Value::Null => {}
+                Value::String(value) => options.extend([option.clone(), value]),
+                Value::Number(value) => options.extend([option.clone(), value.to_string()]),
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(eyre!("invalid value of `{key}` in {}", path.display()))
+                }
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Resolve the secret options of [`ENVIRONMENT`] as command-line options, from the key files and
+/// the environment, which takes precedence.
+///
+/// A key file holds `<VARIABLE>=<value>` lines with the names of the environment variables, and
+/// `#` comments.
+pub fn secret_options(key_files: &[PathBuf]) -> Result<Vec<String>> {
+    let mut values = BTreeMap::new();
+    for path in key_files {
+        let file = fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        for (i, line) in file.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.split_once('=').ok_or_else(|| {
+                eyre!(
+                    "expected <VARIABLE>=<value> at {}:{}",
+                    path.display(),
+                    i + 1
+                )
+            })?;
+            let name = name.trim();
+            if !ENVIRONMENT.iter().any(|(_, variable)| *variable == name) {
+                return Err(eyre!("unknown key `{name}` in {}", path.display()));
+            }
+            values.insert(name.to_string(), value.trim().to_string());
+        }
+    }
+    for (_, variable) in ENVIRONMENT {
+        if let Ok(value) = env::var(variable) {
+            values.insert(variable.to_string(), value);
+        } else if let Ok(path) = env::var(format!("{variable}_FILE")) {
+            let value = fs::read_to_string(&path)
+                .wrap_err_with(|| format!("failed to read {path} named by {variable}_FILE"))?;
+            values.insert(variable.to_string(), value.trim_end().to_string());
+        }
+    }
+    let mut options = Vec::new();
+    for (option, variable) in ENVIRONMENT {
+        let Some(value) = values.remove(variable) else {
+            continue;
+        };
+        if REPEATED.contains(&option) {
+            for value in value.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+                options.extend([format!("--{option}"), value.to_string()]);
+            }
+        } else {
+            options.extend([format!("--{option}"), value]);
+        }
+    }
+    Ok(options)
+}
@@ -0,0 +1,132 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use eyre::Result;
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::{
+    http::{self, Request, Response},
+    sink::SinkStatus,
+};
+
+/// A change requested through the control API, applied by the main loop.
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Resize the window to the given number of blocks.
+    Window(u64),
+    /// Enable or disable the sink at the index.
+    Sink { index: usize, enabled: bool },
+    /// Write the latest snapshot (and the history, if kept) to a file.
+    Dump,
+    /// Move the recordings aside and start new ones.
+    Rotate,
+}
+
+/// The state of the running instance reported at `/status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    pub window: u64,
+    pub block_number: Option<u64>,
+    pub sinks: Vec<SinkStatus>,
+}
+
+/// Serves a JSON API to change a long-running instance without restarting it.
+///
+/// The commands are queued and applied by the main loop; their outcome is logged to the journal
+/// and shows in `/status`.
+pub struct Control {
+    commands: mpsc::UnboundedReceiver<Command>,
+    status: Arc<Mutex<Status>>,
+}
+
+impl Control {
+    /// Serve the API on `addr`:
+    ///
+    /// - `GET /status`
+    /// - `POST /window?blocks=<n>`
+    /// - `POST /sinks/<index>/enable` and `POST /sinks/<index>/disable`
+    /// - `POST /dump`
+    /// - `POST /rotate`
+    pub async fn serve(addr: SocketAddr) -> Result<Self> {
+        let (sender, commands) = mpsc::unbounded_channel();
+        let status = Arc::new(Mutex::new(Status::default()));
+        let served = status.clone();
+        http::serve(
+            addr,
+            Arc::new(move |request| {
+                if request.method == "GET" && request.path == "/status" {
+                    return match serde_json::to_vec(&*served.lock().unwrap()) {
+                        Ok(json) => Response::ok("application/json", json),
+                        Err(err) => Response::internal_error(format!("{err}\n")),
+                    };
+                }
+                if request.method != "POST" {
+                    return error(405, "expected GET /status or a POST command");
+                }
+                match parse(&request) {
+                    Ok(command) => {
+                        let body = json!({ "accepted": format!("{command:?}") }).to_string();
+                        match sender.send(command) {
+                            Ok(()) => Response::status(202, "application/json", body),
+                            Err(_) => Response::internal_error("telescope is stopping\n"),
+                        }
+                    }
+                    Err(response) => response,
+                }
+            }),
+        )
+        .await?;
+        Ok(Self { commands, status })
+    }
+
+    /// Wait for the next command.
+    pub async fn next(&mut self) -> Option<Command> {
+        self.commands.recv().await
+    }
+
+    /// Update the state reported at `/status`.
+    pub fn set_status(&self, status: Status) {
+        *self.status.lock().unwrap() = status;
+    }
+}
+
+/// Parse the command of a POST request.
+fn parse(request: &Request) -> Result<Command, Response> {
+    let segments = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    match segments.as_slice() {
+        ["window"] => match request.param("blocks").map(str::parse::<u64>) {
+            Some(Ok(blocks)) if blocks >= 2 => Ok(Command::Window(blocks)),
+            _ => Err(error(400, "expected ?blocks=<n> of at least 2")),
+        },
+        ["sinks", index, action] => {
+            let index = index
+                .parse()
+                .map_err(|_| error(400, "expected a sink index"))?;
+            let enabled = match *action {
+                "enable" => true,
+                "disable" => false,
+                _ => return Err(error(400, "expected enable or disable")),
+            };
+            Ok(Command::Sink { index, enabled })
+        }
+        ["dump"] => Ok(Command::Dump),
+        ["rotate"] => Ok(Command::Rotate),
+        _ => Err(Response::not_found()),
+    }
+}
+
+fn error(status: u16, message: &str) -> Response {
+    Response::status(
+        status,
+        "application/json",
+        json!({ "error": message }).to_string(),
+    )
+}
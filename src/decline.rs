@@ -0,0 +1,128 @@
+use std::{collections::VecDeque, str::FromStr};
+
+use eyre::{eyre, Result};
+
+use crate::{
+    journal::{Journal, Severity},
+    measurement::Snapshot,
+};
+
+/// When a slow decline counts, given on the command line as `<windows>,<percent>`: a metric
+/// worsening in every one of that many consecutive windows, by more than the percentage in total.
+#[derive(Debug, Clone, Copy)]
+pub struct DeclineRule {
+    pub windows: usize,
+    pub percent: f64,
+}
+
+impl FromStr for DeclineRule {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || eyre!("invalid decline rule `{s}`, expected <windows>,<percent>");
+        let (windows, percent) = s.split_once(',').ok_or_else(invalid)?;
+        let windows = windows.trim().parse::<usize>().map_err(|_| invalid())?;
+        let percent = percent.trim().parse::<f64>().map_err(|_| invalid())?;
+        if windows < 2 || percent <= 0.0 {
+            return Err(eyre!(
+                "invalid decline rule `{s}`, expected at least 2 windows and a positive percentage"
+            ));
+        }
+        Ok(Self { windows, percent })
+    }
+}
+
+/// A watched metric, sampled once per window.
+struct Watched {
+    name: &'static str,
+    /// Whether a higher value is worse, as for intervals.
+    higher_is_worse: bool,
+    value: fn(&Snapshot) -> Option<f64>,
+    samples: VecDeque<f64>,
+    /// Whether the current decline was already logged.
+    alerted: bool,
+}
+
+/// Logs a warning when the performance degrades slowly, e.g. from a leak, while every window
+/// still looks fine on its own.
+pub struct DeclineWatch {
+    rule: DeclineRule,
+    journal: Journal,
+    watched: Vec<Watched>,
+    /// The block the last samples were taken at.
+    sampled_at: Option<u64>,
+}
+
+impl DeclineWatch {
+    pub fn new(rule: DeclineRule, journal: Journal) -> Self {
+        let watched = |name, higher_is_worse, value| Watched {
+            name,
+            higher_is_worse,
+            value,
+            samples: VecDeque::with_capacity(rule.windows + 2),
+            alerted: false,
+        };
+        Self {
+            rule,
+            journal,
+            watched: vec![
+                watched("TPS", false, |s| s.metrics.transactions_per_second),
+                watched("Gas rate", false, |s| Some(s.metrics.gas_per_second)),
+                watched("Mini-block interval", true, |s| {
+                    Some(s.metrics.mini_block_interval_ms)
+                }),
+            ],
+            sampled_at: None,
+        }
+    }
+
+    /// Sample the metrics once a window's worth of new blocks was recorded, and log the metrics
+    /// which worsened in each of the last windows.
+    pub fn observe(&mut self, snapshot: &Snapshot) {
+        let window = snapshot.metrics.window_blocks.max(1) as u64;
+        if self
+            .sampled_at
+            .is_some_and(|block| snapshot.block_number < block + window)
+        {
+            return;
+        }
+        self.sampled_at = Some(snapshot.block_number);
+        for watched in &mut self.watched {
+            let Some(value) = (watched.value)(snapshot).filter(|value| value.is_finite()) else {
+                continue;
+            };
+            watched.samples.push_back(value);
+            // The declines between the windows need one more sample than windows.
+            if watched.samples.len() > self.rule.windows + 1 {
+                watched.samples.pop_front();
+            }
+            let worse = |previous: f64, next: f64| match watched.higher_is_worse {
+                true => next > previous,
+                false => next < previous,
+            };
+            let declining = watched.samples.len() > self.rule.windows
+                && watched
+                    .samples
+                    .iter()
+                    .zip(watched.samples.iter().skip(1))
+                    .all(|(&previous, &next)| worse(previous, next));
+            if !declining {
+                watched.alerted = false;
+                continue;
+            }
+            let (first, last) = (watched.samples[0], value);
+            let change = (last - first).abs() / first.abs().max(f64::EPSILON) * 100.0;
+            if change > self.rule.percent && !watched.alerted {
+                watched.alerted = true;
+                self.journal.log(
+                    Severity::Warn,
+                    "decline",
+                    format!(
+                        "{} worsened in {} consecutive windows, by {change:.1}% from {first:.1} to {last:.1}",
+                        watched.name, self.rule.windows
+                    ),
+                );
+            }
+        }
+    }
+}
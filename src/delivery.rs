@@ -0,0 +1,142 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use alloy::{network::AnyRpcBlock, primitives::B256};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::Serialize;
+
+/// Number of recent block hashes remembered to recognize their later deliveries.
+const SEEN: usize = 1024;
+
+/// Merges the block subscriptions of several providers into one stream of distinct blocks,
+/// counting which provider delivered every block first.
+#[derive(Clone)]
+pub struct FirstDelivery {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    providers: Vec<Provider>,
+    /// The recent blocks and when they were first delivered.
+    seen: HashMap<B256, Instant>,
+    order: VecDeque<B256>,
+}
+
+#[derive(Default)]
+struct Provider {
+    label: String,
+    first: u64,
+    delivered: u64,
+    /// The summed delay of the deliveries behind the first one, in milliseconds.
+    behind_ms: f64,
+}
+
+/// How often a provider delivered the blocks first, a measure of its quality as a data source.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderDelivery {
+    pub provider: String,
+    /// The blocks the provider delivered first.
+    pub first: u64,
+    /// The blocks the provider delivered at all.
+    pub delivered: u64,
+    /// The share of all distinct blocks the provider delivered first, in percent.
+    pub win_rate: f64,
+    /// The average delay of the provider's deliveries behind the first one, in milliseconds.
+    pub mean_behind_ms: Option<f64>,
+}
+
+impl FirstDelivery {
+    /// Merge the subscriptions, labeled by their provider, into one stream yielding every block
+    /// hash once, at its first delivery.
+    pub fn merge(
+        subscriptions: Vec<(String, BoxStream<'static, AnyRpcBlock>)>,
+    ) -> (Self, BoxStream<'static, AnyRpcBlock>) {
+        let providers = subscriptions
+            .iter()
+            .map(|(label, _)| Provider {
+                label: label.clone(),
+                ..Provider::default()
+            })
+            .collect();
+        let delivery = Self {
+            state: Arc::new(Mutex::new(State {
+                providers,
+                seen: HashMap::with_capacity(SEEN),
+                order: VecDeque::with_capacity(SEEN),
+            })),
+        };
+        let merged = stream::select_all(
+            subscriptions
+                .into_iter()
+                .enumerate()
+                .map(|(index, (_, blocks))| blocks.map(move |block| (index, block))),
+        );
+        let first = merged.filter_map({
+            let delivery = delivery.clone();
+            move |(index, block)| {
+                let first = delivery.deliver(index, block.header.hash);
+                async move { first.then_some(block) }
+            }
+        });
+        (delivery, first.boxed())
+    }
+
+    /// Count a delivery of a block, returning whether it was the first.
+    fn deliver(&self, index: usize, hash: B256) -> bool {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.providers[index].delivered += 1;
+        if let Some(&first) = state.seen.get(&hash) {
+            state.providers[index].behind_ms += (now - first).as_secs_f64() * 1000.0;
+            return false;
+        }
+        state.providers[index].first += 1;
+        state.seen.insert(hash, now);
+        state.order.push_back(hash);
+        if state.order.len() > SEEN {
+            if let Some(oldest) = state.order.pop_front() {
+                state.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Collect the first deliveries of every provider since the start.
+    pub fn metrics(&self) -> Vec<ProviderDelivery> {
+        let state = self.state.lock().unwrap();
+        let blocks = state.providers.iter().map(|p| p.first).sum::<u64>();
+        state
+            .providers
+            .iter()
+            .map(|provider| {
+                let behind = provider.delivered - provider.first;
+                ProviderDelivery {
+                    provider: provider.label.clone(),
+                    first: provider.first,
+                    delivered: provider.delivered,
+                    win_rate: match blocks {
+                        0 => 0.0,
+                        blocks => 100.0 * provider.first as f64 / blocks as f64,
+                    },
+                    mean_behind_ms: (behind > 0).then(|| provider.behind_ms / behind as f64),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Label a provider by the host and port of its endpoint, keeping any credentials or keys in the
+/// URL out of the metrics.
+pub fn label(endpoint: &str) -> String {
+    match reqwest::Url::parse(endpoint) {
+        Ok(url) => match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => endpoint.to_string(),
+        },
+        Err(_) => endpoint.to_string(),
+    }
+}
@@ -0,0 +1,210 @@
+use std::str::FromStr;
+
+use eyre::{eyre, Result};
+
+/// A derived metric given on the command line as `<name> = <expression>`.
+///
+/// The expression combines numbers and the (flattened) snapshot fields with `+`, `-`, `*`, `/` and
+/// parentheses, e.g. `gas_per_tx = gas_per_second / transactions_per_second`.
+#[derive(Debug, Clone)]
+pub struct Derived {
+    pub name: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64),
+    Field(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl FromStr for Derived {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, expr) = s
+            .split_once('=')
+            .ok_or_else(|| eyre!("invalid derived metric `{s}`, expected <name> = <expression>"))?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(eyre!("invalid derived metric name `{name}`"));
+        }
+        let mut parser = Parser {
+            input: expr.as_bytes(),
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.input.len() {
+            let rest = String::from_utf8_lossy(&parser.input[parser.pos..]);
+            return Err(eyre!("unexpected `{rest}` in `{s}`"));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            expr,
+        })
+    }
+}
+
+impl Derived {
+    /// Evaluate the expression, looking up the fields with `field`.
+    ///
+    /// Returns `None` if a field is unknown or the result is not finite, e.g. after a division by
+    /// zero.
+    pub fn evaluate(&self, field: &impl Fn(&str) -> Option<f64>) -> Option<f64> {
+        self.expr.evaluate(field).filter(|value| value.is_finite())
+    }
+}
+
+impl Expr {
+    fn evaluate(&self, field: &impl Fn(&str) -> Option<f64>) -> Option<f64> {
+        Some(match self {
+            Self::Number(n) => *n,
+            Self::Field(name) => field(name)?,
+            Self::Neg(expr) => -expr.evaluate(field)?,
+            Self::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(field)?, rhs.evaluate(field)?);
+                match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div => lhs / rhs,
+                }
+            }
+        })
+    }
+}
+
+/// A recursive descent parser of the usual arithmetic precedence.
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    /// `expr := term (('+' | '-') term)*`
+    fn expr(&mut self) -> Result<Expr> {
+        let mut lhs = self.term()?;
+        while let Some(op) = self.operator(&[(b'+', Op::Add), (b'-', Op::Sub)]) {
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    /// `term := factor (('*' | '/') factor)*`
+    fn term(&mut self) -> Result<Expr> {
+        let mut lhs = self.factor()?;
+        while let Some(op) = self.operator(&[(b'*', Op::Mul), (b'/', Op::Div)]) {
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.factor()?));
+        }
+        Ok(lhs)
+    }
+
+    /// `factor := number | field | '-' factor | '(' expr ')'`
+    fn factor(&mut self) -> Result<Expr> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.input.get(self.pos) {
+            Some(b'-') => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some(b'(') => {
+                self.pos += 1;
+                let expr = self.expr()?;
+                self.skip_whitespace();
+                if self.input.get(self.pos) != Some(&b')') {
+                    return Err(eyre!("missing `)` at position {}", self.pos));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() || *c == b'.' => {
+                self.take_while(|c| c.is_ascii_digit() || c == b'.');
+                let number = std::str::from_utf8(&self.input[start..self.pos])?;
+                Ok(Expr::Number(number.parse()?))
+            }
+            Some(c) if c.is_ascii_alphabetic() || *c == b'_' => {
+                self.take_while(|c| c.is_ascii_alphanumeric() || c == b'_');
+                let name = std::str::from_utf8(&self.input[start..self.pos])?;
+                Ok(Expr::Field(name.to_string()))
+            }
+            _ => Err(eyre!("expected a number, field or `(` at position {start}")),
+        }
+    }
+
+    /// Consume one of the given operators if it comes next.
+    fn operator(&mut self, ops: &[(u8, Op)]) -> Option<Op> {
+        self.skip_whitespace();
+        let next = self.input.get(self.pos)?;
+        let (_, op) = ops.iter().find(|(c, _)| c == next)?;
+        self.pos += 1;
+        Some(*op)
+    }
+
+    #[inline]
+    fn skip_whitespace(&mut self) {
+        self.take_while(|c| c.is_ascii_whitespace());
+    }
+
+    #[inline]
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) {
+        while self.input.get(self.pos).is_some_and(|&c| f(c)) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evaluate a derived metric over fields `a` = 2, `b` = 3 and `zero` = 0.
+    fn evaluate(s: &str) -> Option<f64> {
+        let derived = s.parse::<Derived>().unwrap();
+        derived.evaluate(&|name| match name {
+            "a" => Some(2.0),
+            "b" => Some(3.0),
+            "zero" => Some(0.0),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn evaluates_with_precedence_and_parentheses() {
+        assert_eq!(evaluate("x = a + b * 4"), Some(14.0));
+        assert_eq!(evaluate("x = (a + b) * 4"), Some(20.0));
+        assert_eq!(evaluate("x = b - a - 1"), Some(0.0));
+        assert_eq!(evaluate("x = 12 / b / a"), Some(2.0));
+        assert_eq!(evaluate("x = -a * b"), Some(-6.0));
+        assert_eq!(evaluate("x = b - -a"), Some(5.0));
+        assert_eq!(evaluate("x = -(a + b)"), Some(-5.0));
+        assert_eq!(evaluate("x=0.5*a"), Some(1.0));
+    }
+
+    #[test]
+    fn unknown_fields_and_division_by_zero_are_unknown() {
+        assert_eq!(evaluate("x = a + missing"), None);
+        assert_eq!(evaluate("x = a / zero"), None);
+        assert_eq!(evaluate("x = zero / zero"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for s in [
+            "a = (b", "a = b +", "a b = c", "a = b c", "= b", "a", "a = ", "a = 1..2",
+        ] {
+            assert!(s.parse::<Derived>().is_err(), "accepted `{s}`");
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::VecDeque, str::FromStr, time::Instant};
+
+use alloy::{
+    network::AnyTransactionReceipt,
+    primitives::{Address, B256},
+};
+use eyre::{eyre, Result};
+use serde::Serialize;
+
+/// Number of recent occurrences the interval of a watched event is averaged over.
+const OCCURRENCES: usize = 16;
+
+/// An event given on the command line as `<address>:<topic0>`.
+#[derive(Debug, Clone, Copy)]
+pub struct EventSpec {
+    pub address: Address,
+    pub topic0: B256,
+}
+
+impl FromStr for EventSpec {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, topic0) = s
+            .split_once(':')
+            .ok_or_else(|| eyre!("invalid event `{s}`, expected <address>:<topic0>"))?;
+        Ok(Self {
+            address: address.parse()?,
+            topic0: topic0.parse()?,
+        })
+    }
+}
+
+/// Measures the cadence of a recurring event, e.g. an oracle update.
+pub struct EventWatch {
+    spec: EventSpec,
+    /// The times of the recent blocks which emitted the event.
+    occurrences: VecDeque<Instant>,
+    /// Number of times the event was emitted since the start.
+    total: u64,
+}
+
+/// The cadence of the watched event.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventMetrics {
+    /// Average interval between two blocks emitting the event in milliseconds.
+    pub interval_ms: Option<f64>,
+    /// Blocks emitting the event per minute.
+    pub rate_per_minute: Option<f64>,
+    pub occurrences: u64,
+}
+
+impl EventWatch {
+    pub fn new(spec: EventSpec) -> Self {
+        Self {
+            spec,
+            occurrences: VecDeque::with_capacity(OCCURRENCES + 1),
+            total: 0,
+        }
+    }
+
+    /// Record the receipts of a block received at `timestamp`.
+    #[inline]
+    pub fn record(&mut self, timestamp: Instant, receipts: &[AnyTransactionReceipt]) {
+        let emitted = receipts
+            .iter()
+            .flat_map(|r| r.inner.inner.logs())
+            .filter(|log| {
+                log.address() == self.spec.address
+                    && log.topics().first() == Some(&self.spec.topic0)
+            })
+            .count();
+        if emitted == 0 {
+            return;
+        }
+        self.total += emitted as u64;
+        self.occurrences.push_back(timestamp);
+        if self.occurrences.len() > OCCURRENCES {
+            self.occurrences.pop_front();
+        }
+    }
+
+    /// Forget the recorded occurrences.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.occurrences.clear();
+        self.total = 0;
+    }
+
+    /// Collect the event metrics; the cadence is unknown until the event was seen twice.
+    #[inline]
+    pub fn metrics(&self) -> EventMetrics {
+        let interval = match (self.occurrences.front(), self.occurrences.back()) {
+            (Some(first), Some(last)) if self.occurrences.len() > 1 => {
+                Some((*last - *first).as_secs_f64() * 1000.0 / (self.occurrences.len() - 1) as f64)
+            }
+            _ => None,
+        };
+        EventMetrics {
+            interval_ms: interval,
+            rate_per_minute: interval.map(|interval| 60_000.0 / interval),
+            occurrences: self.total,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy::{eips::BlockNumberOrTag, network::AnyRpcHeader, providers::Provider};
+use serde::Serialize;
+
+use crate::{
+    journal::{Journal, Severity},
+    rpc::RpcClient,
+};
+
+/// Number of observed base fees kept to compare with the fee history.
+const OBSERVED: usize = 1024;
+
+/// Polls `eth_feeHistory` for the base fee trajectory and the priority fee percentiles, and
+/// checks the node's base fees against the ones seen in the subscribed headers.
+pub struct FeeHistory {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The base fees of the recorded blocks, by block number.
+    observed: VecDeque<(u64, u64)>,
+    /// The latest block whose base fee was compared.
+    checked: u64,
+    mismatches: u64,
+    metrics: Option<FeeMetrics>,
+}
+
+/// The fee history of the latest blocks as of the latest poll.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeMetrics {
+    /// The base fee of the next block.
+    pub base_fee_gwei: f64,
+    /// The change of the base fee over the polled blocks.
+    pub base_fee_change_percentage: f64,
+    /// The mean gas used ratio of the polled blocks.
+    pub gas_used_ratio: f64,
+    /// The mean priority fee of the polled blocks by percentile, e.g. `p50` or `p12_5`.
+    pub reward_gwei: BTreeMap<String, f64>,
+    /// Blocks whose base fee in the fee history differs from the subscribed header.
+    pub mismatched_blocks: u64,
+    /// The base fees of the polled blocks and the next one, for the TUI trend.
+    #[serde(skip)]
+    pub trajectory_gwei: Vec<f64>,
+}
+
+impl FeeHistory {
+    /// Poll the fee history of the latest `blocks` every `interval`.
+    pub fn spawn(
+        rpc: Arc<RpcClient>,
+        interval: Duration,
+        blocks: u64,
+        percentiles: Vec<f64>,
+        journal: Journal,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let polled = state.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // Log only when the polling starts failing or recovers, not on every poll.
+            let mut failing = false;
+            loop {
+                ticker.tick().await;
+                let percentiles = &percentiles;
+                let history = rpc
+                    .request("eth_feeHistory", |provider| async move {
+                        provider
+                            .get_fee_history(blocks, BlockNumberOrTag::Latest, percentiles)
+                            .await
+                    })
+                    .await;
+                let history = match history {
+                    Ok(history) => history,
+                    Err(err) => {
+                        if !std::mem::replace(&mut failing, true) {
+                            journal.log(
+                                Severity::Warn,
+                                "fees",
+                                format!("Failed to get the fee history: {err}"),
+                            );
+                        }
+                        continue;
+                    }
+                };
+                if std::mem::take(&mut failing) {
+                    journal.log(Severity::Info, "fees", "Got the fee history again");
+                }
+
+                let mut state = polled.lock().unwrap();
+                let fees = history
+                    .base_fee_per_gas
+                    .iter()
+                    .take(history.gas_used_ratio.len());
+                for (number, &base_fee) in (history.oldest_block..).zip(fees) {
+                    if number <= state.checked {
+                        continue;
+                    }
+                    let observed = state
+                        .observed
+                        .iter()
+                        .find(|(observed, _)| *observed == number)
+                        .map(|(_, base_fee)| *base_fee as u128);
+                    let Some(observed) = observed else {
+                        continue;
+                    };
+                    state.checked = number;
+                    if observed != base_fee {
+                        state.mismatches += 1;
+                        journal.log(
+                            Severity::Warn,
+                            "fees",
+                            format!(
+                                "Base fee of block {number} is {base_fee} wei in the fee history \
+                                 but {observed} wei in its header"
+                            ),
+                        );
+                    }
+                }
+
+                let trajectory_gwei = history
+                    .base_fee_per_gas
+                    .iter()
+                    .map(|&fee| gwei(fee))
+                    .collect::<Vec<_>>();
+                let (Some(&first), Some(&last)) = (trajectory_gwei.first(), trajectory_gwei.last())
+                else {
+                    continue;
+                };
+                let rewards = history.reward.unwrap_or_default();
+                let reward_gwei = percentiles
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, percentile)| {
+                        let fees = rewards
+                            .iter()
+                            .filter_map(|block| block.get(i))
+                            .collect::<Vec<_>>();
+                        let mean = fees.iter().map(|&&fee| gwei(fee)).sum::<f64>()
+                            / fees.len().max(1) as f64;
+                        // A key like `p12_5` stays a valid metric name once flattened.
+                        let key = format!("p{percentile}").replace('.', "_");
+                        (!fees.is_empty()).then_some((key, mean))
+                    })
+                    .collect();
+                state.metrics = Some(FeeMetrics {
+                    base_fee_gwei: last,
+                    base_fee_change_percentage: if first > 0.0 {
+                        100.0 * (last - first) / first
+                    } else {
+                        0.0
+                    },
+                    gas_used_ratio: history.gas_used_ratio.iter().sum::<f64>()
+                        / history.gas_used_ratio.len().max(1) as f64,
+                    reward_gwei,
+                    mismatched_blocks: state.mismatches,
+                    trajectory_gwei,
+                });
+            }
+        });
+        Self { state }
+    }
+
+    /// Remember the base fee of a recorded header to check the fee history against.
+    pub fn observe(&self, header: &AnyRpcHeader) {
+        let Some(base_fee) = header.base_fee_per_gas else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        state.observed.push_back((header.number, base_fee));
+        if state.observed.len() > OBSERVED {
+            state.observed.pop_front();
+        }
+    }
+
+    /// Get the fee history as of the latest poll, if any succeeded.
+    pub fn metrics(&self) -> Option<FeeMetrics> {
+        self.state.lock().unwrap().metrics.clone()
+    }
+}
+
+#[inline]
+fn gwei(wei: u128) -> f64 {
+    wei as f64 / 1e9
+}
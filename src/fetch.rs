@@ -0,0 +1,272 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    network::{AnyRpcBlock, AnyRpcHeader, AnyTransactionReceipt},
+    primitives::BlockHash,
+    rpc::types::{serde_helpers::WithOtherFields, Block, BlockTransactions, BlockTransactionsKind},
+};
+use clap::ValueEnum;
+use eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    graphql::GraphQlClient,
+    rpc::RpcClient,
+    sampling::{Sampler, SamplingMetrics},
+};
+
+/// Number of retries for a subscribed block the node does not serve yet.
+const UNAVAILABLE_RETRIES: u32 = 5;
+
+/// Delay before the first retry of an unavailable block, doubled on every further attempt.
+const UNAVAILABLE_DELAY: Duration = Duration::from_millis(20);
+
+/// How much of every block is fetched from the node.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchMode {
+    /// Nothing beyond the subscribed header; the metrics only use its fields (gas, extra data).
+    None,
+    /// The block with its transaction hashes.
+    Hashes,
+    /// The block with its full transactions, enabling the transaction-level metrics.
+    Full,
+}
+
+/// What is fetched from the node for every block.
+#[derive(Debug, Clone, Copy)]
+pub struct FetchConfig {
+    /// Make no request for new blocks and use their subscribed headers alone.
+    pub headers_only: bool,
+    pub transactions: BlockTransactionsKind,
+    pub receipts: bool,
+    /// Count the transactions separately, as some providers truncate the transaction list.
+    pub count_transactions: bool,
+}
+
+/// What the block subscription delivers.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscribeMode {
+    /// Headers (`newHeads`), whose blocks are fetched separately.
+    Headers,
+    /// Full blocks (`newHeads` with `includeTransactions`), saving the fetch where the node
+    /// supports it. Nodes sending bare headers anyway fall back to fetching.
+    Full,
+}
+
+/// Turn a subscribed header into a block without transactions.
+#[inline]
+pub fn header_block(header: AnyRpcHeader) -> AnyRpcBlock {
+    WithOtherFields::new(Block::new(header, BlockTransactions::Uncle))
+}
+
+/// A block and the data fetched along with it.
+pub struct Fetched {
+    pub block: AnyRpcBlock,
+    pub receipts: Option<Vec<AnyTransactionReceipt>>,
+    /// The number of transactions counted apart from the block's transaction list, if enabled.
+    pub transaction_count: Option<usize>,
+    /// Whether the data the detailed analyses need was fetched, rather than skipped by sampling.
+    pub sampled: bool,
+}
+
+/// Fetches blocks and whatever the enabled metrics need about them.
+pub struct Fetcher {
+    rpc: Arc<RpcClient>,
+    config: FetchConfig,
+    /// Fetches the blocks instead of JSON-RPC when only transaction hashes are needed.
+    graphql: Option<GraphQlClient>,
+    /// Skips the full transactions and receipts of some subscribed blocks under load.
+    sampler: Option<Sampler>,
+    counters: Arc<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    unavailable: AtomicU64,
+    missing: AtomicU64,
+    /// The longest time a block took to become available, in microseconds.
+    max_delay: AtomicU64,
+}
+
+/// How often subscribed blocks were announced before the node served them, indicating that its
+/// indexing lags behind the subscription.
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexingStats {
+    /// Blocks which were not available at the first attempt.
+    pub unavailable_blocks: u64,
+    /// Blocks which were still not available after the retries, and were skipped.
+    pub missing_blocks: u64,
+    /// The longest time a block took to become available.
+    pub max_delay_ms: f64,
+}
+
+impl Fetcher {
+    pub fn new(rpc: Arc<RpcClient>, config: FetchConfig) -> Self {
+        Self {
+            rpc,
+            config,
+            graphql: None,
+            sampler: None,
+            counters: Arc::default(),
+        }
+    }
+
+    /// Fetch the blocks through the GraphQL API. Full transactions and receipts are still fetched
+    /// over JSON-RPC.
+    pub fn with_graphql(mut self, graphql: GraphQlClient) -> Self {
+        self.graphql = Some(graphql);
+        self
+    }
+
+    /// Sample the subscribed blocks whose full transactions and receipts are fetched once more
+    /// than `max_blocks_per_second` arrive.
+    pub fn with_sampling(mut self, max_blocks_per_second: f64) -> Self {
+        self.sampler = Some(Sampler::new(max_blocks_per_second));
+        self
+    }
+
+    /// Get how the subscribed blocks are sampled, if they are.
+    pub fn sampling(&self) -> Option<SamplingMetrics> {
+        self.sampler.as_ref().map(Sampler::metrics)
+    }
+
+    /// Get the transactions to fetch, only the hashes for a block skipped by sampling.
+    #[inline]
+    fn transactions(&self, sampled: bool) -> BlockTransactionsKind {
+        if sampled {
+            self.config.transactions
+        } else {
+            BlockTransactionsKind::Hashes
+        }
+    }
+
+    /// Get the GraphQL client if it can serve the blocks.
+    #[inline]
+    fn graphql(&self, transactions: BlockTransactionsKind) -> Option<&GraphQlClient> {
+        self.graphql
+            .as_ref()
+            .filter(|_| matches!(transactions, BlockTransactionsKind::Hashes))
+    }
+
+    /// Complete a subscribed block, fetching it again unless it already carries the configured
+    /// transactions or only headers are used.
+    pub async fn subscribed(&self, block: AnyRpcBlock) -> Result<Option<Fetched>> {
+        if self.config.headers_only {
+            let transaction_count = self.transaction_count(&block, None).await?;
+            return Ok(Some(Fetched {
+                block,
+                receipts: None,
+                transaction_count,
+                sampled: true,
+            }));
+        }
+        let sampled = self.sampler.as_ref().is_none_or(Sampler::sample);
+        let complete = match self.transactions(sampled) {
+            BlockTransactionsKind::Full => block.transactions.is_full(),
+            BlockTransactionsKind::Hashes => !block.transactions.is_uncle(),
+        };
+        if complete {
+            return Ok(Some(self.complete(block, sampled).await?));
+        }
+        // The header may be announced before the node indexed the block.
+        let started = Instant::now();
+        let mut delay = UNAVAILABLE_DELAY;
+        for retry in 0..=UNAVAILABLE_RETRIES {
+            if let Some(fetched) = self.fetch_by_hash(block.header.hash, sampled).await? {
+                if retry > 0 {
+                    let micros = started.elapsed().as_micros() as u64;
+                    self.counters.max_delay.fetch_max(micros, Ordering::Relaxed);
+                }
+                return Ok(Some(fetched));
+            }
+            if retry == 0 {
+                self.counters.unavailable.fetch_add(1, Ordering::Relaxed);
+            }
+            if retry < UNAVAILABLE_RETRIES {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+        self.counters.missing.fetch_add(1, Ordering::Relaxed);
+        Ok(None)
+    }
+
+    /// Get how often subscribed blocks were not yet available.
+    pub fn indexing_stats(&self) -> IndexingStats {
+        IndexingStats {
+            unavailable_blocks: self.counters.unavailable.load(Ordering::Relaxed),
+            missing_blocks: self.counters.missing.load(Ordering::Relaxed),
+            max_delay_ms: self.counters.max_delay.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+
+    /// Fetch the block with the given hash, with the data of the detailed analyses if sampled.
+    async fn fetch_by_hash(&self, hash: BlockHash, sampled: bool) -> Result<Option<Fetched>> {
+        let transactions = self.transactions(sampled);
+        let block = match self.graphql(transactions) {
+            Some(graphql) => graphql.get_block_by_hash(hash).await?,
+            None => self.rpc.get_block_by_hash(hash, transactions).await?,
+        };
+        match block {
+            Some(block) => Ok(Some(self.complete(block, sampled).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Fetch the block with the given number.
+    pub async fn by_number(&self, number: u64) -> Result<Option<Fetched>> {
+        let block = match self.graphql(self.config.transactions) {
+            Some(graphql) => graphql.get_block_by_number(number).await?,
+            None => {
+                self.rpc
+                    .get_block_by_number(number, self.config.transactions)
+                    .await?
+            }
+        };
+        match block {
+            Some(block) => Ok(Some(self.complete(block, true).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Fetch the data belonging to the block, without the receipts unless sampled.
+    async fn complete(&self, block: AnyRpcBlock, sampled: bool) -> Result<Fetched> {
+        let receipts = if self.config.receipts && sampled {
+            self.rpc.get_block_receipts(block.header.hash).await?
+        } else {
+            None
+        };
+        let transaction_count = self.transaction_count(&block, receipts.as_deref()).await?;
+        Ok(Fetched {
+            block,
+            receipts,
+            transaction_count,
+            sampled,
+        })
+    }
+
+    /// Count the transactions of the block if enabled, from its receipts when fetched.
+    async fn transaction_count(
+        &self,
+        block: &AnyRpcBlock,
+        receipts: Option<&[AnyTransactionReceipt]>,
+    ) -> Result<Option<usize>> {
+        if !self.config.count_transactions {
+            return Ok(None);
+        }
+        if let Some(receipts) = receipts {
+            return Ok(Some(receipts.len()));
+        }
+        let count = self
+            .rpc
+            .get_block_transaction_count_by_hash(block.header.hash)
+            .await?;
+        Ok(count.map(|count| count as usize))
+    }
+}
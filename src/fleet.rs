@@ -0,0 +1,262 @@
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use alloy::{providers::Provider, rpc::types::BlockTransactionsKind};
+use clap::Args;
+use eyre::{eyre, Result};
+use futures_util::StreamExt;
+use serde::Serialize;
+
+use crate::{
+    fetch::{header_block, FetchConfig, Fetcher},
+    measurement::{gas_rate, Decoded, Measurement},
+    proxy::{self, Proxy},
+    rpc::{RpcClient, RpcConfig},
+};
+
+/// How long to wait before reconnecting to an instance.
+const RECONNECT_AFTER: Duration = Duration::from_secs(5);
+
+/// Measure several MegaETH instances at once, e.g. testnets or shards, reporting every instance,
+/// every group of instances sharing a label, and the whole fleet.
+#[derive(Args, Debug)]
+pub struct FleetArgs {
+    /// An instance as `<label>=<endpoint>`; instances with the same label are summed up as a
+    /// group. Repeat for every instance.
+    #[arg(long = "instance", value_name = "LABEL=URL", required = true)]
+    instances: Vec<Instance>,
+
+    /// Reach the endpoints through a SOCKS5 or HTTP proxy, as with the main command.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<Proxy>,
+
+    /// The number of blocks every instance's rates are measured over.
+    #[arg(short, long, default_value_t = 64)]
+    window: u64,
+
+    /// How often to report, in seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 1.0)]
+    interval: f64,
+
+    /// Print every report as a JSON line instead of text.
+    #[arg(long)]
+    json: bool,
+}
+
+/// An endpoint of the fleet and the group it is summed up in.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    label: String,
+    endpoint: String,
+}
+
+impl FromStr for Instance {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((label, endpoint)) if !label.is_empty() && !endpoint.is_empty() => Ok(Self {
+                label: label.to_string(),
+                endpoint: endpoint.to_string(),
+            }),
+            _ => Err(eyre!("invalid instance `{s}`, expected <label>=<endpoint>")),
+        }
+    }
+}
+
+/// The latest throughput of an instance, unknown until it recorded two blocks.
+#[derive(Debug, Clone, Default, Serialize)]
+struct Throughput {
+    block: Option<u64>,
+    transactions_per_second: Option<f64>,
+    gas_per_second: Option<f64>,
+    connected: bool,
+}
+
+/// The throughput of an instance in a report.
+#[derive(Serialize)]
+struct InstanceReport<'a> {
+    label: &'a str,
+    endpoint: &'a str,
+    #[serde(flatten)]
+    throughput: Throughput,
+}
+
+/// The summed throughput of the connected instances of a group or of the fleet.
+#[derive(Debug, Default, Serialize)]
+struct Total {
+    instances: usize,
+    transactions_per_second: f64,
+    gas_per_second: f64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    instances: Vec<InstanceReport<'a>>,
+    groups: BTreeMap<&'a str, Total>,
+    fleet: Total,
+}
+
+impl Total {
+    fn add(&mut self, throughput: &Throughput) {
+        if !throughput.connected {
+            return;
+        }
+        self.instances += 1;
+        self.transactions_per_second += throughput.transactions_per_second.unwrap_or(0.0);
+        self.gas_per_second += throughput.gas_per_second.unwrap_or(0.0);
+    }
+
+    fn line(&self, name: &str) -> String {
+        format!(
+            "{name}: {} instances, TPS: {:>8.1}, Gas: {}",
+            self.instances,
+            self.transactions_per_second,
+            gas_rate(self.gas_per_second)
+        )
+    }
+}
+
+/// Watch the instances until interrupted, reporting their throughput every interval.
+pub async fn run(args: FleetArgs) -> Result<()> {
+    let states = args
+        .instances
+        .iter()
+        .map(|instance| {
+            let state = Arc::new(Mutex::new(Throughput::default()));
+            tokio::spawn(watch(
+                instance.clone(),
+                args.proxy.clone(),
+                args.window,
+                state.clone(),
+            ));
+            state
+        })
+        .collect::<Vec<_>>();
+
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(args.interval.max(0.1)));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+        let mut report = Report {
+            instances: Vec::with_capacity(states.len()),
+            groups: BTreeMap::new(),
+            fleet: Total::default(),
+        };
+        for (instance, state) in args.instances.iter().zip(&states) {
+            let throughput = state.lock().unwrap().clone();
+            report
+                .groups
+                .entry(instance.label.as_str())
+                .or_default()
+                .add(&throughput);
+            report.fleet.add(&throughput);
+            report.instances.push(InstanceReport {
+                label: &instance.label,
+                endpoint: &instance.endpoint,
+                throughput,
+            });
+        }
+        if args.json {
+            println!("{}", serde_json::to_string(&report)?);
+            continue;
+        }
+        for instance in &report.instances {
+            let throughput = &instance.throughput;
+            match (throughput.connected, throughput.block) {
+                (false, _) => println!("[{}] {}: disconnected", instance.label, instance.endpoint),
+                (true, None) => println!("[{}] {}: waiting", instance.label, instance.endpoint),
+                (true, Some(block)) => println!(
+                    "[{}] {}: Block: {block}, TPS: {}, Gas: {}",
+                    instance.label,
+                    instance.endpoint,
+                    throughput
+                        .transactions_per_second
+                        .map_or("-".to_string(), |tps| format!("{tps:.1}")),
+                    gas_rate(throughput.gas_per_second.unwrap_or(0.0)).trim_start()
+                ),
+            }
+        }
+        // A group of one is already its instance.
+        if report.groups.len() < report.instances.len() {
+            for (label, total) in &report.groups {
+                println!("{}", total.line(&format!("[{label}]")));
+            }
+        }
+        println!("{}", report.fleet.line("Fleet"));
+    }
+}
+
+/// Measure an instance, reconnecting whenever its subscription fails or ends.
+async fn watch(
+    instance: Instance,
+    proxy: Option<Proxy>,
+    window: u64,
+    state: Arc<Mutex<Throughput>>,
+) {
+    loop {
+        if let Err(err) = measure(&instance, proxy.clone(), window, &state).await {
+            eprintln!("[{}] {}: {err:#}", instance.label, instance.endpoint);
+        }
+        state.lock().unwrap().connected = false;
+        tokio::time::sleep(RECONNECT_AFTER).await;
+    }
+}
+
+async fn measure(
+    instance: &Instance,
+    proxy: Option<Proxy>,
+    window: u64,
+    state: &Mutex<Throughput>,
+) -> Result<()> {
+    let provider = proxy::provider(instance.endpoint.clone(), proxy).await?;
+    let rpc = Arc::new(RpcClient::new(provider, RpcConfig::default()));
+    let fetcher = Fetcher::new(
+        rpc.clone(),
+        FetchConfig {
+            headers_only: false,
+            transactions: BlockTransactionsKind::Hashes,
+            receipts: false,
+            count_transactions: false,
+        },
+    );
+    let mut blocks = rpc.provider().subscribe_blocks().await?.into_stream();
+    // Rates across a reconnect would count the missed blocks' time without their throughput.
+    let mut measurement = Measurement::new(window);
+    state.lock().unwrap().connected = true;
+    while let Some(header) = blocks.next().await {
+        let received = Instant::now();
+        let received_at = SystemTime::now();
+        let number = header.number;
+        let fetched = match fetcher.subscribed(header_block(header)).await {
+            Ok(Some(fetched)) => fetched,
+            Ok(None) => continue,
+            Err(err) => {
+                eprintln!("[{}] Failed to get block {number}: {err}", instance.label);
+                continue;
+            }
+        };
+        if !measurement.record(
+            Decoded::new(fetched),
+            received,
+            Some(Instant::now()),
+            received_at,
+        ) {
+            continue;
+        }
+        let metrics = (measurement.buffer_len() > 1).then(|| measurement.metrics());
+        *state.lock().unwrap() = Throughput {
+            block: Some(number),
+            transactions_per_second: metrics.as_ref().and_then(|m| m.transactions_per_second),
+            gas_per_second: metrics.map(|m| m.gas_per_second),
+            connected: true,
+        };
+    }
+    Err(eyre!("the block subscription ended"))
+}
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+use eyre::{eyre, Result};
+use serde::Serialize;
+
+/// The boundaries classifying the gaps between consecutive blocks, given on the command line as
+/// `<slow ms>,<stall ms>`: gaps from the first are slow, gaps from the second stalls.
+#[derive(Debug, Clone, Copy)]
+pub struct GapClasses {
+    pub slow_ms: f64,
+    pub stall_ms: f64,
+}
+
+/// The class of a gap between two blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gap {
+    Normal,
+    Slow,
+    Stall,
+}
+
+/// The number of gaps per class.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct GapCounts {
+    pub normal: u64,
+    pub slow: u64,
+    pub stall: u64,
+}
+
+/// How often the chain was noticeably slow.
+#[derive(Debug, Clone, Serialize)]
+pub struct GapMetrics {
+    pub slow_ms: f64,
+    pub stall_ms: f64,
+    /// The gaps between the blocks of the window.
+    pub window: GapCounts,
+    /// The gaps between the live blocks since the start.
+    pub total: GapCounts,
+}
+
+impl FromStr for GapClasses {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || eyre!("invalid gap classes `{s}`, expected <slow ms>,<stall ms>");
+        let (slow, stall) = s.split_once(',').ok_or_else(invalid)?;
+        let slow_ms = slow.trim().parse::<f64>().map_err(|_| invalid())?;
+        let stall_ms = stall.trim().parse::<f64>().map_err(|_| invalid())?;
+        if !(0.0 < slow_ms && slow_ms < stall_ms) {
+            return Err(eyre!(
+                "invalid gap classes `{s}`, the slow boundary must be below the stall boundary"
+            ));
+        }
+        Ok(Self { slow_ms, stall_ms })
+    }
+}
+
+impl GapClasses {
+    /// Classify a gap.
+    #[inline]
+    pub fn classify(&self, gap_ms: f64) -> Gap {
+        if gap_ms >= self.stall_ms {
+            Gap::Stall
+        } else if gap_ms >= self.slow_ms {
+            Gap::Slow
+        } else {
+            Gap::Normal
+        }
+    }
+}
+
+impl GapCounts {
+    /// Count a gap.
+    #[inline]
+    pub fn add(&mut self, gap: Gap) {
+        match gap {
+            Gap::Normal => self.normal += 1,
+            Gap::Slow => self.slow += 1,
+            Gap::Stall => self.stall += 1,
+        }
+    }
+}
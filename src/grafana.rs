@@ -0,0 +1,64 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+use crate::journal::{Journal, Severity};
+
+/// Pushes telescope events as annotations to Grafana, so they show up on the timelines of the
+/// existing dashboards.
+#[derive(Clone)]
+pub struct Grafana {
+    http: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    /// Logs the failed pushes, which the journal does not push again.
+    journal: Journal,
+}
+
+impl Grafana {
+    pub fn new(url: String, token: Option<String>, journal: Journal) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            token,
+            journal,
+        }
+    }
+
+    /// Check that Grafana accepts the token by reading the latest annotation.
+    pub async fn check(&self) -> reqwest::Result<()> {
+        let mut request = self
+            .http
+            .get(format!("{}/api/annotations", self.url))
+            .query(&[("limit", "1")]);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Create an annotation at the current time in the background.
+    pub fn annotate(&self, tag: &'static str, text: String) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut request = self.http.post(format!("{}/api/annotations", self.url));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let request =
+            request.json(&json!({ "time": time, "tags": ["telescope", tag], "text": text }));
+        let journal = self.journal.clone();
+        tokio::spawn(async move {
+            if let Err(err) = request.send().await.and_then(|r| r.error_for_status()) {
+                journal.log(
+                    Severity::Warn,
+                    "grafana",
+                    format!("Failed to push Grafana annotation: {err}"),
+                );
+            }
+        });
+    }
+}
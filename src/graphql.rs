@@ -0,0 +1,105 @@
+use alloy::{network::AnyRpcBlock, primitives::BlockHash};
+use eyre::{eyre, Result};
+use serde_json::{json, Map, Value};
+
+/// The block fields telescope needs, shaped like the JSON-RPC block object.
+const BLOCK_FIELDS: &str = "hash parent { hash } ommerHash miner { address } stateRoot \
+    transactionsRoot receiptsRoot logsBloom difficulty number gasLimit gasUsed timestamp \
+    extraData mixHash nonce baseFeePerGas transactions { hash }";
+
+/// Fetches blocks through the Ethereum GraphQL API (EIP-1767).
+pub struct GraphQlClient {
+    http: reqwest::Client,
+    endpoint: String,
+}
+
+impl GraphQlClient {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            endpoint,
+        }
+    }
+
+    /// Get a block by its hash, with transaction hashes only.
+    pub async fn get_block_by_hash(&self, hash: BlockHash) -> Result<Option<AnyRpcBlock>> {
+        self.block(
+            &format!("query($hash: Bytes32!) {{ block(hash: $hash) {{ {BLOCK_FIELDS} }} }}"),
+            json!({ "hash": hash }),
+        )
+        .await
+    }
+
+    /// Get a block by its number, with transaction hashes only.
+    pub async fn get_block_by_number(&self, number: u64) -> Result<Option<AnyRpcBlock>> {
+        self.block(
+            &format!("query($number: Long!) {{ block(number: $number) {{ {BLOCK_FIELDS} }} }}"),
+            json!({ "number": number }),
+        )
+        .await
+    }
+
+    async fn block(&self, query: &str, variables: Value) -> Result<Option<AnyRpcBlock>> {
+        let response: Value = self
+            .http
+            .post(&self.endpoint)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(errors) = response.get("errors") {
+            return Err(eyre!("GraphQL query failed: {errors}"));
+        }
+        match response.pointer("/data/block") {
+            Some(Value::Object(block)) => Ok(Some(serde_json::from_value(to_rpc_block(block))?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Reshape a GraphQL block into a JSON-RPC block object.
+fn to_rpc_block(block: &Map<String, Value>) -> Value {
+    let field = |name: &str| block.get(name).cloned().unwrap_or(Value::Null);
+    let address = |name: &str| block.get(name).and_then(|v| v.get("address")).cloned();
+    json!({
+        "hash": field("hash"),
+        "parentHash": block.get("parent").and_then(|p| p.get("hash")).cloned(),
+        "sha3Uncles": field("ommerHash"),
+        "miner": address("miner"),
+        "stateRoot": field("stateRoot"),
+        "transactionsRoot": field("transactionsRoot"),
+        "receiptsRoot": field("receiptsRoot"),
+        "logsBloom": field("logsBloom"),
+        "difficulty": quantity(&field("difficulty")),
+        "number": quantity(&field("number")),
+        "gasLimit": quantity(&field("gasLimit")),
+        "gasUsed": quantity(&field("gasUsed")),
+        "timestamp": quantity(&field("timestamp")),
+        "extraData": field("extraData"),
+        "mixHash": field("mixHash"),
+        "nonce": field("nonce"),
+        "baseFeePerGas": quantity(&field("baseFeePerGas")),
+        "uncles": [],
+        "transactions": block
+            .get("transactions")
+            .and_then(Value::as_array)
+            .map(|txs| txs.iter().filter_map(|tx| tx.get("hash").cloned()).collect::<Vec<_>>())
+            .unwrap_or_default(),
+    })
+}
+
+/// Normalize a GraphQL `Long`/`BigInt`, which nodes encode as decimal numbers or hex strings, into
+/// a JSON-RPC quantity.
+fn quantity(value: &Value) -> Value {
+    match value {
+        Value::Number(n) => n
+            .as_u64()
+            .map_or(Value::Null, |n| Value::String(format!("{n:#x}"))),
+        Value::String(s) if !s.starts_with("0x") => s
+            .parse::<u128>()
+            .map_or(Value::Null, |n| Value::String(format!("{n:#x}"))),
+        value => value.clone(),
+    }
+}
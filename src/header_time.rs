@@ -0,0 +1,59 @@
+use std::str::FromStr;
+
+use alloy::network::AnyRpcBlock;
+use eyre::{eyre, Result};
+use serde_json::Value;
+
+/// Where the headers of a chain carry their millisecond-precision time, given on the command line
+/// as `field:<name>` for an extra header field holding the milliseconds since the Unix epoch (as
+/// a number or hex quantity) or `extra-data:<offset>` for a big-endian 64-bit millisecond time at
+/// a byte offset of the extra data.
+#[derive(Debug, Clone)]
+pub enum HeaderTime {
+    Field(String),
+    ExtraData(usize),
+}
+
+impl FromStr for HeaderTime {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(name) = s.strip_prefix("field:").filter(|name| !name.is_empty()) {
+            Ok(Self::Field(name.to_string()))
+        } else if let Some(offset) = s.strip_prefix("extra-data:") {
+            Ok(Self::ExtraData(offset.parse()?))
+        } else {
+            Err(eyre!(
+                "invalid header time `{s}`, expected field:<name> or extra-data:<offset>"
+            ))
+        }
+    }
+}
+
+impl HeaderTime {
+    /// Decode the time of a block in milliseconds since the Unix epoch, unknown if the block does
+    /// not carry it or it disagrees with the header's seconds, e.g. for a wrong offset.
+    ///
+    /// Extra header fields are only known for fetched blocks, not for subscribed headers alone.
+    pub fn millis(&self, block: &AnyRpcBlock) -> Option<u64> {
+        self.decode(block)
+            .filter(|millis| (millis / 1000).abs_diff(block.header.timestamp) <= 1)
+    }
+
+    fn decode(&self, block: &AnyRpcBlock) -> Option<u64> {
+        match self {
+            Self::Field(name) => match block.other.get(name)? {
+                Value::Number(number) => number.as_u64(),
+                Value::String(quantity) => match quantity.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                    None => quantity.parse().ok(),
+                },
+                _ => None,
+            },
+            Self::ExtraData(offset) => {
+                let bytes = block.header.extra_data.get(*offset..*offset + 8)?;
+                Some(u64::from_be_bytes(bytes.try_into().ok()?))
+            }
+        }
+    }
+}
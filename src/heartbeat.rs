@@ -0,0 +1,65 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::journal::{Journal, Severity};
+
+/// Pings a dead man's switch (e.g. healthchecks.io) while blocks keep arriving, so the monitor
+/// alerts once telescope stops or stops receiving blocks.
+pub struct Heartbeat {
+    /// When the last block was recorded.
+    last_block: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Heartbeat {
+    /// Ping `url` every `interval` if a block was recorded since the previous ping.
+    pub fn spawn(url: String, interval: Duration, journal: Journal) -> Self {
+        let last_block = Arc::new(Mutex::new(None));
+        let beats = last_block.clone();
+        tokio::spawn(async move {
+            let http = reqwest::Client::new();
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            let mut previous = None;
+            // Log only when the pings start failing or recover, not on every ping.
+            let mut failing = false;
+            loop {
+                ticker.tick().await;
+                let last = *beats.lock().unwrap();
+                if last.is_none() || last == previous {
+                    continue;
+                }
+                previous = last;
+                match http
+                    .get(&url)
+                    .timeout(interval)
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status())
+                {
+                    Ok(_) if std::mem::take(&mut failing) => {
+                        journal.log(Severity::Info, "heartbeat", "Pinged the heartbeat again");
+                    }
+                    Ok(_) => {}
+                    Err(err) if !failing => {
+                        failing = true;
+                        journal.log(
+                            Severity::Warn,
+                            "heartbeat",
+                            format!("Failed to ping heartbeat: {err}"),
+                        );
+                    }
+                    Err(_) => {}
+                }
+            }
+        });
+        Self { last_block }
+    }
+
+    /// Note that a block was recorded.
+    #[inline]
+    pub fn beat(&self) {
+        *self.last_block.lock().unwrap() = Some(Instant::now());
+    }
+}
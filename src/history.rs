@@ -0,0 +1,141 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+use eyre::Result;
+use serde_json::json;
+
+use crate::{
+    http::{self, Response},
+    measurement::Snapshot,
+};
+
+/// The snapshots of the last minutes, kept beyond the measurement window so the surroundings of
+/// an incident can be dumped without having run a full recording.
+#[derive(Clone)]
+pub struct History {
+    span: Duration,
+    snapshots: Arc<Mutex<VecDeque<(Instant, Snapshot)>>>,
+}
+
+impl History {
+    pub fn new(span: Duration) -> Self {
+        Self {
+            span,
+            snapshots: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Record a snapshot and forget the ones that fell out of the span.
+    pub fn record(&self, snapshot: &Snapshot) {
+        let now = Instant::now();
+        let mut snapshots = self.snapshots.lock().unwrap();
+        snapshots.push_back((now, snapshot.clone()));
+        while snapshots
+            .front()
+            .is_some_and(|(taken, _)| now.duration_since(*taken) > self.span)
+        {
+            snapshots.pop_front();
+        }
+    }
+
+    /// Serialize the recorded snapshots as a JSON array, oldest first.
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        let snapshots = self.snapshots.lock().unwrap();
+        Ok(serde_json::to_vec(
+            &snapshots.iter().map(|(_, s)| s).collect::<Vec<_>>(),
+        )?)
+    }
+
+    /// Average the TPS of the recorded snapshots taken from `from` until before `to` (Unix
+    /// seconds) over steps of `step` seconds, as a JSON array of the steps holding snapshots,
+    /// e.g. `[{"time": 1791966300, "tps": 1234.5, "snapshots": 58}]`.
+    pub fn tps(&self, from: u64, to: u64, step: u64) -> Result<Vec<u8>> {
+        // The sum of the TPS, the snapshots and those with a TPS by the start of the step.
+        let mut steps = BTreeMap::<u64, (f64, usize, usize)>::new();
+        for (_, snapshot) in self.snapshots.lock().unwrap().iter() {
+            let Some(taken) = DateTime::parse_from_rfc3339(&snapshot.timestamp)
+                .ok()
+                .and_then(|taken| u64::try_from(taken.timestamp()).ok())
+                .filter(|taken| (from..to).contains(taken))
+            else {
+                continue;
+            };
+            let (sum, snapshots, rated) = steps
+                .entry(from + (taken - from) / step * step)
+                .or_default();
+            *snapshots += 1;
+            if let Some(tps) = snapshot.metrics.transactions_per_second {
+                *sum += tps;
+                *rated += 1;
+            }
+        }
+        let steps = steps
+            .into_iter()
+            .map(|(time, (sum, snapshots, rated))| {
+                json!({
+                    "time": time,
+                    "tps": (rated > 0).then(|| sum / rated as f64),
+                    "snapshots": snapshots,
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(serde_json::to_vec(&steps)?)
+    }
+
+    /// Dump the recorded snapshots to a timestamped file in the working directory.
+    pub fn dump(&self) -> Result<PathBuf> {
+        let path = PathBuf::from(format!(
+            "telescope-history-{}.json",
+            Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        fs::write(&path, self.to_json()?)?;
+        Ok(path)
+    }
+
+    /// Serve the recorded snapshots at `/history` and their TPS over time at
+    /// `/api/tps?from=<unix secs>&to=<unix secs>&step=<secs>`, all of them in steps of a minute
+    /// by default.
+    pub async fn serve(&self, addr: SocketAddr) -> Result<()> {
+        let history = self.clone();
+        http::serve(
+            addr,
+            Arc::new(move |request| {
+                let json = match request.path.as_str() {
+                    "/history" => history.to_json(),
+                    "/api/tps" => {
+                        let param = |name, default| match request.param(name) {
+                            Some(value) => value.parse::<u64>().ok(),
+                            None => Some(default),
+                        };
+                        match (param("from", 0), param("to", u64::MAX), param("step", 60)) {
+                            (Some(from), Some(to), Some(step)) if step > 0 => {
+                                history.tps(from, to, step)
+                            }
+                            _ => {
+                                return Response::status(
+                                    400,
+                                    "text/plain",
+                                    "expected ?from=<unix secs>&to=<unix secs>&step=<secs> \
+                                     with a positive step\n",
+                                )
+                            }
+                        }
+                    }
+                    _ => return Response::not_found(),
+                };
+                match json {
+                    Ok(json) => Response::ok("application/json", json),
+                    Err(err) => Response::internal_error(format!("{err}\n")),
+                }
+            }),
+        )
+        .await
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use eyre::Result;
+use serde::Serialize;
+
+use crate::{grafana::Grafana, measurement::Snapshot, refclock::ReferenceClock, smtp::Mailer};
+
+/// Number of recent entries kept for display.
+const RECENT: usize = 32;
+
+/// How long without a new block until the subscription counts as stalled.
+const STALL_AFTER: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Severity {
+    Info,
+    Warn,
+    Crit,
+}
+
+/// Something that happened besides the metrics, e.g. a reorg or a stalled subscription.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// The local wall-clock time of the entry (RFC 3339).
+    pub time: String,
+    /// The same time on the reference clock, if one is tracked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_time: Option<String>,
+    pub severity: Severity,
+    pub tag: &'static str,
+    pub text: String,
+}
+
+/// The log of the events of a run, shown next to the metrics, optionally persisted as JSON lines
+/// and pushed to Grafana.
+#[derive(Clone)]
+pub struct Journal {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    recent: VecDeque<Entry>,
+    /// Print the entries to stderr, when they are not rendered along with the metrics.
+    echo: bool,
+    file: Option<File>,
+    /// Receives the warnings and critical entries as annotations.
+    grafana: Option<Grafana>,
+    /// Receives the warnings and critical entries as mails.
+    mailer: Option<Mailer>,
+    /// Corrects the entry times for merging the journals of several probes.
+    reference_clock: Option<ReferenceClock>,
+    /// The entries logged since the sinks last took them, if they record the events.
+    unrecorded: Option<Vec<Entry>>,
+    /// The last recorded block and when it was recorded.
+    last_block: Option<(u64, Instant)>,
+    stalled: bool,
+    /// Whether the block subscription ended.
+    subscription_ended: bool,
+    /// The counters of the last snapshot, to log when they increase.
+    breaker_opens: u64,
+    inconsistent_blocks: u64,
+    out_of_order_blocks: u64,
+    sink_drops: u64,
+}
+
+impl Journal {
+    /// Create the journal, appending the entries to `file` if given.
+    pub fn new(file: Option<&Path>, echo: bool) -> Result<Self> {
+        let file = file
+            .map(|path| OpenOptions::new().create(true).append(true).open(path))
+            .transpose()?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(State {
+                recent: VecDeque::with_capacity(RECENT + 1),
+                echo,
+                file,
+                grafana: None,
+                mailer: None,
+                reference_clock: None,
+                unrecorded: None,
+                last_block: None,
+                stalled: false,
+                subscription_ended: false,
+                breaker_opens: 0,
+                inconsistent_blocks: 0,
+                out_of_order_blocks: 0,
+                sink_drops: 0,
+            })),
+        })
+    }
+
+    /// Push the warnings and critical entries as annotations to Grafana.
+    pub fn with_grafana(self, grafana: Grafana) -> Self {
+        self.state.lock().unwrap().grafana = Some(grafana);
+        self
+    }
+
+    /// Mail the warnings and critical entries.
+    pub fn with_mailer(self, mailer: Mailer) -> Self {
+        self.state.lock().unwrap().mailer = Some(mailer);
+        self
+    }
+
+    /// Add the reference clock time to the entries.
+    pub fn with_reference_clock(self, clock: ReferenceClock) -> Self {
+        self.state.lock().unwrap().reference_clock = Some(clock);
+        self
+    }
+
+    /// Keep the entries until the sinks take them with [`Journal::take_unrecorded`].
+    pub fn record_in_sinks(&self) {
+        self.state
+            .lock()
+            .unwrap()
+            .unrecorded
+            .get_or_insert_with(Vec::new);
+    }
+
+    /// Take the entries logged since the previous call, oldest first.
+    pub fn take_unrecorded(&self) -> Vec<Entry> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .unrecorded
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Log an entry.
+    pub fn log(&self, severity: Severity, tag: &'static str, text: impl Into<String>) {
+        let mut state = self.state.lock().unwrap();
+        let time = Local::now().to_rfc3339();
+        let entry = Entry {
+            reference_time: state
+                .reference_clock
+                .as_ref()
+                .and_then(|clock| clock.correct(&time)),
+            time,
+            severity,
+            tag,
+            text: text.into(),
+        };
+        if state.echo {
+            eprintln!("{entry}");
+        }
+        if let Some(file) = &mut state.file {
+            let written = serde_json::to_string(&entry)
+                .map_err(eyre::Report::from)
+                .and_then(|line| Ok(writeln!(file, "{line}")?));
+            if let Err(err) = written {
+                eprintln!("Failed to write event: {err}");
+            }
+        }
+        // The failures to push or mail the entries are not pushed or mailed again.
+        if let Some(grafana) = &state.grafana {
+            if severity >= Severity::Warn && tag != "grafana" {
+                grafana.annotate(tag, entry.text.clone());
+            }
+        }
+        if let Some(mailer) = &state.mailer {
+            if severity >= Severity::Warn && tag != "smtp" {
+                mailer.notify(entry.clone());
+            }
+        }
+        if let Some(unrecorded) = &mut state.unrecorded {
+            unrecorded.push(entry.clone());
+        }
+        state.recent.push_back(entry);
+        if state.recent.len() > RECENT {
+            state.recent.pop_front();
+        }
+    }
+
+    /// Get the `n` most recent entries, oldest first.
+    pub fn recent(&self, n: usize) -> Vec<Entry> {
+        let state = self.state.lock().unwrap();
+        state.recent.iter().rev().take(n).rev().cloned().collect()
+    }
+
+    /// Note a recorded block, logging missed blocks and the end of a stall.
+    pub fn observe_block(&self, number: u64) {
+        let now = Instant::now();
+        let (last_block, stalled) = {
+            let mut state = self.state.lock().unwrap();
+            let last_block = state.last_block.replace((number, now));
+            (last_block, std::mem::take(&mut state.stalled))
+        };
+        let Some((last_number, last_time)) = last_block else {
+            return;
+        };
+        if stalled {
+            self.log(
+                Severity::Info,
+                "stall",
+                format!(
+                    "Blocks resumed after {:.1} s",
+                    now.duration_since(last_time).as_secs_f64()
+                ),
+            );
+        }
+        if number > last_number + 1 {
+            self.log(
+                Severity::Warn,
+                "lag",
+                format!(
+                    "Missed {} blocks before block {number}",
+                    number - last_number - 1
+                ),
+            );
+        }
+    }
+
+    /// Log the end of the block subscription.
+    pub fn end_subscription(&self) {
+        self.state.lock().unwrap().subscription_ended = true;
+        self.log(Severity::Info, "disconnect", "Block subscription ended");
+    }
+
+    /// Describe the state of the block subscription, e.g. `receiving` or `stalled for 12 s`.
+    pub fn connection(&self) -> String {
+        let state = self.state.lock().unwrap();
+        match state.last_block {
+            _ if state.subscription_ended => "subscription ended".to_string(),
+            None => "waiting for the first block".to_string(),
+            Some((_, time)) if state.stalled => {
+                format!("stalled for {:.0} s", time.elapsed().as_secs_f64())
+            }
+            Some(_) => "receiving".to_string(),
+        }
+    }
+
+    /// Log a stall if no block was recorded for a while, returning whether one was logged.
+    pub fn check_stall(&self) -> bool {
+        let since = {
+            let mut state = self.state.lock().unwrap();
+            match state.last_block {
+                Some((_, time)) if !state.stalled && time.elapsed() > STALL_AFTER => {
+                    state.stalled = true;
+                    time.elapsed()
+                }
+                _ => return false,
+            }
+        };
+        self.log(
+            Severity::Warn,
+            "stall",
+            format!("No new block for {:.0} s", since.as_secs_f64()),
+        );
+        true
+    }
+
+    /// Log the alerts that fired since the previous snapshot.
+    pub fn observe(&self, snapshot: &Snapshot) {
+        let mut alerts = Vec::new();
+        let mut warnings = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(rpc) = &snapshot.metrics.rpc {
+                if rpc.breaker_opens > state.breaker_opens {
+                    alerts.push((
+                        "rpc",
+                        format!("RPC circuit breaker opened ({} total)", rpc.breaker_opens),
+                    ));
+                }
+                state.breaker_opens = rpc.breaker_opens;
+            }
+            if let Some(quality) = &snapshot.metrics.data_quality {
+                if quality.total_inconsistent_blocks > state.inconsistent_blocks {
+                    alerts.push((
+                        "data-quality",
+                        format!(
+                            "Inconsistent data from the endpoint up to block {}",
+                            snapshot.block_number
+                        ),
+                    ));
+                }
+                state.inconsistent_blocks = quality.total_inconsistent_blocks;
+            }
+            let out_of_order = snapshot.metrics.dropped_blocks.out_of_order;
+            if out_of_order > state.out_of_order_blocks {
+                warnings.push((
+                    "out-of-order",
+                    format!("Blocks delivered out of order ({out_of_order} total)"),
+                ));
+            }
+            state.out_of_order_blocks = out_of_order;
+            let sink_drops = snapshot
+                .metrics
+                .sinks
+                .iter()
+                .flatten()
+                .map(|delivery| delivery.dropped)
+                .sum::<u64>();
+            if sink_drops > state.sink_drops {
+                warnings.push((
+                    "sink-drop",
+                    format!("Snapshots dropped for slow sinks ({sink_drops} total)"),
+                ));
+            }
+            state.sink_drops = sink_drops;
+        }
+        for (tag, text) in alerts {
+            self.log(Severity::Crit, tag, text);
+        }
+        for (tag, text) in warnings {
+            self.log(Severity::Warn, tag, text);
+        }
+    }
+}
+
+impl fmt::Debug for Journal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Journal").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Crit => "CRIT",
+        })
+    }
+}
+
+impl Entry {
+    /// Get the ANSI color of the severity.
+    #[inline]
+    pub fn color(&self) -> &'static str {
+        match self.severity {
+            Severity::Info => "\x1b[0m",
+            Severity::Warn => "\x1b[33m",
+            Severity::Crit => "\x1b[1;31m",
+        }
+    }
+
+    /// Format the entry as a single line without the date.
+    pub fn line(&self) -> String {
+        // The time of day of the RFC 3339 timestamp.
+        let time = self.time.get(11..19).unwrap_or_default();
+        format!("{time} {} {}", self.severity, self.text)
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {} {}", self.time, self.severity, self.text)
+    }
+}
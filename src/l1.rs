@@ -0,0 +1,180 @@
+use std::{
+    collections::VecDeque,
+    io::{stdout, Write},
+    sync::Arc,
+    time::Instant,
+};
+
+use alloy::{
+    consensus::Transaction,
+    eips::BlockNumberOrTag,
+    network::{AnyRpcBlock, TransactionResponse},
+    primitives::Address,
+    providers::Provider,
+    rpc::types::BlockTransactionsKind,
+};
+use chrono::Local;
+use clap::Args;
+use eyre::{eyre, Result};
+use futures_util::StreamExt;
+
+use crate::{
+    proxy::{self, Proxy},
+    rpc::{RpcClient, RpcConfig},
+};
+
+/// Size of the data carried by one blob in bytes.
+const BLOB_SIZE: u64 = 131_072;
+
+/// Watch the L1 contract where the rollup posts its batches and state roots.
+#[derive(Args, Debug)]
+pub struct L1Args {
+    /// The WebSocket endpoint of the L1 node.
+    #[arg(long)]
+    l1_endpoint: String,
+
+    /// The address batches and commitments are sent to.
+    #[arg(long)]
+    inbox: Address,
+
+    /// The WebSocket endpoint of the L2 node, used to measure how far commitments lag behind.
+    #[arg(long, default_value = "ws://localhost:8546")]
+    l2_endpoint: String,
+
+    /// The window size (number of batches) to measure the batch activity.
+    #[arg(short, long, default_value = "16")]
+    window: usize,
+
+    /// Refresh the printed metrics.
+    #[arg(short, long)]
+    refresh: bool,
+
+    /// Reach both endpoints through a SOCKS5 or HTTP proxy, as with the main command.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<Proxy>,
+}
+
+/// A batch (or commitment) transaction sent to the inbox.
+struct Batch {
+    timestamp: Instant,
+    /// Calldata plus blob bytes.
+    size: u64,
+    gas_used: u64,
+    /// Execution and blob fees in wei.
+    fee: u128,
+}
+
+/// Watch the L1 inbox and print the batch activity for every new L1 block.
+pub async fn run(args: L1Args) -> Result<()> {
+    let connect = |endpoint: String| async {
+        let provider = proxy::provider(endpoint, args.proxy.clone()).await?;
+        Ok::<_, eyre::Report>(Arc::new(RpcClient::new(provider, RpcConfig::default())))
+    };
+    let l1 = connect(args.l1_endpoint).await?;
+    let l2 = connect(args.l2_endpoint).await?;
+
+    let mut stream = l1.provider().subscribe_blocks().await?.into_stream();
+    let mut batches = VecDeque::with_capacity(args.window + 1);
+
+    while let Some(header) = stream.next().await {
+        let Some(block) = l1
+            .get_block_by_hash(header.hash, BlockTransactionsKind::Full)
+            .await?
+        else {
+            continue;
+        };
+        for batch in inbox_batches(&l1, &block, args.inbox).await? {
+            batches.push_back(batch);
+            if batches.len() > args.window {
+                batches.pop_front();
+            }
+        }
+
+        let lag = commitment_lag(&l2).await.ok();
+        print!(
+            "\r[{}] L1 block {}, {}, L2 lag: {} {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.6f"),
+            block.header.number,
+            summary(&batches),
+            lag.map_or("n/a".to_string(), |lag| format!("{lag} blocks")),
+            if args.refresh { "" } else { "\n" }
+        );
+        stdout().flush()?;
+    }
+
+    Ok(())
+}
+
+/// Collect the batches sent to the inbox in the block.
+async fn inbox_batches(rpc: &RpcClient, block: &AnyRpcBlock, inbox: Address) -> Result<Vec<Batch>> {
+    let txs = block
+        .transactions
+        .txns()
+        .filter(|tx| Transaction::to(*tx) == Some(inbox))
+        .collect::<Vec<_>>();
+    if txs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let receipts = rpc
+        .get_block_receipts(block.header.hash)
+        .await?
+        .ok_or_else(|| eyre!("Receipts of block {} do not exist", block.header.number))?;
+    let now = Instant::now();
+    Ok(txs
+        .into_iter()
+        .filter_map(|tx| {
+            let receipt = receipts
+                .iter()
+                .find(|r| r.transaction_hash == tx.tx_hash())?;
+            let blobs = tx.blob_versioned_hashes().map_or(0, |hashes| hashes.len()) as u64;
+            let blob_fee = receipt.blob_gas_used.unwrap_or_default() as u128
+                * receipt.blob_gas_price.unwrap_or_default();
+            Some(Batch {
+                timestamp: now,
+                size: tx.input().len() as u64 + blobs * BLOB_SIZE,
+                gas_used: receipt.gas_used,
+                fee: receipt.gas_used as u128 * receipt.effective_gas_price + blob_fee,
+            })
+        })
+        .collect())
+}
+
+/// Get the number of L2 blocks not yet covered by a commitment on L1, i.e. the distance between
+/// the L2 head and the L2 safe head.
+async fn commitment_lag(l2: &RpcClient) -> Result<u64> {
+    let head = l2.get_block_number().await?;
+    let safe = l2
+        .request("eth_getBlockByNumber", |provider| async move {
+            provider
+                .get_block_by_number(BlockNumberOrTag::Safe, BlockTransactionsKind::Hashes)
+                .await
+        })
+        .await?
+        .ok_or_else(|| eyre!("The L2 node does not report a safe head"))?;
+    Ok(head.saturating_sub(safe.header.number))
+}
+
+/// Format the batch activity of the window.
+fn summary(batches: &VecDeque<Batch>) -> String {
+    let (Some(first), Some(last)) = (batches.front(), batches.back()) else {
+        return "No batches yet".to_string();
+    };
+    let n = batches.len() as f64;
+    let interval = match batches.len() {
+        1 => "n/a".to_string(),
+        len => {
+            let interval = (last.timestamp - first.timestamp).as_secs_f64() / (len - 1) as f64;
+            format!("{interval:.1} s ({:.2}/min)", 60.0 / interval)
+        }
+    };
+    let size = batches.iter().map(|b| b.size).sum::<u64>() as f64 / n;
+    let gas = batches.iter().map(|b| b.gas_used).sum::<u64>() as f64 / n;
+    let fee = batches.iter().map(|b| b.fee).sum::<u128>() as f64 / n;
+    format!(
+        "Batch interval: {interval}, Size: {:.1} KiB, L1 gas: {:.0}/batch, Fee: {:.6} ETH/batch",
+        size / 1024.0,
+        gas,
+        fee / 1e18,
+    )
+}
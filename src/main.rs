@@ -1,176 +1,1821 @@
 use std::{
-    io::{stdout, Write},
-    time::Instant,
+    fs, future,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::pin,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use chrono::Local;
-use clap::Parser;
+
+use clap::{Parser, Subcommand, ValueEnum};
 
 use alloy::{
-    primitives::bytes::Buf,
-    providers::{Provider, ProviderBuilder, WsConnect},
-    rpc::types::{Block, BlockTransactionsKind},
+    network::{AnyNetwork, AnyRpcBlock},
+    primitives::{Address, Bytes, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::BlockTransactionsKind,
 };
 use eyre::Result;
-use futures_util::StreamExt;
+use futures_util::{stream::BoxStream, StreamExt};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde_json::json;
+use tokio::signal::unix::{signal, SignalKind};
+
+mod address;
+mod bridge;
+mod cache;
+mod check;
+mod clock;
+mod config;
+mod control;
+mod decline;
+mod delivery;
+mod derive;
+mod event;
+mod fees;
+mod fetch;
+mod fleet;
+mod gaps;
+mod grafana;
+mod graphql;
+mod header_time;
+mod heartbeat;
+mod history;
+mod http;
+mod journal;
+mod l1;
+mod measurement;
+mod mempool;
+mod node_metrics;
+mod ordering;
+mod peers;
+mod pipeline;
+mod price;
+mod proxy;
+mod quality;
+mod ramp;
+mod read_probe;
+mod rebroadcast;
+mod refclock;
+mod rollup;
+mod rpc;
+mod sampling;
+mod self_load;
+mod selftest;
+mod sink;
+mod sketch;
+mod smtp;
+mod soak;
+mod stages;
+mod state;
+mod state_probe;
+mod sustained;
+mod token;
+mod topics;
+mod track;
+mod trends;
+mod tui;
+mod upgrade;
+mod upload;
+mod window;
+mod workload;
+
+use address::AddressWatch;
+use check::CheckArgs;
+use config::Preset;
+use control::{Control, Status};
+use decline::{DeclineRule, DeclineWatch};
+use delivery::FirstDelivery;
+use derive::Derived;
+use event::EventSpec;
+use fees::FeeHistory;
+use fetch::{FetchConfig, FetchMode, Fetcher, SubscribeMode};
+use fleet::FleetArgs;
+use gaps::GapClasses;
+use grafana::Grafana;
+use graphql::GraphQlClient;
+use header_time::HeaderTime;
+use heartbeat::Heartbeat;
+use history::History;
+use journal::{Journal, Severity};
+use l1::L1Args;
+use measurement::{Decoded, Measurement, RateClock, Snapshot};
+use mempool::Mempool;
+use node_metrics::{NodeMetricNames, NodeMetricsScraper};
+use peers::PeerMonitor;
+use price::PriceFeed;
+use proxy::Proxy;
+use read_probe::ReadProbe;
+use rebroadcast::{RebroadcastFormat, Rebroadcaster};
+use refclock::{ClockSource, ReferenceClock};
+use rollup::Rollup;
+use rpc::{RpcClient, RpcConfig};
+use self_load::SelfLoad;
+use selftest::SelftestArgs;
+use sink::{HistogramBuckets, Rotation, SinkOptions, SinkSpec, Sinks};
+use smtp::{Mailer, SmtpServer};
+use soak::SoakArgs;
+use stages::StageTimer;
+use state_probe::StateProbe;
+use token::TokenWatch;
+use topics::TopicLeaderboard;
+use track::TrackArgs;
+use trends::TrendsArgs;
+use tui::{Targets, Tui};
+use upgrade::UpgradeWatch;
+use upload::{UploadTarget, Uploader};
+use window::WindowSizer;
 
 /// A utility to monitor the MegaETH performance.
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    args_override_self = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Read the options from a JSON file whose keys are the long option names, e.g.
+    /// `{"endpoint": "ws://node:8546", "sink": ["stdout", "csv:run.csv"], "refresh": true}`.
+    /// Options on the command line override the file.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Start from a built-in combination of options; the configuration file and the command line
+    /// override its values and add to it.
+    #[arg(long, value_enum)]
+    preset: Option<Preset>,
+
+    /// Read secret options from a file of `<VARIABLE>=<value>` lines, named like the environment
+    /// variables setting them (e.g. `TELESCOPE_GRAFANA_TOKEN`), so they stay out of the process
+    /// arguments. The environment overrides the files, the configuration file and the command
+    /// line override both. Can be given multiple times.
+    #[arg(long = "key-file", value_name = "PATH")]
+    key_files: Vec<PathBuf>,
+
     /// The WebSocket endpoint to connect to the blockchain.
     #[arg(short, long, default_value = "ws://localhost:8546")]
     endpoint: String,
 
-    /// The window size (number of blocks) to measure the performance.
-    #[arg(short, long, default_value = "16")]
+    /// Also subscribe to the blocks of this WebSocket endpoint, measuring every block at its
+    /// first delivery by any endpoint and reporting how often each endpoint delivered first.
+    /// Can be given multiple times.
+    #[arg(long = "extra-endpoint", value_name = "URL")]
+    extra_endpoints: Vec<String>,
+
+    /// Reach the endpoint through a proxy, `socks5://[<user>:<password>@]<host>:<port>` or
+    /// `http://[<user>:<password>@]<host>:<port>` (tunneled with `CONNECT`).
+    #[arg(long, value_name = "URL")]
+    proxy: Option<Proxy>,
+
+    /// The window size (number of blocks) to measure the performance; the rates are computed
+    /// over the time from the oldest to the newest block.
+    #[arg(short, long, default_value = "16", value_parser = clap::value_parser!(u64).range(2..))]
     window: u64,
 
+    /// Warn when the window spans less than this many seconds at the observed block rate, too
+    /// short for stable rates; 0 disables the check.
+    #[arg(long, value_name = "SECS", default_value_t = 2.0, value_parser = non_negative)]
+    min_window_span: f64,
+
+    /// Resize the window as the block rate changes so that it spans this many seconds, instead
+    /// of keeping `--window` blocks.
+    #[arg(long, value_name = "SECS", value_parser = positive)]
+    auto_window: Option<f64>,
+
+    /// Which time of a block the rates are based on: when the header was received, or when
+    /// fetching the block data completed.
+    #[arg(long, value_enum, default_value = "receive")]
+    rate_clock: RateClock,
+
+    /// Where the headers carry their millisecond-precision time (chain dependent), for
+    /// `--rate-clock header`: `field:<name>` for an extra header field or `extra-data:<offset>`
+    /// for a big-endian 64-bit value in the extra data. Extra fields need fetched blocks.
+    #[arg(long, value_name = "SOURCE")]
+    header_time: Option<HeaderTime>,
+
+    /// How much of every block is fetched: `none` uses the subscribed headers alone (no
+    /// transaction count, no extra RPC calls apart from the backfill), `hashes` the block with
+    /// its transaction hashes, `full` the full transactions. Metrics needing more upgrade
+    /// `hashes` to `full`.
+    #[arg(long, value_enum, default_value = "hashes")]
+    fetch: FetchMode,
+
+    /// Count the transactions with `eth_getBlockTransactionCountByHash` (or from the receipts,
+    /// if fetched) instead of the block's transaction list, for providers that truncate block
+    /// bodies. Works with `--fetch none` too.
+    #[arg(long)]
+    count_transactions: bool,
+
+    /// Once more subscribed blocks than this arrive per second, fetch the full transactions and
+    /// receipts of an evenly spread sample of them only, so the detailed breakdowns keep up
+    /// with the chain. Their rates are scaled up by the sampling ratio, which is reported.
+    #[arg(
+        long,
+        value_name = "BLOCKS_PER_S",
+        conflicts_with_all = ["mempool", "watch_address", "watch_event", "watch_token"]
+    )]
+    sample_above: Option<f64>,
+
+    /// Subscribe to headers, or to full blocks to save fetching every block where the node
+    /// supports it.
+    #[arg(long, value_enum, default_value = "headers")]
+    subscribe_mode: SubscribeMode,
+
+    /// Where the block data is fetched from. New blocks are always announced over the WebSocket
+    /// endpoint.
+    #[arg(long, value_enum, default_value = "jsonrpc")]
+    transport: Transport,
+
+    /// The Ethereum GraphQL endpoint used by `--transport graphql`.
+    #[arg(long, default_value = "http://localhost:8545/graphql")]
+    graphql_endpoint: String,
+
     /// Refresh the printed metrics.
     #[arg(short, long)]
     refresh: bool,
+
+    /// Print the run-wide totals and averages on a second line.
+    #[arg(long)]
+    totals: bool,
+
+    /// Chart the TPS of the last WINDOWS windows as bars below the printed metrics, redrawn
+    /// whenever a window's worth of new blocks was recorded.
+    #[arg(long, value_name = "WINDOWS", conflicts_with = "tui")]
+    chart: Option<usize>,
+
+    /// Print the change of every metric versus the previous window instead of its value, to
+    /// make a gradual degradation visible.
+    #[arg(long, conflicts_with = "tui")]
+    diff: bool,
+
+    /// Show the shortened hash of every block on the metrics line and record the full hash of
+    /// its parent besides its own, to cross-reference the output with explorers and node logs.
+    #[arg(long)]
+    show_hashes: bool,
+
+    /// Where to write the window snapshots: `stdout`, `json:<path>`, `csv:<path>`,
+    /// `prometheus:<addr>`, `clickhouse:<url>` or `mqtt:<host>:<port>/<topic>`. Can be given
+    /// multiple times; defaults to `stdout` unless the TUI is shown.
+    #[arg(long = "sink", value_name = "SPEC")]
+    sinks: Vec<SinkSpec>,
+
+    /// How many snapshots may wait for a slow sink before further ones are dropped for it.
+    #[arg(long, value_name = "SNAPSHOTS", default_value_t = 256, value_parser = clap::value_parser!(u64).range(1..))]
+    sink_queue: u64,
+
+    /// Rotate the JSON and CSV recordings once they are this old (`30m`, `1h`, `1d`) or large
+    /// (`500MB`, `2GB`), moving them aside with a timestamp suffix.
+    #[arg(long, value_name = "AGE|SIZE")]
+    rotate: Option<Rotation>,
+
+    /// Keep only this many rotated files per recording, deleting the oldest.
+    #[arg(long, value_name = "FILES", value_parser = clap::value_parser!(u64).range(1..))]
+    retain: Option<u64>,
+
+    /// The bucket bounds of the Prometheus histogram of the transactions per block.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "0,1,5,10,50,100,500,1000,5000"
+    )]
+    histogram_transactions: Vec<f64>,
+
+    /// The bucket bounds of the Prometheus histogram of the gas used per block.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "100000,1000000,5000000,10000000,30000000,100000000,300000000,1000000000"
+    )]
+    histogram_gas: Vec<f64>,
+
+    /// The bucket bounds (milliseconds) of the Prometheus histogram of the block interval.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "1,5,10,20,50,100,200,500,1000,2000"
+    )]
+    histogram_interval_ms: Vec<f64>,
+
+    /// Resume the recording of a previous run: the JSON and CSV sinks are appended to, skipping
+    /// the blocks they already contain, and the snapshots keep the given run ID.
+    #[arg(long, value_name = "RUN_ID")]
+    resume: Option<String>,
+
+    /// Add a metric derived from the snapshot fields, e.g.
+    /// `gas_per_tx = gas_per_second / transactions_per_second`. Can be given multiple times.
+    #[arg(long = "derive", value_name = "NAME = EXPR")]
+    derived: Vec<Derived>,
+
+    /// Keep the snapshots of the last minutes in memory and dump them to a JSON file on SIGUSR1.
+    #[arg(long, value_name = "MINUTES")]
+    history_minutes: Option<u64>,
+
+    /// Serve the kept snapshots as JSON at `/history` on this address, and their TPS averaged over
+    /// steps at `/api/tps?from=<unix secs>&to=<unix secs>&step=<secs>` for dashboards.
+    #[arg(long, value_name = "ADDR", requires = "history_minutes")]
+    history_addr: Option<SocketAddr>,
+
+    /// Serve a JSON control API on this address to resize the window, enable or disable sinks,
+    /// dump the latest snapshot or rotate the recordings of a running instance.
+    #[arg(long, value_name = "ADDR")]
+    control_addr: Option<SocketAddr>,
+
+    /// Upload the recordings, the event log and the dumps to `s3://<bucket>/<prefix>` or
+    /// `gs://<bucket>/<prefix>` when they are rotated or dumped, and at exit. The credentials are
+    /// taken from the `AWS_*` or the `GOOGLE_OAUTH_ACCESS_TOKEN` environment variables.
+    #[arg(long, value_name = "URL")]
+    upload: Option<UploadTarget>,
+
+    /// Push annotations to this Grafana instance on reorgs, RPC circuit breaker openings, gas
+    /// mismatches and marks set from the TUI.
+    #[arg(long, value_name = "URL")]
+    grafana_url: Option<String>,
+
+    /// The service account token used to push the Grafana annotations.
+    #[arg(long, value_name = "TOKEN", requires = "grafana_url")]
+    grafana_token: Option<String>,
+
+    /// Mail the warnings and critical events (collected for a minute into one mail) and a
+    /// summary at the end of the run through this SMTP server:
+    /// `smtp://[<user>:<password>@]<host>[:<port>]`, upgraded with STARTTLS if offered, or
+    /// `smtps://...` for implicit TLS.
+    #[arg(long, value_name = "URL", requires = "smtp_to")]
+    smtp: Option<SmtpServer>,
+
+    /// The sender address of the mails.
+    #[arg(long, value_name = "ADDRESS", default_value = "telescope@localhost")]
+    smtp_from: String,
+
+    /// A recipient of the mails; repeat for several.
+    #[arg(long, value_name = "ADDRESS")]
+    smtp_to: Vec<String>,
+
+    /// Append the logged events (connects, stalls, missed blocks, reorgs and alerts) to this
+    /// file as JSON lines.
+    #[arg(long, value_name = "PATH")]
+    events_file: Option<PathBuf>,
+
+    /// Interleave the logged events with the snapshots of the JSON and CSV sinks, as objects with
+    /// `"record": "event"` and as rows filling the `event_*` columns.
+    #[arg(long)]
+    sink_events: bool,
+
+    /// Roll the snapshots up into means and percentiles every this many minutes, logged as an
+    /// event; 0 disables the rollups.
+    #[arg(long, value_name = "MINUTES", default_value_t = 60)]
+    rollup_minutes: u64,
+
+    /// Also append the rollups with the distribution of every metric to this file as JSON lines.
+    #[arg(long, value_name = "PATH")]
+    rollup_file: Option<PathBuf>,
+
+    /// Ping this dead man's switch URL (e.g. healthchecks.io) while blocks are being received,
+    /// so that the absence of telescope or of blocks pages someone.
+    #[arg(long, value_name = "URL")]
+    heartbeat_url: Option<String>,
+
+    /// The interval between the heartbeat pings in seconds.
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 60,
+        requires = "heartbeat_url"
+    )]
+    heartbeat_interval: u64,
+
+    /// Serve a local WebSocket on this address re-broadcasting every datapoint (block and
+    /// derived metrics) to downstream consumers.
+    #[arg(long, value_name = "ADDR")]
+    rebroadcast: Option<SocketAddr>,
+
+    /// How the rebroadcast datapoints are encoded: `json` with the block and all metrics, or
+    /// `binary` frames of the key figures, cheaper at hundreds of blocks per second.
+    #[arg(long, value_enum, default_value = "json", requires = "rebroadcast")]
+    rebroadcast_format: RebroadcastFormat,
+
+    /// Report the trends of TPS and gas/s over the window (linear regression), e.g. while
+    /// gradually ramping up a load generator.
+    #[arg(long)]
+    ramp_report: bool,
+
+    /// Track the best gas rate and TPS sustained over sliding intervals of these lengths in
+    /// seconds (e.g. `10,60`) and report them with the run totals.
+    #[arg(long, value_name = "SECONDS", value_delimiter = ',')]
+    sustained: Vec<f64>,
+
+    /// Classify the gaps between consecutive blocks by two boundaries in milliseconds,
+    /// `<slow>,<stall>` (e.g. `500,2000`), and count the normal, slow and stalled gaps in the
+    /// window and since the start.
+    #[arg(long, value_name = "SLOW_MS,STALL_MS")]
+    gap_classes: Option<GapClasses>,
+
+    /// Warn when the TPS, the gas rate or the mini-block interval worsened in each of
+    /// `<windows>` consecutive windows, by more than `<percent>` in total (e.g. `5,10`), a slow
+    /// degradation threshold alerts miss.
+    #[arg(long, value_name = "WINDOWS,PERCENT")]
+    decline: Option<DeclineRule>,
+
+    /// Report the block share and throughput of every block producer (the header beneficiary),
+    /// e.g. on networks with several sequencers or to follow a failover.
+    #[arg(long)]
+    producers: bool,
+
+    /// Rank the event signatures (topic0) of the window's logs and report the most frequent
+    /// ones, showing which protocols dominate during a spike (fetches receipts).
+    #[arg(long, value_name = "COUNT")]
+    topics: Option<usize>,
+
+    /// A JSON file naming event signatures for the topic leaderboard, e.g.
+    /// `{"0x1c41…bad1": "Sync"}`; common ERC-20 and AMM events are named without it.
+    #[arg(long, value_name = "PATH", requires = "topics")]
+    topic_names: Option<PathBuf>,
+
+    /// Split the gas rate into execution and calldata/blob gas (fetches full transactions).
+    #[arg(long)]
+    gas_split: bool,
+
+    /// Report the bridge activity: deposits from L1 and withdrawals initiated to L1 (fetches full
+    /// transactions and receipts).
+    #[arg(long)]
+    bridge: bool,
+
+    /// Count same-sender nonce chains and back-to-back transactions as a proxy for bot and bundle
+    /// activity (fetches full transactions).
+    #[arg(long)]
+    ordering: bool,
+
+    /// Classify the transactions by their calldata (transfer, ERC-20, swap, NFT, deploy, other)
+    /// and report each category's share of the TPS and gas (fetches full transactions and
+    /// receipts).
+    #[arg(long)]
+    workload: bool,
+
+    /// Show a full-screen terminal UI instead of printing lines.
+    #[arg(long)]
+    tui: bool,
+
+    /// The time span (minutes) covered by the interval heatmap in the TUI.
+    #[arg(long, default_value = "5")]
+    heatmap_minutes: u64,
+
+    /// Draw a target line at this mini-block interval (milliseconds) in the TUI heatmap, e.g. the
+    /// advertised 10 ms.
+    #[arg(long, value_name = "MS")]
+    target_interval_ms: Option<f64>,
+
+    /// Draw the TPS against this target in the TUI.
+    #[arg(long, value_name = "TPS")]
+    target_tps: Option<f64>,
+
+    /// Estimate the local clock skew against the block header timestamps and report the
+    /// propagation latency.
+    #[arg(long)]
+    clock_drift: bool,
+
+    /// Subtract the estimated clock skew from the propagation latency (implies `--clock-drift`).
+    #[arg(long)]
+    clock_correction: bool,
+
+    /// Record the snapshot and event times also on an external reference clock,
+    /// `ntp:<host>[:<port>]` for a (PTP- or GPS-disciplined) NTP server or `ptp:<device>` for a
+    /// PTP hardware clock, so the recordings of distributed probes can be merged on a common time base.
+    #[arg(long, value_name = "SOURCE")]
+    reference_clock: Option<ClockSource>,
+
+    /// Seconds between the offset measurements against the reference clock.
+    #[arg(long, default_value_t = 16)]
+    reference_clock_interval: u64,
+
+    /// Maximum number of retries of a failed RPC call.
+    #[arg(long, default_value = "3")]
+    rpc_retries: u32,
+
+    /// Maximum number of RPC calls in flight.
+    #[arg(long, default_value = "8", value_parser = clap::value_parser!(u64).range(1..))]
+    rpc_concurrency: u64,
+
+    /// Number of consecutive RPC failures that open the circuit breaker.
+    #[arg(long, default_value = "5")]
+    rpc_breaker_threshold: u32,
+
+    /// How long (milliseconds) the circuit breaker stays open before a trial call.
+    #[arg(long, default_value = "5000")]
+    rpc_breaker_cooldown_ms: u64,
+
+    /// Maximum number of RPC requests (including retries) sent per second, to stay within the
+    /// rate limit of hosted endpoints.
+    #[arg(long, value_name = "RPS", value_parser = positive)]
+    max_rps: Option<f64>,
+
+    /// Account the bytes received from the node per subscription and RPC method, reporting
+    /// telescope's own bandwidth use.
+    #[arg(long)]
+    bandwidth: bool,
+
+    /// Correlate telescope's own request rate with the block fetch latency, warning when the
+    /// enabled metrics likely overload the endpoint and recommending a lighter configuration.
+    #[arg(long)]
+    self_load: bool,
+
+    /// Time every stage of the blocks (produced, header received, data fetched, recorded,
+    /// written) and report the stage latencies, telling whether a lag behind the chain stems
+    /// from the network, the node or telescope itself.
+    #[arg(long)]
+    stages: bool,
+
+    /// Number of received blocks fetched and decoded concurrently, so heavy analysis modes keep
+    /// up with the chain. The blocks are still recorded in order.
+    #[arg(long, value_name = "BLOCKS", default_value_t = 1)]
+    pipeline_depth: usize,
+
+    /// Do not backfill the window with the latest blocks on startup.
+    #[arg(long)]
+    no_backfill: bool,
+
+    /// Save the window to this file on shutdown and restore it on start if it still matches the
+    /// chain, so a brief restart keeps the window and its percentiles.
+    #[arg(long, value_name = "PATH")]
+    state_file: Option<PathBuf>,
+
+    /// Verify the header `gas_used` of every block against the sum of its receipts' gas
+    /// (fetches receipts).
+    #[arg(long)]
+    verify_gas: bool,
+
+    /// Cross-check the data of the endpoint for consistency (receipt count and gas against the
+    /// block, receipts of another block, timestamps going backwards) and report the inconsistent
+    /// blocks, as cached data of RPC gateways silently corrupts the metrics (fetches receipts).
+    #[arg(long)]
+    data_quality: bool,
+
+    /// Report the event throughput (logs emitted per second), counted from the receipts (fetches
+    /// receipts).
+    #[arg(long)]
+    events: bool,
+
+    /// Report the gas burned by reverted transactions per window (fetches receipts).
+    #[arg(long)]
+    failed_gas: bool,
+
+    /// Report the fees paid per window and per transaction, including L1 data fees (fetches
+    /// receipts).
+    #[arg(long)]
+    transaction_fees: bool,
+
+    /// Convert the fees to USD at the native token price polled from this JSON feed, e.g.
+    /// `https://api.coinbase.com/v2/prices/ETH-USD/spot`.
+    #[arg(long, value_name = "URL", requires = "transaction_fees")]
+    price_url: Option<String>,
+
+    /// The dot-separated path of the price in the feed's response, e.g. `data.amount`; defaults
+    /// to the first number in it.
+    #[arg(long, value_name = "PATH", requires = "price_url")]
+    price_field: Option<String>,
+
+    /// The interval between the price polls in seconds.
+    #[arg(long, default_value_t = 60, requires = "price_url")]
+    price_interval: u64,
+
+    /// Measure the interval and rate of a recurring event given as `<address>:<topic0>`, e.g. an
+    /// oracle update (fetches receipts).
+    #[arg(long, value_name = "ADDRESS:TOPIC0")]
+    watch_event: Option<EventSpec>,
+
+    /// Track pending transactions and report their inclusion latency by transaction type and
+    /// priority fee band (fetches full transactions).
+    #[arg(long)]
+    mempool: bool,
+
+    /// Report the transactions, gas, nonce and balance changes of this address per window, e.g.
+    /// of a load generator's sender account (fetches receipts).
+    #[arg(long, value_name = "ADDRESS")]
+    watch_address: Option<Address>,
+
+    /// Warn when the watched address would run out of funds within this many minutes at its
+    /// current spend rate.
+    #[arg(long, value_name = "MINUTES", requires = "watch_address", value_parser = positive)]
+    min_runway_minutes: Option<f64>,
+
+    /// Report the transfers per second, the distinct holders touched and the gas used of this
+    /// ERC-20 or ERC-721 token per window, e.g. during its launch (fetches receipts).
+    #[arg(long, value_name = "ADDRESS")]
+    watch_token: Option<Address>,
+
+    /// Scrape the node's own Prometheus endpoint and show its gas rate and block production
+    /// time next to the observed ones.
+    #[arg(long, value_name = "URL")]
+    node_metrics_url: Option<String>,
+
+    /// The node metric of the gas rate: a gauge in gas/s, or a `_total` counter of gas.
+    #[arg(long, default_value = "reth_sync_execution_gas_per_second")]
+    node_gas_metric: String,
+
+    /// The node metric of the block production time in seconds: a gauge, histogram or summary.
+    #[arg(long, value_name = "NAME")]
+    node_block_time_metric: Option<String>,
+
+    /// The interval between the node metric scrapes in seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 5)]
+    node_metrics_interval: u64,
+
+    /// Watch for network upgrades: header fields appearing, base fee rule changes, and changes
+    /// of the chain ID and of the configuration reported by `eth_config`.
+    #[arg(long)]
+    watch_upgrades: bool,
+
+    /// Poll the node's P2P peers (`net_peerCount`, and `admin_peers` if exposed).
+    #[arg(long)]
+    peers: bool,
+
+    /// The interval between the peer polls in seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 10, requires = "peers")]
+    peers_interval: u64,
+
+    /// Log a warning when the peer count drops below this threshold.
+    #[arg(long, value_name = "PEERS", requires = "peers")]
+    min_peers: Option<u64>,
+
+    /// Poll `eth_feeHistory` for the base fee trend and the priority fee percentiles, and check
+    /// its base fees against the subscribed headers.
+    #[arg(long)]
+    fee_history: bool,
+
+    /// The interval between the fee history polls in seconds.
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 10,
+        requires = "fee_history"
+    )]
+    fee_history_interval: u64,
+
+    /// Number of the latest blocks covered by every fee history poll.
+    #[arg(
+        long,
+        value_name = "BLOCKS",
+        default_value_t = 20,
+        requires = "fee_history"
+    )]
+    fee_history_blocks: u64,
+
+    /// The priority fee percentiles requested from the fee history.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "10,50,90",
+        requires = "fee_history"
+    )]
+    fee_percentiles: Vec<f64>,
+
+    /// Periodically read from this contract (`eth_getStorageAt` and `eth_call`) and correlate the
+    /// read latency with the observed load.
+    #[arg(long, value_name = "ADDRESS")]
+    read_probe: Option<Address>,
+
+    /// The storage slot read by the read probe.
+    #[arg(long, default_value = "0")]
+    read_probe_slot: U256,
+
+    /// The calldata of the read probe's `eth_call`.
+    #[arg(long, default_value = "0x")]
+    read_probe_calldata: Bytes,
+
+    /// The interval (milliseconds) between two read probes.
+    #[arg(long, default_value = "1000")]
+    read_probe_interval_ms: u64,
+
+    /// Periodically prove storage slots of this contract with `eth_getProof` and read them in a
+    /// batch of `eth_getStorageAt` calls, following the state access latency under load.
+    #[arg(long, value_name = "ADDRESS")]
+    state_probe: Option<Address>,
+
+    /// The number of storage slots, from slot 0, proven and read by the state probe.
+    #[arg(long, value_name = "SLOTS", default_value_t = 64, value_parser = clap::value_parser!(u64).range(1..))]
+    state_probe_slots: u64,
+
+    /// The interval (milliseconds) between two state probes.
+    #[arg(long, default_value = "1000")]
+    state_probe_interval_ms: u64,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Watch the L1 contract where the rollup posts batches and state roots.
+    L1(L1Args),
+    /// Report the inclusion times and positions of a list of transactions, e.g. sent by a load
+    /// generator.
+    Track(TrackArgs),
+    /// Validate a configuration file and check that the endpoints, sinks and credentials it
+    /// names work, then exit; fails if any check failed.
+    Check(CheckArgs),
+    /// Watch the chain for a number of hours and fail if the TPS or mini-block interval varied
+    /// or the blocks stalled beyond the thresholds, e.g. to qualify a node build for release.
+    Soak(SoakArgs),
+    /// Measure several instances at once, e.g. testnets or shards, and report the throughput of
+    /// every instance, of the groups sharing a label and of the whole fleet.
+    Fleet(FleetArgs),
+    /// Probe an endpoint: the block subscription, the baseline latencies of the RPC methods and
+    /// the MegaETH extra data, then print which features of telescope it supports.
+    Selftest(SelftestArgs),
+    /// Aggregate the JSON and CSV recordings of many runs into a static HTML site charting the
+    /// TPS, gas and latency across node versions and dates.
+    Trends(TrendsArgs),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Transport {
+    /// Full JSON-RPC block objects.
+    Jsonrpc,
+    /// Only the fields telescope needs, through the GraphQL API.
+    Graphql,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if args.preset.is_some() || args.config.is_some() {
+        // Parse the options of the preset and the file first, so the command line overrides them.
+        args = Args::parse_from(file_options(&args, Vec::new())?);
+    }
+    // The subcommands take their own options.
+    if args.command.is_none() {
+        let secrets = config::secret_options(&args.key_files)?;
+        if !secrets.is_empty() {
+            args = Args::parse_from(file_options(&args, secrets)?);
+        }
+    }
+    match args.command {
+        Some(Command::L1(l1_args)) => return l1::run(l1_args).await,
+        Some(Command::Track(track_args)) => return track::run(track_args).await,
+        Some(Command::Check(check_args)) => return check::run(check_args).await,
+        Some(Command::Soak(soak_args)) => return soak::run(soak_args).await,
+        Some(Command::Fleet(fleet_args)) => return fleet::run(fleet_args).await,
+        Some(Command::Selftest(selftest_args)) => return selftest::run(selftest_args).await,
+        Some(Command::Trends(trends_args)) => return trends::run(trends_args),
+        None => {}
+    }
     assert!(args.window > 1, "Window size must be greater than 1");
 
+    // Log the events of the run, shown along with the metrics if they are refreshed in place.
+    let mut journal = Journal::new(args.events_file.as_deref(), !args.refresh && !args.tui)?;
+    let grafana = args
+        .grafana_url
+        .clone()
+        .map(|url| Grafana::new(url, args.grafana_token.clone(), journal.clone()));
+    if let Some(grafana) = &grafana {
+        journal = journal.with_grafana(grafana.clone());
+    }
+    let mailer = args.smtp.clone().map(|server| {
+        Mailer::spawn(
+            server,
+            args.smtp_from.clone(),
+            args.smtp_to.clone(),
+            delivery::label(&args.endpoint),
+            journal.clone(),
+        )
+    });
+    if let Some(mailer) = &mailer {
+        journal = journal.with_mailer(mailer.clone());
+    }
+    if args.sink_events {
+        journal.record_in_sinks();
+    }
+    let reference_clock = match args.reference_clock.clone() {
+        Some(source) => Some(
+            ReferenceClock::start(
+                source,
+                Duration::from_secs(args.reference_clock_interval.max(1)),
+                journal.clone(),
+            )
+            .await,
+        ),
+        None => None,
+    };
+    if let Some(clock) = &reference_clock {
+        journal = journal.with_reference_clock(clock.clone());
+    }
+
     // Create the provider.
-    let ws = WsConnect::new(args.endpoint);
-    let provider = ProviderBuilder::new().on_ws(ws).await?;
+    let provider = proxy::provider(args.endpoint.clone(), args.proxy.clone()).await?;
+    let rpc = Arc::new(RpcClient::new(
+        provider,
+        RpcConfig {
+            retries: args.rpc_retries,
+            concurrency: args.rpc_concurrency as usize,
+            breaker_threshold: args.rpc_breaker_threshold,
+            breaker_cooldown: Duration::from_millis(args.rpc_breaker_cooldown_ms),
+            max_rps: args.max_rps,
+            bandwidth: args.bandwidth,
+        },
+    ));
+    // Recorded along with the snapshots, so runs can be compared across node versions.
+    let node_version = rpc.provider().get_client_version().await.ok();
 
     // Subscribe to new blocks.
-    let sub = provider.subscribe_blocks().await?;
-    let mut stream = sub.into_stream();
+    let subscription = subscribe(rpc.provider(), args.subscribe_mode).await?;
+    journal.log(
+        Severity::Info,
+        "connect",
+        format!(
+            "Subscribed to new blocks at {}",
+            delivery::label(&args.endpoint)
+        ),
+    );
+    // The extra providers must outlive their subscriptions.
+    let mut extra_providers = Vec::with_capacity(args.extra_endpoints.len());
+    let (first_delivery, subscription) = if args.extra_endpoints.is_empty() {
+        (None, subscription)
+    } else {
+        let mut subscriptions = vec![(delivery::label(&args.endpoint), subscription)];
+        for endpoint in &args.extra_endpoints {
+            let provider = proxy::provider(endpoint.clone(), args.proxy.clone()).await?;
+            subscriptions.push((
+                delivery::label(endpoint),
+                subscribe(&provider, args.subscribe_mode).await?,
+            ));
+            extra_providers.push(provider);
+            journal.log(
+                Severity::Info,
+                "connect",
+                format!("Subscribed to new blocks at {}", delivery::label(endpoint)),
+            );
+        }
+        let (first_delivery, merged) = FirstDelivery::merge(subscriptions);
+        (Some(first_delivery), merged)
+    };
+
+    // Start the rebroadcast server.
+    let rebroadcaster = match args.rebroadcast {
+        Some(addr) => Some(Rebroadcaster::bind(addr, args.rebroadcast_format).await?),
+        None => None,
+    };
 
     // Create the measurement.
-    let mut measurement = Measurement::new(args.window);
+    let mut measurement = Measurement::new(args.window).with_rate_clock(args.rate_clock);
+    if let Some(header_time) = args.header_time.clone() {
+        measurement = measurement.with_header_time(header_time);
+    } else if let RateClock::Header = args.rate_clock {
+        eyre::bail!("--rate-clock header needs --header-time");
+    }
+    if args.clock_drift || args.clock_correction {
+        measurement = measurement.with_clock_drift(args.clock_correction);
+    }
+    if args.verify_gas {
+        measurement = measurement.with_gas_verification();
+    }
+    if args.data_quality {
+        measurement = measurement.with_quality_check();
+    }
+    if let Some(classes) = args.gap_classes {
+        measurement = measurement.with_gap_classes(classes);
+    }
+    if let Some(spec) = args.watch_event {
+        measurement = measurement.with_event_watch(spec);
+    }
+    if args.ramp_report {
+        measurement = measurement.with_ramp_report();
+    }
+    if !args.sustained.is_empty() {
+        let intervals = args
+            .sustained
+            .iter()
+            .map(|&seconds| {
+                Duration::try_from_secs_f64(seconds)
+                    .ok()
+                    .filter(|d| !d.is_zero())
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| eyre::eyre!("--sustained needs positive interval lengths"))?;
+        measurement = measurement.with_sustained_max(&intervals);
+    }
+    if args.producers {
+        measurement = measurement.with_producers();
+    }
+    if args.show_hashes {
+        measurement = measurement.with_hashes();
+    }
+    if let Some(size) = args.topics {
+        measurement = measurement
+            .with_topic_leaderboard(TopicLeaderboard::new(size, args.topic_names.as_deref())?);
+    }
 
-    while let Some(header) = stream.next().await {
-        let block = provider
-            .get_block_by_hash(header.hash, BlockTransactionsKind::Hashes)
-            .await
-            .expect("Failed to get block")
-            .expect("Block does not exist");
-        measurement.record(block);
-        measurement.print(args.refresh);
+    // Only fetch the full transactions and receipts if a metric needs them.
+    let full = args.gas_split || args.bridge || args.mempool || args.ordering || args.workload;
+    let receipts = args.verify_gas
+        || args.data_quality
+        || args.workload
+        || args.events
+        || args.topics.is_some()
+        || args.failed_gas
+        || args.transaction_fees
+        || args.bridge
+        || args.watch_event.is_some()
+        || args.watch_address.is_some()
+        || args.watch_token.is_some();
+    if args.fetch == FetchMode::None && (full || receipts) {
+        eyre::bail!("--fetch none cannot be combined with metrics that need the block bodies");
+    }
+    let mut fetcher = Fetcher::new(
+        rpc.clone(),
+        FetchConfig {
+            headers_only: args.fetch == FetchMode::None,
+            transactions: if full || args.fetch == FetchMode::Full {
+                BlockTransactionsKind::Full
+            } else {
+                BlockTransactionsKind::Hashes
+            },
+            receipts,
+            count_transactions: args.count_transactions,
+        },
+    );
+    if let Some(max_blocks_per_second) = args.sample_above {
+        fetcher = fetcher.with_sampling(max_blocks_per_second);
+    }
+    if let Transport::Graphql = args.transport {
+        fetcher = fetcher.with_graphql(GraphQlClient::new(args.graphql_endpoint.clone()));
+    }
+
+    // Continue with the window of the previous run.
+    if let Some(path) = &args.state_file {
+        match state::load(path, &rpc).await {
+            Ok(saved) => {
+                let (restored, invalid) = measurement.restore(saved);
+                if restored > 0 {
+                    journal.log(
+                        Severity::Info,
+                        "state",
+                        format!("Restored {restored} blocks from {}", path.display()),
+                    );
+                }
+                if invalid > 0 {
+                    journal.log(
+                        Severity::Warn,
+                        "state",
+                        format!(
+                            "Skipped {invalid} blocks with invalid times in {}",
+                            path.display()
+                        ),
+                    );
+                }
+            }
+            Err(err) => journal.log(
+                Severity::Warn,
+                "state",
+                format!("Discarded the state in {}: {err:#}", path.display()),
+            ),
+        }
+    }
+
+    // Fill the window with the latest blocks so metrics are meaningful right away, skipping the
+    // restored ones.
+    if !args.no_backfill {
+        let head = rpc.get_block_number().await?;
+        let first = head.saturating_sub(args.window.saturating_sub(1)).max(
+            measurement
+                .last()
+                .map_or(0, |last| last.block.header.number + 1),
+        );
+        // A block that cannot be fetched leaves a gap instead of failing the start.
+        let fetched =
+            futures::future::join_all((first..=head).map(|number| fetcher.by_number(number))).await;
+        let (blocks, failed): (Vec<_>, Vec<_>) = fetched
+            .into_iter()
+            .zip(first..)
+            .partition(|(block, _)| block.is_ok());
+        if let Some((Err(err), number)) = failed.first() {
+            journal.log(
+                Severity::Warn,
+                "backfill",
+                format!(
+                    "Failed to backfill {} of {} blocks, first block {number}: {err}",
+                    failed.len(),
+                    head + 1 - first
+                ),
+            );
+        }
+        let blocks = blocks
+            .into_par_iter()
+            .filter_map(|(block, _)| block.ok().flatten())
+            .map(Decoded::new)
+            .collect::<Vec<_>>();
+        for decoded in blocks {
+            measurement.backfill(decoded);
+        }
+        for mismatch in measurement.take_gas_mismatches() {
+            journal.log(Severity::Warn, "gas-mismatch", mismatch);
+        }
+    }
+
+    let read_probe = args.read_probe.map(|address| {
+        ReadProbe::spawn(
+            rpc.clone(),
+            address,
+            args.read_probe_slot,
+            args.read_probe_calldata.clone(),
+            Duration::from_millis(args.read_probe_interval_ms),
+        )
+    });
+    let state_probe = args.state_probe.map(|address| {
+        StateProbe::spawn(
+            rpc.clone(),
+            address,
+            args.state_probe_slots as usize,
+            Duration::from_millis(args.state_probe_interval_ms),
+        )
+    });
+
+    let address_watch = args.watch_address.map(|address| {
+        let watch = AddressWatch::new(rpc.clone(), address);
+        match args.min_runway_minutes {
+            Some(minutes) => watch.with_runway_warning(
+                journal.clone(),
+                Duration::try_from_secs_f64(minutes * 60.0).unwrap_or(Duration::MAX),
+            ),
+            None => watch,
+        }
+    });
+    let token_watch = args.watch_token.map(TokenWatch::new);
+    let mut decline = args
+        .decline
+        .map(|rule| DeclineWatch::new(rule, journal.clone()));
+    let min_window_span = Duration::try_from_secs_f64(args.min_window_span)
+        .map_err(|_| eyre::eyre!("--min-window-span is too long"))?;
+    let auto_window = args
+        .auto_window
+        .map(|span| {
+            Duration::try_from_secs_f64(span)
+                .map(|span| span.max(Duration::from_millis(100)))
+                .map_err(|_| eyre::eyre!("--auto-window is too long"))
+        })
+        .transpose()?;
+    let mut window_sizer = WindowSizer::new(min_window_span, auto_window, journal.clone());
+    let upgrades = args
+        .watch_upgrades
+        .then(|| UpgradeWatch::spawn(rpc.clone(), Duration::from_secs(60), journal.clone()));
+    let peers = args.peers.then(|| {
+        PeerMonitor::spawn(
+            rpc.clone(),
+            Duration::from_secs(args.peers_interval),
+            args.min_peers,
+            journal.clone(),
+        )
+    });
+    let fees = args.fee_history.then(|| {
+        FeeHistory::spawn(
+            rpc.clone(),
+            Duration::from_secs(args.fee_history_interval),
+            args.fee_history_blocks,
+            args.fee_percentiles.clone(),
+            journal.clone(),
+        )
+    });
+    let self_load = args.self_load.then(|| {
+        let advice = if full {
+            "try without --gas-split, --bridge, --mempool, --ordering and --workload"
+        } else if receipts {
+            "try without the metrics that need receipts"
+        } else if args.fetch == FetchMode::Full {
+            "try --fetch hashes"
+        } else if args.fetch == FetchMode::Hashes {
+            "try --fetch none"
+        } else {
+            "try a lower --rpc-concurrency or a --max-rps"
+        };
+        SelfLoad::new(advice.to_string(), journal.clone())
+    });
+    let stages = args.stages.then(StageTimer::default);
+    let price = args.price_url.clone().map(|url| {
+        PriceFeed::spawn(
+            url,
+            args.price_field.clone(),
+            Duration::from_secs(args.price_interval.max(1)),
+            journal.clone(),
+        )
+    });
+    let node_metrics = args.node_metrics_url.clone().map(|url| {
+        NodeMetricsScraper::spawn(
+            url,
+            NodeMetricNames {
+                gas: args.node_gas_metric.clone(),
+                block_time: args.node_block_time_metric.clone(),
+            },
+            Duration::from_secs(args.node_metrics_interval),
+            journal.clone(),
+        )
+    });
+    let mempool = if args.mempool {
+        Some(Mempool::spawn(rpc.clone()).await?)
+    } else {
+        None
+    };
+
+    let mut tui = if args.tui {
+        Some(Tui::new(
+            Duration::from_secs(args.heatmap_minutes * 60),
+            Targets {
+                interval_ms: args.target_interval_ms,
+                transactions_per_second: args.target_tps,
+            },
+            journal.clone(),
+        )?)
+    } else {
+        None
+    };
+    let mut keys = tui.as_ref().map(Tui::keys);
+
+    let history = args
+        .history_minutes
+        .map(|minutes| History::new(Duration::from_secs(minutes * 60)));
+    if let (Some(history), Some(addr)) = (&history, args.history_addr) {
+        history.serve(addr).await?;
+    }
+    let mut rollup = (args.rollup_minutes > 0)
+        .then(|| {
+            Rollup::new(
+                Duration::from_secs(args.rollup_minutes * 60),
+                args.rollup_file.as_deref(),
+                journal.clone(),
+            )
+        })
+        .transpose()?;
+    let heartbeat = args.heartbeat_url.clone().map(|url| {
+        Heartbeat::spawn(
+            url,
+            Duration::from_secs(args.heartbeat_interval),
+            journal.clone(),
+        )
+    });
+    let mut dump_signal = signal(SignalKind::user_defined1())?;
+    let run_id = args.resume.clone().unwrap_or_else(new_run_id);
+    let options = SinkOptions {
+        refresh: args.refresh,
+        totals: args.totals,
+        chart: args.chart,
+        diff: args.diff,
+        journal: journal.clone(),
+        events: args.sink_events,
+        resume: args.resume.is_some(),
+        buckets: HistogramBuckets {
+            transactions: args.histogram_transactions.clone(),
+            gas: args.histogram_gas.clone(),
+            interval_ms: args.histogram_interval_ms.clone(),
+        },
+        queue: args.sink_queue as usize,
+    };
+    let mut sinks = if args.sinks.is_empty() && !args.tui {
+        Sinks::open(&[SinkSpec::Stdout], &options).await?
+    } else {
+        Sinks::open(&args.sinks, &options).await?
+    };
+    if let Some(rotation) = args.rotate {
+        sinks = sinks.with_rotation(rotation);
+    }
+    if let Some(retain) = args.retain {
+        sinks = sinks.with_retention(retain as usize);
+    }
+
+    let extras = Extras {
+        run_id: &run_id,
+        node_version: node_version.as_deref(),
+        rpc: &rpc,
+        fetcher: &fetcher,
+        read_probe: read_probe.as_ref(),
+        state_probe: state_probe.as_ref(),
+        first_delivery: first_delivery.as_ref(),
+        mempool: mempool.as_ref(),
+        address_watch: address_watch.as_ref(),
+        token_watch: token_watch.as_ref(),
+        node_metrics: node_metrics.as_ref(),
+        peers: peers.as_ref(),
+        upgrades: upgrades.as_ref(),
+        fees: fees.as_ref(),
+        self_load: self_load.as_ref(),
+        stages: stages.as_ref(),
+        price: price.as_ref(),
+        reference_clock: reference_clock.as_ref(),
+        derived: &args.derived,
+    };
+
+    let mut last_snapshot = None;
+    if let Some(mut snapshot) = measurement.snapshot() {
+        extras.complete(&mut snapshot);
+        snapshot.metrics.sinks = sinks.delivery();
+        if let Some(tui) = &mut tui {
+            tui.draw(&snapshot.metrics)?;
+        }
+        sinks.write(&snapshot);
+        if let Some(history) = &history {
+            history.record(&snapshot);
+        }
+        if let Some(rollup) = &mut rollup {
+            rollup.record(&snapshot)?;
+        }
+        last_snapshot = Some(snapshot);
+    }
+
+    let mut blocks = pin!(pipeline::spawn(
+        subscription,
+        rpc.clone(),
+        &fetcher,
+        args.pipeline_depth,
+    ));
+    let uploader = args
+        .upload
+        .clone()
+        .map(|target| Uploader::new(target, run_id.clone(), journal.clone()));
+    let mut control = match args.control_addr {
+        Some(addr) => Some(Control::serve(addr).await?),
+        None => None,
+    };
+    if let Some(control) = &control {
+        control.set_status(control_status(&measurement, &sinks));
+    }
+    let mut stall_check = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        // Stop on Ctrl-C so the TUI can restore the terminal.
+        let block = tokio::select! {
+            block = blocks.next() => match block {
+                Some(block) => block,
+                None => {
+                    journal.end_subscription();
+                    break;
+                }
+            },
+            _ = stall_check.tick() => {
+                if let (true, Some(tui)) = (journal.check_stall(), &mut tui) {
+                    tui.repaint()?;
+                }
+                // Keep the head age and connection of the refreshed panel current between blocks.
+                if args.refresh {
+                    sinks.repaint();
+                }
+                continue;
+            }
+            Some(key) = next_key(&mut keys) => {
+                if let Some(tui) = &mut tui {
+                    if !handle_key(
+                        key,
+                        tui,
+                        &mut measurement,
+                        last_snapshot.as_ref(),
+                        grafana.as_ref(),
+                    )? {
+                        break;
+                    }
+                }
+                continue;
+            }
+            Some(command) = next_command(&mut control) => {
+                apply_command(
+                    command,
+                    &mut measurement,
+                    &mut sinks,
+                    last_snapshot.as_ref(),
+                    history.as_ref(),
+                    uploader.as_ref(),
+                    &journal,
+                )
+                .await;
+                if let Some(control) = &control {
+                    control.set_status(control_status(&measurement, &sinks));
+                }
+                if let Some(tui) = &mut tui {
+                    tui.repaint()?;
+                }
+                continue;
+            }
+            _ = dump_signal.recv() => {
+                if let Some(history) = &history {
+                    match history.dump() {
+                        Ok(path) => {
+                            journal.log(
+                                Severity::Info,
+                                "history",
+                                format!("History dumped to {}", path.display()),
+                            );
+                            if let Some(uploader) = &uploader {
+                                uploader.upload(path);
+                            }
+                        }
+                        Err(err) => journal.log(
+                            Severity::Warn,
+                            "history",
+                            format!("Failed to dump history: {err}"),
+                        ),
+                    }
+                }
+                continue;
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        let number = block.number;
+        if let Some(depth) = measurement.reorg_depth(number, block.hash, block.parent_hash) {
+            journal.log(
+                Severity::Crit,
+                "reorg",
+                format!("Reorg of depth {depth} at block {number}"),
+            );
+        }
+        let decoded = match block.decoded {
+            Ok(Some(decoded)) => decoded,
+            Ok(None) => {
+                journal.log(
+                    Severity::Warn,
+                    "block",
+                    format!("Block {number} is still unavailable, skipping it"),
+                );
+                continue;
+            }
+            Err(err) => {
+                journal.log(
+                    Severity::Warn,
+                    "block",
+                    format!("Failed to get block {number}: {err}"),
+                );
+                continue;
+            }
+        };
+        if !measurement.record(decoded, block.received, block.fetched, block.received_at) {
+            continue;
+        }
+        for mismatch in measurement.take_gas_mismatches() {
+            journal.log(Severity::Warn, "gas-mismatch", mismatch);
+        }
+        window_sizer.observe(&mut measurement);
+        let recorded = Instant::now();
+        if let (Some(stages), Some(last)) = (&stages, measurement.last()) {
+            let header_ms = args
+                .header_time
+                .as_ref()
+                .and_then(|header_time| header_time.millis(&last.block))
+                .map_or(last.block.header.timestamp as f64 * 1000.0, |millis| {
+                    millis as f64
+                });
+            stages.observe_block(
+                header_ms,
+                block.received_at,
+                block.received,
+                block.fetched,
+                recorded,
+            );
+        }
+        journal.observe_block(number);
+        if let Some(heartbeat) = &heartbeat {
+            heartbeat.beat();
+        }
+        if let (Some(mempool), Some(last)) = (&mempool, measurement.last()) {
+            mempool.observe(&last.block, last.received);
+        }
+        if let (Some(upgrades), Some(last)) = (&upgrades, measurement.last()) {
+            upgrades.observe(&last.block.header);
+        }
+        if let (Some(fees), Some(last)) = (&fees, measurement.last()) {
+            fees.observe(&last.block.header);
+        }
+        if let (Some(watch), Some(last)) = (&address_watch, measurement.last()) {
+            watch.observe(
+                last.block.header.number,
+                last.receipts.as_deref().unwrap_or_default(),
+                measurement.window_size() as usize,
+            );
+        }
+        if let (Some(watch), Some(last)) = (&token_watch, measurement.last()) {
+            watch.observe(
+                last.receipts.as_deref().unwrap_or_default(),
+                measurement.window_size() as usize,
+            );
+        }
+        let Some(mut snapshot) = measurement.snapshot() else {
+            continue;
+        };
+        extras.complete(&mut snapshot);
+        snapshot.metrics.sinks = sinks.delivery();
+        journal.observe(&snapshot);
+        if let Some(decline) = &mut decline {
+            decline.observe(&snapshot);
+        }
+        if let Some(tui) = &mut tui {
+            tui.record(&measurement);
+            tui.draw(&snapshot.metrics)?;
+        }
+        sinks.write(&snapshot);
+        if let Some(stages) = &stages {
+            stages.observe_output(recorded);
+        }
+        match sinks.rotate_if_due().await {
+            Ok(rotated) if !rotated.is_empty() => {
+                if let Some(uploader) = &uploader {
+                    rotated.iter().for_each(|path| uploader.upload(path));
+                }
+                let rotated = rotated
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>();
+                journal.log(
+                    Severity::Info,
+                    "rotate",
+                    format!("Recordings rotated to {}", rotated.join(", ")),
+                );
+            }
+            Ok(_) => {}
+            Err(err) => journal.log(
+                Severity::Warn,
+                "rotate",
+                format!("Rotating the recordings failed: {err}"),
+            ),
+        }
+        if let Some(history) = &history {
+            history.record(&snapshot);
+        }
+        if let Some(rollup) = &mut rollup {
+            if let Err(err) = rollup.record(&snapshot) {
+                journal.log(
+                    Severity::Warn,
+                    "rollup",
+                    format!("Failed to write the rollup: {err}"),
+                );
+            }
+        }
+        if let (Some(rebroadcaster), Some(last)) = (&rebroadcaster, measurement.last()) {
+            rebroadcaster.send(last, &snapshot.metrics)?;
+        }
+        if let Some(control) = &control {
+            control.set_status(control_status(&measurement, &sinks));
+        }
+        last_snapshot = Some(snapshot);
+    }
+
+    // Let the sinks write the queued snapshots before the recordings are uploaded.
+    if args.refresh {
+        sinks.repaint();
+    }
+    let recordings = sinks.paths();
+    sinks.close().await;
+
+    if let Some(path) = &args.state_file {
+        if let Err(err) = state::save(path, &measurement) {
+            journal.log(
+                Severity::Warn,
+                "state",
+                format!("Failed to save the state to {}: {err:#}", path.display()),
+            );
+        }
+    }
+
+    // Roll up the rest of the last period.
+    if let Some(rollup) = &mut rollup {
+        if let Err(err) = rollup.finish() {
+            journal.log(
+                Severity::Warn,
+                "rollup",
+                format!("Failed to write the rollup: {err}"),
+            );
+        }
+    }
+
+    if let Some(mailer) = &mailer {
+        let body = match &last_snapshot {
+            Some(snapshot) => format!(
+                "{}\n{}\n",
+                snapshot.metrics.summary(),
+                snapshot.metrics.totals_line()
+            ),
+            None => "No block was recorded.\n".to_string(),
+        };
+        let subject = format!(
+            "telescope {}: run {run_id} ended",
+            delivery::label(&args.endpoint)
+        );
+        mailer.finish(subject, body).await;
+    }
+
+    // Upload the recordings as they stand at exit.
+    if let Some(uploader) = &uploader {
+        for path in recordings {
+            uploader.upload(path);
+        }
+        for path in args.events_file.iter().chain(&args.rollup_file) {
+            uploader.upload(path);
+        }
+        uploader.finish().await;
     }
 
     Ok(())
 }
 
-struct Measurement {
-    window_start: Instant,
-    buffer: Vec<Datapoint>,
-    window_size: u64,
+/// Subscribe to the new blocks of a provider as configured.
+async fn subscribe(
+    provider: &RootProvider<AnyNetwork>,
+    mode: SubscribeMode,
+) -> Result<BoxStream<'static, AnyRpcBlock>> {
+    Ok(match mode {
+        SubscribeMode::Headers => provider
+            .subscribe_blocks()
+            .await?
+            .into_stream()
+            .map(fetch::header_block)
+            .boxed(),
+        SubscribeMode::Full => provider
+            .subscribe::<_, AnyRpcBlock>(("newHeads", json!({ "includeTransactions": true })))
+            .await?
+            .into_stream()
+            .boxed(),
+    })
+}
+
+/// Wait for the next control command, forever if there is no control API.
+async fn next_command(control: &mut Option<Control>) -> Option<control::Command> {
+    match control {
+        Some(control) => control.next().await,
+        None => future::pending().await,
+    }
 }
 
-impl Measurement {
-    fn new(window_size: u64) -> Self {
-        Self {
-            window_start: Instant::now(),
-            buffer: Vec::with_capacity(window_size as usize + 1),
-            window_size,
+/// Apply a control command, logging its outcome.
+async fn apply_command(
+    command: control::Command,
+    measurement: &mut Measurement,
+    sinks: &mut Sinks,
+    snapshot: Option<&Snapshot>,
+    history: Option<&History>,
+    uploader: Option<&Uploader>,
+    journal: &Journal,
+) {
+    let upload = |path: &Path| {
+        if let Some(uploader) = uploader {
+            uploader.upload(path);
+        }
+    };
+    let outcome = match command {
+        control::Command::Window(window) => {
+            measurement.set_window_size(window);
+            Ok(format!("Window set to {window} blocks"))
+        }
+        control::Command::Sink { index, enabled } => sinks.set_enabled(index, enabled).map(|()| {
+            let action = if enabled { "enabled" } else { "disabled" };
+            format!("Sink {index} {action}")
+        }),
+        control::Command::Dump => {
+            let snapshot = snapshot
+                .ok_or_else(|| eyre::eyre!("no snapshot yet"))
+                .and_then(dump_snapshot);
+            match (snapshot, history.map(History::dump).transpose()) {
+                (Ok(snapshot), Ok(history)) => {
+                    upload(Path::new(&snapshot));
+                    let mut text = format!("Snapshot written to {snapshot}");
+                    if let Some(history) = history {
+                        upload(&history);
+                        text.push_str(&format!(", history to {}", history.display()));
+                    }
+                    Ok(text)
+                }
+                (Err(err), _) | (_, Err(err)) => Err(err),
+            }
         }
+        control::Command::Rotate => sinks.rotate().await.map(|rotated| {
+            rotated.iter().for_each(|path| upload(path));
+            let rotated = rotated
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>();
+            format!("Recordings rotated to {}", rotated.join(", "))
+        }),
+    };
+    match outcome {
+        Ok(text) => journal.log(Severity::Info, "control", text),
+        Err(err) => journal.log(
+            Severity::Warn,
+            "control",
+            format!("Control command failed: {err}"),
+        ),
     }
+}
 
-    /// Get the size of the buffer.
-    #[inline]
-    #[allow(unused)]
-    fn buffer_len(&self) -> usize {
-        self.buffer.len()
+/// Get the state reported by the control API.
+fn control_status(measurement: &Measurement, sinks: &Sinks) -> Status {
+    Status {
+        window: measurement.window_size(),
+        block_number: measurement.last().map(|last| last.block.header.number),
+        sinks: sinks.status(),
     }
+}
 
-    /// Record a new block in the buffer.
-    #[inline]
-    fn record(&mut self, block: Block) {
-        if let Some(last) = self.buffer.last() {
-            if last.block.header.number >= block.header.number {
-                return;
-            }
+/// Write a snapshot to a timestamped file in the working directory, returning its path.
+fn dump_snapshot(snapshot: &Snapshot) -> Result<String> {
+    let path = format!(
+        "telescope-snapshot-{}.json",
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+    fs::write(&path, serde_json::to_vec_pretty(snapshot)?)?;
+    Ok(path)
+}
+
+/// Wait for the next pressed key, forever if there is no TUI.
+async fn next_key(keys: &mut Option<tokio::sync::mpsc::UnboundedReceiver<u8>>) -> Option<u8> {
+    match keys {
+        Some(keys) => keys.recv().await,
+        None => future::pending().await,
+    }
+}
+
+/// Apply a TUI hotkey, returning `false` if telescope should stop.
+fn handle_key(
+    key: u8,
+    tui: &mut Tui,
+    measurement: &mut Measurement,
+    snapshot: Option<&Snapshot>,
+    grafana: Option<&Grafana>,
+) -> Result<bool> {
+    match key {
+        b'q' => return Ok(false),
+        b'p' => tui.toggle_pause(),
+        b'h' => tui.toggle_heatmap(),
+        b'+' | b'=' | b'-' => {
+            let window = if key == b'-' {
+                (measurement.window_size() - 1).max(2)
+            } else {
+                measurement.window_size() + 1
+            };
+            measurement.set_window_size(window);
+            tui.set_status(format!("Window: {window} blocks"));
         }
-        self.buffer.push(Datapoint::new(block));
-        if self.buffer.len() > self.window_size as usize {
-            let data_point = self.buffer.remove(0);
-            self.window_start = data_point.timestamp;
-        }
-    }
-
-    /// Calculate the transactions per second (TPS) using the data in the buffer.
-    #[inline]
-    fn transactions_per_second(&self) -> f64 {
-        let last_block = self.buffer.last().expect("Buffer is empty");
-        let time_window = last_block.timestamp - self.window_start;
-        let n_txs = self.buffer.iter().map(|b| b.transactions()).sum::<usize>();
-        n_txs as f64 / time_window.as_secs_f64()
-    }
-
-    /// Calculate the gas per second (gas/s) using the data in the buffer.
-    #[inline]
-    fn gas_per_second(&self) -> f64 {
-        let last_block = self.buffer.last().expect("Buffer is empty");
-        let time_window = last_block.timestamp - self.window_start;
-        let n_gas = self.buffer.iter().map(|b| b.gas_used()).sum::<u64>();
-        n_gas as f64 / time_window.as_secs_f64()
-    }
-
-    /// Calculate the mini-block rate (mini-blocks/s) using the data in the buffer.
-    #[inline]
-    fn mini_block_rate(&self) -> f64 {
-        let last_block = self.buffer.last().expect("Buffer is empty");
-        let time_window = last_block.timestamp - self.window_start;
-        let n_mini_blocks = self.buffer.iter().map(|b| b.mini_blocks()).sum::<u64>();
-        n_mini_blocks as f64 / time_window.as_secs_f64()
-    }
-
-    /// Print the current measurements.
-    #[inline]
-    fn print(&self, refresh: bool) {
-        let now = Local::now();
-        print!(
-            "\r[{}] Mini-block interval: {:.1} ms, TPS: {:.1}, Gas: {:.2} Mgas/s {}",
-            now.format("%Y-%m-%d %H:%M:%S%.6f"),
-            1000.0 / self.mini_block_rate(),
-            self.transactions_per_second(),
-            self.gas_per_second() / 1_000_000.0,
-            if refresh { "" } else { "\n" }
-        );
-        stdout().flush().unwrap();
+        b'r' => {
+            measurement.reset_totals();
+            tui.reset();
+            tui.set_status("Statistics reset");
+        }
+        b's' => match snapshot {
+            Some(snapshot) => match dump_snapshot(snapshot) {
+                Ok(path) => tui.set_status(format!("Snapshot written to {path}")),
+                Err(err) => tui.set_status(format!("Failed to write snapshot: {err}")),
+            },
+            None => tui.set_status("No snapshot yet"),
+        },
+        b'a' => match grafana {
+            Some(grafana) => {
+                let block = snapshot.map_or(0, |snapshot| snapshot.block_number);
+                grafana.annotate("mark", format!("Operator mark at block {block}"));
+                tui.set_status("Grafana annotation pushed");
+            }
+            None => tui.set_status("No Grafana configured"),
+        },
+        _ => return Ok(true),
     }
+    tui.repaint()?;
+    Ok(true)
 }
 
-/// Contains the data we sample from the blockchain.
-struct Datapoint {
-    timestamp: Instant,
-    block: Block,
+/// The components living outside the measurement whose metrics are added to every snapshot.
+struct Extras<'a> {
+    run_id: &'a str,
+    node_version: Option<&'a str>,
+    rpc: &'a RpcClient,
+    fetcher: &'a Fetcher,
+    read_probe: Option<&'a ReadProbe>,
+    state_probe: Option<&'a StateProbe>,
+    first_delivery: Option<&'a FirstDelivery>,
+    mempool: Option<&'a Mempool>,
+    address_watch: Option<&'a AddressWatch>,
+    token_watch: Option<&'a TokenWatch>,
+    node_metrics: Option<&'a NodeMetricsScraper>,
+    peers: Option<&'a PeerMonitor>,
+    upgrades: Option<&'a UpgradeWatch>,
+    fees: Option<&'a FeeHistory>,
+    self_load: Option<&'a SelfLoad>,
+    stages: Option<&'a StageTimer>,
+    price: Option<&'a PriceFeed>,
+    reference_clock: Option<&'a ReferenceClock>,
+    derived: &'a [Derived],
 }
 
-impl Datapoint {
-    fn new(block: Block) -> Self {
-        Self {
-            timestamp: Instant::now(),
-            block,
+impl Extras<'_> {
+    /// Add the metrics of the components to a snapshot.
+    fn complete(&self, snapshot: &mut Snapshot) {
+        snapshot.run_id = self.run_id.to_string();
+        snapshot.node_version = self.node_version.map(String::from);
+        if let Some(clock) = self.reference_clock {
+            snapshot.reference_timestamp = clock.correct(&snapshot.timestamp);
+            snapshot.metrics.reference_clock = clock.metrics();
+        }
+        snapshot.metrics.rpc = Some(self.rpc.stats());
+        snapshot.metrics.indexing = Some(self.fetcher.indexing_stats());
+        snapshot.metrics.sampling = self.fetcher.sampling();
+        if let Some(self_load) = self.self_load {
+            snapshot.metrics.self_load = Some(self_load.observe(snapshot));
+        }
+        if let Some(stages) = self.stages {
+            snapshot.metrics.stages = Some(stages.metrics());
+        }
+        if let (Some(fees), Some(usd)) = (
+            &mut snapshot.metrics.transaction_fees,
+            self.price.and_then(PriceFeed::usd),
+        ) {
+            fees.set_price(usd);
+        }
+        if let Some(read_probe) = self.read_probe {
+            read_probe.observe_load(
+                snapshot.metrics.transactions_per_second,
+                snapshot.metrics.gas_per_second,
+            );
+            snapshot.metrics.read_probe = Some(read_probe.metrics());
+        }
+        if let Some(state_probe) = self.state_probe {
+            state_probe.observe_load(snapshot.metrics.gas_per_second);
+            snapshot.metrics.state_probe = Some(state_probe.metrics());
+        }
+        if let Some(first_delivery) = self.first_delivery {
+            snapshot.metrics.first_delivery = Some(first_delivery.metrics());
+        }
+        if let Some(mempool) = self.mempool {
+            snapshot.metrics.mempool = Some(mempool.metrics());
+        }
+        if let Some(watch) = self.address_watch {
+            snapshot.metrics.address = Some(watch.metrics());
+        }
+        if let Some(watch) = self.token_watch {
+            snapshot.metrics.token = Some(watch.metrics());
+        }
+        if let Some(node_metrics) = self.node_metrics {
+            snapshot.metrics.node = Some(node_metrics.metrics(snapshot.metrics.gas_per_second));
+        }
+        if let Some(peers) = self.peers {
+            snapshot.metrics.peers = Some(peers.metrics());
+        }
+        if let Some(upgrades) = self.upgrades {
+            snapshot.metrics.upgrade = Some(upgrades.metrics());
+        }
+        if let Some(fees) = self.fees {
+            snapshot.metrics.fees = fees.metrics();
+        }
+        if !self.derived.is_empty() {
+            let fields = sink::flatten(snapshot).unwrap_or_default();
+            let field = |name: &str| {
+                fields
+                    .iter()
+                    .find(|(field, _)| field == name)
+                    .and_then(|(_, value)| value.as_f64())
+            };
+            snapshot.metrics.derived = Some(
+                self.derived
+                    .iter()
+                    .map(|d| (d.name.clone(), d.evaluate(&field).into()))
+                    .collect(),
+            );
         }
     }
+}
 
-    /// Get the gas used by the block.
-    #[inline]
-    fn gas_used(&self) -> u64 {
-        self.block.header.gas_used
+/// Assemble the command line with the options of the preset, the secrets and the configuration
+/// file ahead of the given ones, which override them.
+fn file_options(args: &Args, secrets: Vec<String>) -> Result<Vec<String>> {
+    let mut argv = std::env::args();
+    let mut options = vec![argv.next().unwrap_or_default()];
+    options.extend(args.preset.map(Preset::options).unwrap_or_default());
+    options.extend(secrets);
+    if let Some(path) = &args.config {
+        options.extend(config::options(path)?);
     }
+    options.extend(argv);
+    Ok(options)
+}
 
-    /// Get the number of transactions in the block.
-    #[inline]
-    fn transactions(&self) -> usize {
-        self.block.transactions.len()
+/// Generate a random (version 4) UUID identifying the run.
+fn new_run_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = alloy::primitives::hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Parse a number that must be positive and finite, e.g. a rate or a duration.
+fn positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        Ok(_) => Err(format!("`{s}` is not a positive number")),
+        Err(err) => Err(err.to_string()),
     }
+}
 
-    /// Calculate the number of mini-blocks in the block.
-    #[inline]
-    fn mini_blocks(&self) -> u64 {
-        let mut buf = self.block.header.extra_data.clone();
-        let fragment_count = buf.get_u8();
-        fragment_count as u64
+/// Parse a number that must be finite and not negative, e.g. a threshold where 0 disables it.
+fn non_negative(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+        Ok(_) => Err(format!("`{s}` is not a non-negative number")),
+        Err(err) => Err(err.to_string()),
     }
 }
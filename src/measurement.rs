@@ -0,0 +1,2151 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    consensus::{Transaction, EMPTY_ROOT_HASH},
+    network::{AnyRpcBlock, AnyTransactionReceipt},
+    primitives::{bytes::Buf, Address, B256, U256},
+};
+use chrono::Local;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    address::AddressMetrics,
+    bridge::{self, BridgeMetrics},
+    clock::{ClockDrift, ClockMetrics},
+    delivery::ProviderDelivery,
+    event::{EventMetrics, EventSpec, EventWatch},
+    fees::FeeMetrics,
+    fetch::{Fetched, IndexingStats},
+    gaps::{GapClasses, GapCounts, GapMetrics},
+    header_time::HeaderTime,
+    mempool::MempoolMetrics,
+    node_metrics::NodeMetrics,
+    ordering::{BlockOrdering, OrderingMetrics},
+    peers::PeerMetrics,
+    quality::{DataQuality, QualityCheck},
+    ramp::{RampMetrics, Slope},
+    read_probe::ReadProbeMetrics,
+    refclock::ReferenceClockMetrics,
+    rpc::RpcStats,
+    sampling::SamplingMetrics,
+    self_load::SelfLoadMetrics,
+    sink::SinkDelivery,
+    sketch::QuantileSketch,
+    stages::StageMetrics,
+    state_probe::StateProbeMetrics,
+    sustained::{SustainedMax, SustainedPeak},
+    token::TokenMetrics,
+    topics::{TopicLeaderboard, TopicRank},
+    upgrade::UpgradeMetrics,
+    workload::{self, BlockWorkload, Category, CategoryShare},
+};
+
+/// The time of a block the rates are based on.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum RateClock {
+    /// When the header was received; unaffected by slow block fetches.
+    Receive,
+    /// When the block data fetch completed.
+    Fetch,
+    /// The millisecond-precision time the header carries, see `--header-time`; blocks without
+    /// one fall back to the receive time.
+    Header,
+}
+
+/// The relative accuracy of the mini-block interval percentiles.
+const INTERVAL_ACCURACY: f64 = 0.01;
+
+pub struct Measurement {
+    buffer: Vec<Datapoint>,
+    window_size: u64,
+    rate_clock: RateClock,
+    clock: Option<ClockDrift>,
+    event: Option<EventWatch>,
+    /// Report the throughput trends.
+    ramp: bool,
+    /// Report the block share of the producers.
+    producers: bool,
+    /// Show the hash of the newest block and record its parent's.
+    hashes: bool,
+    /// Ranks the event signatures of the window's logs, if enabled.
+    topics: Option<TopicLeaderboard>,
+    totals: Totals,
+    /// The mini-block intervals of the buffered blocks in milliseconds.
+    intervals: QuantileSketch,
+    /// Number of blocks whose header gas disagreed with their receipts, if verified.
+    gas_mismatches: Option<u64>,
+    /// The mismatches found since they were last taken, to be logged as events.
+    unlogged_gas_mismatches: Vec<String>,
+    /// Cross-checks the blocks and receipts for consistency, if enabled.
+    quality: Option<QualityCheck>,
+    /// The boundaries the gaps between the blocks are classified by, if enabled.
+    gap_classes: Option<GapClasses>,
+    /// The classified gaps between the live blocks since the start.
+    gap_totals: GapCounts,
+    /// The blocks rejected by `record` since the start.
+    dropped: DroppedBlocks,
+    /// The best throughput of the live blocks sustained over each interval.
+    sustained: Vec<SustainedMax>,
+    /// Decodes the millisecond header times for the `header` rate clock.
+    header_time: Option<HeaderTime>,
+    /// The header time of the first block and its receive time, mapping the header times onto
+    /// the monotonic clock.
+    header_anchor: Option<(u64, Instant)>,
+}
+
+impl Measurement {
+    pub fn new(window_size: u64) -> Self {
+        Self {
+            buffer: Vec::with_capacity(window_size as usize + 1),
+            window_size,
+            rate_clock: RateClock::Receive,
+            clock: None,
+            event: None,
+            ramp: false,
+            producers: false,
+            hashes: false,
+            topics: None,
+            totals: Totals::new(),
+            intervals: QuantileSketch::new(INTERVAL_ACCURACY),
+            gas_mismatches: None,
+            unlogged_gas_mismatches: Vec::new(),
+            quality: None,
+            gap_classes: None,
+            gap_totals: GapCounts::default(),
+            dropped: DroppedBlocks::default(),
+            sustained: Vec::new(),
+            header_time: None,
+            header_anchor: None,
+        }
+    }
+
+    /// Choose which time of a block the rates are based on.
+    pub fn with_rate_clock(mut self, rate_clock: RateClock) -> Self {
+        self.rate_clock = rate_clock;
+        self
+    }
+
+    /// Decode the millisecond-precision header times, which the `header` rate clock uses.
+    pub fn with_header_time(mut self, header_time: HeaderTime) -> Self {
+        self.header_time = Some(header_time);
+        self
+    }
+
+    /// Verify the header gas of every block against the sum of its receipts' gas.
+    pub fn with_gas_verification(mut self) -> Self {
+        self.gas_mismatches = Some(0);
+        self
+    }
+
+    /// Cross-check every block for consistency with its receipts and its predecessor.
+    pub fn with_quality_check(mut self) -> Self {
+        self.quality = Some(QualityCheck::default());
+        self
+    }
+
+    /// Classify the gaps between the blocks as normal, slow or stalls.
+    pub fn with_gap_classes(mut self, classes: GapClasses) -> Self {
+        self.gap_classes = Some(classes);
+        self
+    }
+
+    /// Track the best throughput sustained over sliding intervals of the given lengths.
+    pub fn with_sustained_max(mut self, intervals: &[Duration]) -> Self {
+        self.sustained = intervals.iter().copied().map(SustainedMax::new).collect();
+        self
+    }
+
+    /// Estimate the clock drift against the node, optionally correcting the propagation latency.
+    pub fn with_clock_drift(mut self, correction: bool) -> Self {
+        self.clock = Some(ClockDrift::new(self.window_size as usize, correction));
+        self
+    }
+
+    /// Measure the interval of a recurring event in the receipts.
+    pub fn with_event_watch(mut self, spec: EventSpec) -> Self {
+        self.event = Some(EventWatch::new(spec));
+        self
+    }
+
+    /// Detect trends in the throughput over the window.
+    pub fn with_ramp_report(mut self) -> Self {
+        self.ramp = true;
+        self
+    }
+
+    /// Break the window down by block producer (the header beneficiary).
+    pub fn with_producers(mut self) -> Self {
+        self.producers = true;
+        self
+    }
+
+    /// Show the shortened hash of the newest block in the summary and record the hash of its
+    /// parent, to cross-reference the output with explorers and node logs.
+    pub fn with_hashes(mut self) -> Self {
+        self.hashes = true;
+        self
+    }
+
+    /// Rank the event signatures (topic0) of the window's logs.
+    pub fn with_topic_leaderboard(mut self, leaderboard: TopicLeaderboard) -> Self {
+        self.topics = Some(leaderboard);
+        self
+    }
+
+    /// Get the window size (number of blocks).
+    #[inline]
+    pub fn window_size(&self) -> u64 {
+        self.window_size
+    }
+
+    /// Change the window size, evicting the oldest blocks if it shrinks.
+    pub fn set_window_size(&mut self, window_size: u64) {
+        self.window_size = window_size;
+        let excess = self.buffer.len().saturating_sub(window_size as usize);
+        self.evict(excess);
+    }
+
+    /// Reset the counters accumulated since the start.
+    pub fn reset_totals(&mut self) {
+        self.totals = Totals::new();
+        self.gap_totals = GapCounts::default();
+        self.dropped = DroppedBlocks::default();
+        for sustained in &mut self.sustained {
+            *sustained = SustainedMax::new(sustained.interval());
+        }
+        if let Some(mismatches) = &mut self.gas_mismatches {
+            *mismatches = 0;
+        }
+        if let Some(quality) = &mut self.quality {
+            *quality = QualityCheck::default();
+        }
+        if let Some(event) = &mut self.event {
+            event.reset();
+        }
+    }
+
+    /// Take the descriptions of the gas mismatches found since the previous call.
+    pub fn take_gas_mismatches(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.unlogged_gas_mismatches)
+    }
+
+    /// Get the size of the buffer.
+    #[inline]
+    pub fn buffer_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Get the most recently recorded datapoint.
+    #[inline]
+    pub fn last(&self) -> Option<&Datapoint> {
+        self.buffer.last()
+    }
+
+    /// Record a new block in the buffer, whose header was received at `received` (`received_at`
+    /// on the wall clock) and whose data was fetched at `fetched`, if at all.
+    ///
+    /// Returns `false` if the block was dropped because it is not newer than the last one.
+    #[inline]
+    pub fn record(
+        &mut self,
+        decoded: Decoded,
+        received: Instant,
+        fetched: Option<Instant>,
+        received_at: SystemTime,
+    ) -> bool {
+        if !self.is_newer(&decoded.fetched.block) {
+            self.drop_block(&decoded.fetched.block);
+            return false;
+        }
+        let mut datapoint =
+            Datapoint::new(decoded, received, fetched, received_at, self.rate_clock);
+        datapoint.processing_lag = Some(received.elapsed());
+        if let Some(clock) = &mut self.clock {
+            clock.record(datapoint.block.header.timestamp, datapoint.received_at);
+        }
+        self.totals.record(&datapoint);
+        self.push(datapoint);
+        if let Some(last) = self.buffer.last() {
+            for sustained in &mut self.sustained {
+                sustained.record(last.timestamp, last.transactions(), last.gas_used());
+            }
+        }
+        if let (Some(classes), Some(gap_ms)) = (
+            self.gap_classes,
+            self.buffer.last().and_then(|last| last.gap_ms),
+        ) {
+            self.gap_totals.add(classes.classify(gap_ms));
+        }
+        true
+    }
+
+    /// Record a historical block, timestamped by its header rather than by when it was received.
+    ///
+    /// Backfilled blocks are not fed to the clock drift estimator since their receive time is
+    /// derived from the header.
+    pub fn backfill(&mut self, decoded: Decoded) -> bool {
+        if !self.is_newer(&decoded.fetched.block) {
+            return false;
+        }
+        let received_at = UNIX_EPOCH + Duration::from_secs(decoded.fetched.block.header.timestamp);
+        let age = SystemTime::now()
+            .duration_since(received_at)
+            .unwrap_or_default();
+        let timestamp = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+        self.push(Datapoint::new(
+            decoded,
+            timestamp,
+            None,
+            received_at,
+            self.rate_clock,
+        ));
+        true
+    }
+
+    /// Get the buffered blocks in a form that outlives the process, see [`SavedBlock`].
+    pub fn save(&self) -> Vec<SavedBlock> {
+        self.buffer.iter().map(SavedBlock::new).collect()
+    }
+
+    /// Refill the buffer with blocks saved by a previous run, which keep their receive times.
+    ///
+    /// The restored blocks are not counted in the run totals. Returns the number of restored
+    /// blocks and the number of those skipped because their times are invalid, e.g. in an edited
+    /// state file.
+    pub fn restore(&mut self, saved: Vec<SavedBlock>) -> (usize, usize) {
+        let (mut restored, mut invalid) = (0, 0);
+        for block in saved {
+            let SavedBlock {
+                block,
+                receipts,
+                transaction_count,
+                received_at,
+                fetch_latency,
+                processing_lag,
+                skipped,
+            } = block;
+            if !self.is_newer(&block) {
+                continue;
+            }
+            // `None` if invalid, `Some(None)` if not saved.
+            let seconds = |seconds: Option<f64>| {
+                seconds.map_or(Some(None), |seconds| {
+                    Duration::try_from_secs_f64(seconds).ok().map(Some)
+                })
+            };
+            let (Some(received_at), Some(fetch_latency), Some(processing_lag)) = (
+                Duration::try_from_secs_f64(received_at)
+                    .ok()
+                    .and_then(|since| UNIX_EPOCH.checked_add(since)),
+                seconds(fetch_latency),
+                seconds(processing_lag),
+            ) else {
+                invalid += 1;
+                continue;
+            };
+            let age = SystemTime::now()
+                .duration_since(received_at)
+                .unwrap_or_default();
+            let received = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+            let fetched = fetch_latency.and_then(|latency| received.checked_add(latency));
+            let decoded = Decoded::new(Fetched {
+                block,
+                receipts,
+                transaction_count,
+                sampled: !skipped,
+            });
+            let mut datapoint =
+                Datapoint::new(decoded, received, fetched, received_at, self.rate_clock);
+            datapoint.processing_lag = processing_lag;
+            self.push(datapoint);
+            restored += 1;
+        }
+        (restored, invalid)
+    }
+
+    /// Detect whether a new head reorganizes the recorded chain, returning the reorg depth.
+    ///
+    /// A head reorganizes the chain if its parent differs from the block recorded at its parent's
+    /// number, or if it replaces a recorded block with a different hash. A re-delivery of a
+    /// recorded block, or a block whose neighbours were not recorded, is no reorg.
+    pub fn reorg_depth(&self, number: u64, hash: B256, parent_hash: B256) -> Option<u64> {
+        let last = self.buffer.last()?.block.header.number;
+        // Sorted out as a duplicate by `drop_block`.
+        if self.buffer.iter().any(|b| b.block.header.hash == hash) {
+            return None;
+        }
+        let recorded = |number: u64| {
+            self.buffer
+                .iter()
+                .find(|b| b.block.header.number == number)
+                .map(|b| b.block.header.hash)
+        };
+        if number
+            .checked_sub(1)
+            .and_then(recorded)
+            .is_some_and(|parent| parent != parent_hash)
+        {
+            // The recorded parent and all its recorded successors are replaced.
+            Some(last + 2 - number)
+        } else if recorded(number).is_some() {
+            Some(last + 1 - number)
+        } else {
+            None
+        }
+    }
+
+    /// Check whether the block is newer than the last recorded one.
+    #[inline]
+    fn is_newer(&self, block: &AnyRpcBlock) -> bool {
+        self.buffer
+            .last()
+            .is_none_or(|last| last.block.header.number < block.header.number)
+    }
+
+    /// Count a block that is not newer than the last recorded one by why it was delivered.
+    fn drop_block(&mut self, block: &AnyRpcBlock) {
+        let header = &block.header;
+        let first = self.buffer.first().map_or(0, |b| b.block.header.number);
+        if self
+            .buffer
+            .iter()
+            .any(|b| b.block.header.hash == header.hash)
+        {
+            self.dropped.duplicate += 1;
+        } else if header.number >= first
+            && self
+                .buffer
+                .iter()
+                .all(|b| b.block.header.number != header.number)
+        {
+            // A block missing from the window which arrived after its successors.
+            self.dropped.out_of_order += 1;
+        } else {
+            self.dropped.stale += 1;
+        }
+    }
+
+    /// Push a datapoint into the buffer, evicting the oldest one if the window is full.
+    #[inline]
+    fn push(&mut self, mut datapoint: Datapoint) {
+        if let RateClock::Header = self.rate_clock {
+            if let Some(timestamp) = self.header_timestamp(&datapoint) {
+                datapoint.timestamp = timestamp;
+            }
+        }
+        if let Some(mismatches) = self.gas_mismatches.as_mut().filter(|_| datapoint.sampled) {
+            let receipt_gas = datapoint.receipt_gas_used();
+            if receipt_gas != Some(datapoint.gas_used()) {
+                *mismatches += 1;
+                self.unlogged_gas_mismatches.push(format!(
+                    "Gas mismatch in block {}: header {}, receipts {}",
+                    datapoint.block.header.number,
+                    datapoint.gas_used(),
+                    receipt_gas.map_or("unavailable".to_string(), |gas| gas.to_string()),
+                ));
+            }
+        }
+        if let Some(quality) = &mut self.quality {
+            let previous = self.buffer.last().map(|p| p.block.header.timestamp);
+            datapoint.inconsistent =
+                quality.check(&datapoint.block, datapoint.receipts.as_deref(), previous);
+        }
+        if let (Some(event), Some(receipts)) = (&mut self.event, &datapoint.receipts) {
+            event.record(datapoint.timestamp, receipts);
+        }
+        if let Some(previous) = self.buffer.last() {
+            let interval = datapoint
+                .timestamp
+                .saturating_duration_since(previous.timestamp);
+            let interval_ms =
+                interval.as_secs_f64() * 1000.0 / datapoint.mini_blocks().max(1) as f64;
+            datapoint.gap_ms = Some(interval.as_secs_f64() * 1000.0);
+            datapoint.mini_block_interval_ms = Some(interval_ms);
+            self.intervals.add(interval_ms);
+        }
+        self.buffer.push(datapoint);
+        let excess = self.buffer.len().saturating_sub(self.window_size as usize);
+        self.evict(excess);
+    }
+
+    /// Map the millisecond header time of a block onto the monotonic clock, anchored at the first
+    /// block carrying one.
+    #[inline]
+    fn header_timestamp(&mut self, datapoint: &Datapoint) -> Option<Instant> {
+        let millis = self.header_time.as_ref()?.millis(&datapoint.block)?;
+        let (anchor_millis, anchor) = *self
+            .header_anchor
+            .get_or_insert((millis, datapoint.received));
+        if millis >= anchor_millis {
+            Some(anchor + Duration::from_millis(millis - anchor_millis))
+        } else {
+            anchor.checked_sub(Duration::from_millis(anchor_millis - millis))
+        }
+    }
+
+    /// Drop the `count` oldest datapoints from the buffer.
+    fn evict(&mut self, count: usize) {
+        for datapoint in self.buffer.drain(..count) {
+            if let Some(interval_ms) = datapoint.mini_block_interval_ms {
+                self.intervals.remove(interval_ms);
+            }
+        }
+    }
+
+    /// Estimate the percentiles of the mini-block intervals in the window from a sketch, so
+    /// they take the same memory however large the window.
+    pub fn mini_block_interval_percentiles(&self) -> Option<IntervalPercentiles> {
+        Some(IntervalPercentiles {
+            p50_ms: self.intervals.quantile(0.5)?,
+            p90_ms: self.intervals.quantile(0.9)?,
+            p99_ms: self.intervals.quantile(0.99)?,
+        })
+    }
+
+    /// Get the blocks the rates are computed over and the seconds they span.
+    ///
+    /// The window starts at the oldest block in the buffer, whose transactions and gas were
+    /// produced before that and are not counted. Returns `None` until the buffer spans some time.
+    #[inline]
+    fn rate_window(&self) -> Option<(&[Datapoint], f64)> {
+        let (first, blocks) = self.buffer.split_first()?;
+        let span = (blocks.last()?.timestamp - first.timestamp).as_secs_f64();
+        (span > 0.0).then_some((blocks, span))
+    }
+
+    /// Get the time from the oldest to the newest block of the window on the rate clock, unknown
+    /// until the window spans some time.
+    pub fn window_span(&self) -> Option<Duration> {
+        let span = self.rate_window()?.1;
+        Some(Duration::from_secs_f64(span))
+    }
+
+    /// Get the blocks of the rate window the detailed analyses cover and the seconds they
+    /// represent: the span of the window scaled by their share of its blocks, so their rates
+    /// estimate the rates of all blocks when only a sample is analyzed.
+    #[inline]
+    fn sampled_window(&self) -> Option<(Vec<&Datapoint>, f64)> {
+        let (blocks, time_window) = self.rate_window()?;
+        let sampled = blocks.iter().filter(|b| b.sampled).collect::<Vec<_>>();
+        let share = sampled.len() as f64 / blocks.len() as f64;
+        (!sampled.is_empty()).then_some((sampled, time_window * share))
+    }
+
+    /// Calculate the transactions per second (TPS) using the data in the buffer.
+    ///
+    /// Returns `None` unless the transactions of every block in the buffer were fetched.
+    #[inline]
+    pub fn transactions_per_second(&self) -> Option<f64> {
+        let (blocks, time_window) = self.rate_window()?;
+        let n_txs = blocks
+            .iter()
+            .map(|b| b.transactions())
+            .sum::<Option<usize>>()?;
+        Some(n_txs as f64 / time_window)
+    }
+
+    /// Calculate the gas per second (gas/s) using the data in the buffer.
+    #[inline]
+    pub fn gas_per_second(&self) -> f64 {
+        let (blocks, time_window) = self.rate_window().expect("Buffer spans no time");
+        let n_gas = blocks.iter().map(|b| b.gas_used()).sum::<u64>();
+        n_gas as f64 / time_window
+    }
+
+    /// Calculate the mini-block rate (mini-blocks/s) using the data in the buffer.
+    #[inline]
+    pub fn mini_block_rate(&self) -> f64 {
+        let (blocks, time_window) = self.rate_window().expect("Buffer spans no time");
+        let n_mini_blocks = blocks.iter().map(|b| b.mini_blocks()).sum::<u64>();
+        n_mini_blocks as f64 / time_window
+    }
+
+    /// Calculate the logs emitted per second using the data in the buffer.
+    ///
+    /// Returns `None` unless every sampled block in the buffer carries its receipts.
+    #[inline]
+    pub fn events_per_second(&self) -> Option<f64> {
+        let (blocks, time_window) = self.sampled_window()?;
+        let n_events = blocks.iter().map(|b| b.logs()).sum::<Option<usize>>()?;
+        Some(n_events as f64 / time_window)
+    }
+
+    /// Rank the event signatures of the logs in the buffer.
+    ///
+    /// Returns `None` unless every sampled block in the buffer carries its receipts.
+    #[inline]
+    pub fn topics(&self, leaderboard: &TopicLeaderboard) -> Option<Vec<TopicRank>> {
+        let (blocks, time_window) = self.sampled_window()?;
+        let receipts = blocks
+            .iter()
+            .map(|b| b.receipts.as_deref())
+            .collect::<Option<Vec<_>>>()?;
+        Some(leaderboard.rank(receipts, time_window))
+    }
+
+    /// Count the blocks in the buffer whose transaction list was truncated.
+    ///
+    /// Returns `None` unless the transactions are counted separately.
+    #[inline]
+    pub fn truncated_blocks(&self) -> Option<usize> {
+        self.buffer
+            .iter()
+            .any(|b| b.transaction_count.is_some())
+            .then(|| self.buffer.iter().filter(|b| b.is_truncated()).count())
+    }
+
+    /// Sum up the gas burned by reverted transactions in the buffer.
+    ///
+    /// Returns `None` unless every sampled block in the buffer carries its receipts.
+    #[inline]
+    pub fn failed_gas(&self) -> Option<FailedGas> {
+        let (mut transactions, mut gas_used, mut total_gas) = (0, 0, 0);
+        for block in self.buffer.iter().filter(|b| b.sampled) {
+            for receipt in block.receipts.as_deref()? {
+                total_gas += receipt.gas_used;
+                if !receipt.inner.inner.status() {
+                    transactions += 1;
+                    gas_used += receipt.gas_used;
+                }
+            }
+        }
+        Some(FailedGas {
+            transactions,
+            gas_used,
+            percentage: if total_gas > 0 {
+                100.0 * gas_used as f64 / total_gas as f64
+            } else {
+                0.0
+            },
+        })
+    }
+
+    /// Sum up the fees paid by the transactions in the buffer, including the L1 data fees of
+    /// rollup receipts.
+    ///
+    /// Returns `None` unless every sampled block in the buffer carries its receipts.
+    #[inline]
+    pub fn transaction_fees(&self) -> Option<TransactionFees> {
+        let (mut transactions, mut total_wei) = (0, 0.0);
+        for block in self.buffer.iter().filter(|b| b.sampled) {
+            for receipt in block.receipts.as_deref()? {
+                let l1_fee = receipt
+                    .other
+                    .get_deserialized::<U256>("l1Fee")
+                    .and_then(Result::ok)
+                    .map_or(0.0, f64::from);
+                transactions += 1;
+                total_wei += receipt.gas_used as f64 * receipt.effective_gas_price as f64 + l1_fee;
+            }
+        }
+        Some(TransactionFees {
+            transactions,
+            total_wei,
+            average_wei: (transactions > 0).then(|| total_wei / transactions as f64),
+            total_usd: None,
+            average_usd: None,
+        })
+    }
+
+    /// Calculate the bridging activity using the data in the buffer.
+    ///
+    /// Returns `None` unless every sampled block in the buffer carries full transactions and
+    /// receipts.
+    #[inline]
+    pub fn bridge(&self) -> Option<BridgeMetrics> {
+        let (blocks, time_window) = self.sampled_window()?;
+        let deposits = blocks.iter().map(|b| b.deposits).sum::<Option<usize>>()?;
+        let withdrawals = blocks
+            .iter()
+            .map(|b| b.receipts.as_deref().map(bridge::withdrawals))
+            .sum::<Option<usize>>()?;
+        Some(BridgeMetrics {
+            deposits_per_second: deposits as f64 / time_window,
+            withdrawals_per_second: withdrawals as f64 / time_window,
+            window_deposits: deposits,
+            window_withdrawals: withdrawals,
+        })
+    }
+
+    /// Sum up the same-sender patterns of the blocks in the buffer.
+    ///
+    /// Returns `None` unless every sampled block in the buffer carries full transaction bodies.
+    #[inline]
+    pub fn ordering(&self) -> Option<OrderingMetrics> {
+        let blocks = self
+            .buffer
+            .iter()
+            .filter(|b| b.sampled)
+            .map(|b| b.ordering)
+            .collect::<Option<Vec<_>>>()?;
+        Some(OrderingMetrics::new(blocks))
+    }
+
+    /// Break the throughput of the window down by transaction category.
+    ///
+    /// Returns `None` unless every sampled block in the buffer carries full transactions and
+    /// receipts.
+    #[inline]
+    pub fn workload(&self) -> Option<BTreeMap<Category, CategoryShare>> {
+        let (blocks, time_window) = self.sampled_window()?;
+        let blocks = blocks
+            .iter()
+            .map(|b| b.workload)
+            .collect::<Option<Vec<_>>>()?;
+        Some(workload::shares(blocks, time_window))
+    }
+
+    /// Calculate the average time from receiving a header until its block data was fetched.
+    ///
+    /// Returns `None` if no block in the buffer was fetched live.
+    #[inline]
+    pub fn fetch_latency_ms(&self) -> Option<f64> {
+        let latencies = self
+            .buffer
+            .iter()
+            .filter_map(|b| Some(b.fetched?.duration_since(b.received).as_secs_f64() * 1000.0))
+            .collect::<Vec<_>>();
+        (!latencies.is_empty()).then(|| latencies.iter().sum::<f64>() / latencies.len() as f64)
+    }
+
+    /// Calculate the average time from receiving a header until its block was recorded, i.e.
+    /// fetched and decoded.
+    ///
+    /// Returns `None` if no block in the buffer was received live.
+    #[inline]
+    pub fn processing_lag_ms(&self) -> Option<f64> {
+        let lags = self
+            .buffer
+            .iter()
+            .filter_map(|b| Some(b.processing_lag?.as_secs_f64() * 1000.0))
+            .collect::<Vec<_>>();
+        (!lags.is_empty()).then(|| lags.iter().sum::<f64>() / lags.len() as f64)
+    }
+
+    /// Count the blocks in the buffer without any transaction.
+    #[inline]
+    pub fn empty_blocks(&self) -> usize {
+        self.buffer.iter().filter(|b| b.is_empty()).count()
+    }
+
+    /// Split the gas rate into execution and data availability (calldata) gas per second.
+    ///
+    /// Returns `None` unless every sampled block in the buffer carries full transaction bodies.
+    #[inline]
+    pub fn gas_split(&self) -> Option<GasSplit> {
+        let (blocks, time_window) = self.sampled_window()?;
+        let calldata_gas = blocks.iter().map(|b| b.calldata_gas).sum::<Option<u64>>()?;
+        let n_gas = blocks.iter().map(|b| b.gas_used()).sum::<u64>();
+        let blob_gas = blocks.iter().map(|b| b.blob_gas_used()).sum::<u64>();
+        Some(GasSplit {
+            execution_gas_per_second: n_gas.saturating_sub(calldata_gas) as f64 / time_window,
+            calldata_gas_per_second: calldata_gas as f64 / time_window,
+            blob_gas_per_second: blob_gas as f64 / time_window,
+        })
+    }
+
+    /// Break the blocks in the buffer down by their producer.
+    ///
+    /// The rates count the blocks after the oldest one, like the window-wide rates.
+    #[inline]
+    pub fn producers(&self) -> BTreeMap<Address, ProducerShare> {
+        let mut producers = BTreeMap::<Address, ProducerShare>::new();
+        for block in &self.buffer {
+            producers
+                .entry(block.block.header.beneficiary)
+                .or_default()
+                .blocks += 1;
+        }
+        if let Some((blocks, time_window)) = self.rate_window() {
+            for block in blocks {
+                let share = producers.entry(block.block.header.beneficiary).or_default();
+                share.transactions_per_second = block.transactions().map(|transactions| {
+                    share.transactions_per_second.unwrap_or_default()
+                        + transactions as f64 / time_window
+                });
+                share.gas_per_second += block.gas_used() as f64 / time_window;
+            }
+        }
+        for share in producers.values_mut() {
+            share.share_percentage = 100.0 * share.blocks as f64 / self.buffer.len() as f64;
+        }
+        producers
+    }
+
+    /// Fit the transactions and gas of the blocks in the buffer against time.
+    #[inline]
+    pub fn ramp(&self) -> RampMetrics {
+        let block_interval = match self.buffer.as_slice() {
+            [first, .., last] => {
+                (last.timestamp - first.timestamp).as_secs_f64() / (self.buffer.len() - 1) as f64
+            }
+            _ => 0.0,
+        };
+        let transactions = self
+            .buffer
+            .iter()
+            .map(|b| Some((b.timestamp, b.transactions()? as f64)))
+            .collect::<Option<Vec<_>>>();
+        let gas = self
+            .buffer
+            .iter()
+            .map(|b| (b.timestamp, b.gas_used() as f64))
+            .collect::<Vec<_>>();
+        RampMetrics {
+            transactions_per_second: transactions
+                .and_then(|transactions| Slope::fit(&transactions, block_interval)),
+            gas_per_second: Slope::fit(&gas, block_interval),
+        }
+    }
+
+    /// Collect the derived metrics of the current window.
+    #[inline]
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            transactions_per_second: self.transactions_per_second(),
+            gas_per_second: self.gas_per_second(),
+            mini_block_interval_ms: 1000.0 / self.mini_block_rate(),
+            mini_block_interval_percentiles: self.mini_block_interval_percentiles(),
+            head_hash: self
+                .buffer
+                .last()
+                .filter(|_| self.hashes)
+                .map(|last| last.block.header.hash),
+            head_block: self
+                .buffer
+                .last()
+                .map_or(0, |last| last.block.header.number),
+            head_age_ms: self.buffer.last().map_or(0.0, |last| {
+                let age = SystemTime::now().duration_since(last.received_at);
+                age.unwrap_or_default().as_secs_f64() * 1000.0
+            }),
+            head_received_at: self.buffer.last().map(|last| last.received_at),
+            window_blocks: self.buffer.len(),
+            fetch_latency_ms: self.fetch_latency_ms(),
+            processing_lag_ms: self.processing_lag_ms(),
+            empty_blocks: self.empty_blocks(),
+            empty_block_percentage: 100.0 * self.empty_blocks() as f64 / self.buffer.len() as f64,
+            gas_split: self.gas_split(),
+            events_per_second: self.events_per_second(),
+            failed_gas: self.failed_gas(),
+            transaction_fees: self.transaction_fees(),
+            truncated_blocks: self.truncated_blocks(),
+            bridge: self.bridge(),
+            ordering: self.ordering(),
+            workload: self.workload(),
+            clock: self.clock.as_ref().map(ClockDrift::metrics),
+            event: self.event.as_ref().map(EventWatch::metrics),
+            ramp: self.ramp.then(|| self.ramp()),
+            producers: self.producers.then(|| self.producers()),
+            topics: self
+                .topics
+                .as_ref()
+                .and_then(|leaderboard| self.topics(leaderboard)),
+            totals: self.totals.metrics(),
+            sustained: (!self.sustained.is_empty())
+                .then(|| self.sustained.iter().map(SustainedMax::metrics).collect()),
+            gaps: self.gap_classes.map(|classes| {
+                let mut window = GapCounts::default();
+                for gap_ms in self.buffer.iter().filter_map(|b| b.gap_ms) {
+                    window.add(classes.classify(gap_ms));
+                }
+                GapMetrics {
+                    slow_ms: classes.slow_ms,
+                    stall_ms: classes.stall_ms,
+                    window,
+                    total: self.gap_totals,
+                }
+            }),
+            dropped_blocks: self.dropped,
+            gas_check: self.gas_mismatches.map(|total_mismatches| GasCheck {
+                window_mismatches: self
+                    .buffer
+                    .iter()
+                    .filter(|b| b.sampled && b.receipt_gas_used() != Some(b.gas_used()))
+                    .count(),
+                total_mismatches,
+            }),
+            data_quality: self.quality.as_ref().map(|quality| {
+                quality.metrics(self.buffer.iter().filter(|b| b.inconsistent).count())
+            }),
+            rpc: None,
+            indexing: None,
+            sampling: None,
+            self_load: None,
+            stages: None,
+            reference_clock: None,
+            read_probe: None,
+            state_probe: None,
+            first_delivery: None,
+            mempool: None,
+            address: None,
+            token: None,
+            node: None,
+            peers: None,
+            upgrade: None,
+            fees: None,
+            derived: None,
+            sinks: None,
+        }
+    }
+
+    /// Get the interval between the two most recently recorded blocks.
+    #[inline]
+    pub fn last_interval(&self) -> Option<Duration> {
+        match self.buffer.as_slice() {
+            [.., previous, last] => Some(last.timestamp - previous.timestamp),
+            _ => None,
+        }
+    }
+
+    /// Take a snapshot of the current window, or `None` until the recorded blocks span some time.
+    #[inline]
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        self.rate_window()?;
+        let last = self.buffer.last()?;
+        Some(Snapshot {
+            run_id: String::new(),
+            node_version: None,
+            timestamp: Local::now().to_rfc3339(),
+            reference_timestamp: None,
+            block_number: last.block.header.number,
+            block_hash: last.block.header.hash,
+            parent_hash: self.hashes.then_some(last.block.header.parent_hash),
+            block_transactions: last.transactions(),
+            block_gas_used: last.gas_used(),
+            block_interval_ms: self
+                .last_interval()
+                .map(|interval| interval.as_secs_f64() * 1000.0),
+            metrics: self.metrics(),
+        })
+    }
+}
+
+/// A snapshot of the measurement taken after a block was recorded, handed to every sink.
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    /// The run the snapshot belongs to, filled in by the caller.
+    pub run_id: String,
+    /// The client version of the node, filled in by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_version: Option<String>,
+    /// The local wall-clock time the snapshot was taken (RFC 3339).
+    pub timestamp: String,
+    /// The same time on the reference clock, filled in by the caller which tracks it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_timestamp: Option<String>,
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// The hash of the block's parent, recorded with `--show-hashes`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_hash: Option<B256>,
+    /// The number of transactions in the block, unknown if only its header was fetched.
+    pub block_transactions: Option<usize>,
+    pub block_gas_used: u64,
+    /// The interval between the block and its predecessor in milliseconds.
+    pub block_interval_ms: Option<f64>,
+    #[serde(flatten)]
+    pub metrics: Metrics,
+}
+
+/// The metrics derived from the current measurement window.
+#[derive(Debug, Clone, Serialize)]
+pub struct Metrics {
+    /// Unknown if only the headers were fetched.
+    pub transactions_per_second: Option<f64>,
+    pub gas_per_second: f64,
+    pub mini_block_interval_ms: f64,
+    /// The distribution of the mini-block intervals, unknown until two blocks were recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mini_block_interval_percentiles: Option<IntervalPercentiles>,
+    /// The newest block, already part of the snapshot as its block number.
+    #[serde(skip)]
+    pub head_block: u64,
+    /// The hash of the newest block if shown, already part of the snapshot.
+    #[serde(skip)]
+    pub head_hash: Option<B256>,
+    /// The time since the newest block was received in milliseconds, when the snapshot was
+    /// taken.
+    pub head_age_ms: f64,
+    /// When the newest block was received, to tell its age later on.
+    #[serde(skip)]
+    pub head_received_at: Option<SystemTime>,
+    pub window_blocks: usize,
+    /// Average time from receiving a header until its block data was fetched in milliseconds.
+    pub fetch_latency_ms: Option<f64>,
+    /// Average time from receiving a header until its block was fetched and decoded in
+    /// milliseconds.
+    pub processing_lag_ms: Option<f64>,
+    /// Blocks in the window without any transaction, i.e. produced ahead of demand.
+    pub empty_blocks: usize,
+    pub empty_block_percentage: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_split: Option<GasSplit>,
+    /// Logs emitted per second, known only when receipts were fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events_per_second: Option<f64>,
+    /// Gas burned by reverted transactions, known only when receipts were fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_gas: Option<FailedGas>,
+    /// Fees paid by the transactions of the window, known only when receipts were fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction_fees: Option<TransactionFees>,
+    /// Blocks whose transaction list was shorter than their transaction count, known only when
+    /// the transactions were counted separately.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated_blocks: Option<usize>,
+    /// Deposits and withdrawals, known only when full transactions and receipts were fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bridge: Option<BridgeMetrics>,
+    /// Same-sender nonce chains and back-to-back transactions, known only when full transactions
+    /// were fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ordering: Option<OrderingMetrics>,
+    /// The share of every transaction category in the throughput, known only when full
+    /// transactions and receipts were fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workload: Option<BTreeMap<Category, CategoryShare>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clock: Option<ClockMetrics>,
+    /// Cadence of the watched event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<EventMetrics>,
+    /// Totals of the live blocks since the start or the last reset, beyond the window.
+    pub totals: RunTotals,
+    /// The best throughput of the live blocks sustained over each tracked interval.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sustained: Option<Vec<SustainedPeak>>,
+    /// The gaps between the blocks by class.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gaps: Option<GapMetrics>,
+    /// Trends of the throughput over the window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ramp: Option<RampMetrics>,
+    /// The blocks and throughput of every block producer in the window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub producers: Option<BTreeMap<Address, ProducerShare>>,
+    /// The most frequent event signatures of the window's logs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topics: Option<Vec<TopicRank>>,
+    /// The blocks delivered again, too late or out of order since the start, which were dropped.
+    pub dropped_blocks: DroppedBlocks,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gas_check: Option<GasCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_quality: Option<DataQuality>,
+    /// Counters of the RPC client, filled in by the caller which owns it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc: Option<RpcStats>,
+    /// How often subscribed blocks were not yet served by the node, filled in by the caller which
+    /// fetches them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub indexing: Option<IndexingStats>,
+    /// How the detailed analyses sample the blocks, filled in by the caller which fetches them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingMetrics>,
+    /// How the fetch latency follows telescope's own requests, filled in by the caller which
+    /// watches them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub self_load: Option<SelfLoadMetrics>,
+    /// The latencies of the stages of the recent blocks, filled in by the caller which times
+    /// them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stages: Option<StageMetrics>,
+    /// The offset of the local clock against the reference clock, filled in by the caller which
+    /// tracks it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_clock: Option<ReferenceClockMetrics>,
+    /// Read probe latencies, filled in by the caller which owns the probe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_probe: Option<ReadProbeMetrics>,
+    /// How often every endpoint delivered the blocks first, filled in by the caller which merges
+    /// their subscriptions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_delivery: Option<Vec<ProviderDelivery>>,
+    /// State access latencies, filled in by the caller which owns the probe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_probe: Option<StateProbeMetrics>,
+    /// Inclusion latency of the mempool transactions, filled in by the caller which owns the
+    /// mempool subscription.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mempool: Option<MempoolMetrics>,
+    /// Activity of the watched address, filled in by the caller which owns the watch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<AddressMetrics>,
+    /// Activity of the watched token, filled in by the caller which owns the watch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<TokenMetrics>,
+    /// The node's own gas rate and block production time, filled in by the caller which scrapes
+    /// them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<NodeMetrics>,
+    /// The node's P2P peers, filled in by the caller which polls them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peers: Option<PeerMetrics>,
+    /// The observed chain configuration, filled in by the caller which watches for upgrades.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgrade: Option<UpgradeMetrics>,
+    /// The fee history reported by the node, filled in by the caller which polls it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fees: Option<FeeMetrics>,
+    /// User-defined metrics derived from the others, filled in by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub derived: Option<Map<String, Value>>,
+    /// How the snapshots were delivered to every sink, filled in by the caller which owns them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sinks: Option<Vec<SinkDelivery>>,
+}
+
+/// Shorten a line to at most `width` characters, never splitting one like the `…` of the hashes.
+pub fn truncate_chars(line: &mut String, width: usize) {
+    if let Some((end, _)) = line.char_indices().nth(width) {
+        line.truncate(end);
+    }
+}
+
+impl Metrics {
+    /// Format the metrics as a single line.
+    #[inline]
+    pub fn summary(&self) -> String {
+        self.summary_within(usize::MAX)
+    }
+
+    /// Format the metrics as a single line of at most `width` characters, dropping the
+    /// lowest-priority segments first.
+    pub fn summary_within(&self, width: usize) -> String {
+        let mut segments = self.summary_segments();
+        while segments.len() > 1
+            && segments.iter().map(|s| s.chars().count()).sum::<usize>() > width
+        {
+            segments.pop();
+        }
+        let mut line = segments.concat();
+        truncate_chars(&mut line, width);
+        line
+    }
+
+    /// Format the change of the metrics since `previous` as a single line of at most `width`
+    /// characters, e.g. `Block: 1200 vs 1100, TPS: -12.0 (-4.1%)`.
+    pub fn diff_within(&self, previous: &Metrics, width: usize) -> String {
+        let mut segments = vec![format!(
+            "Block: {} vs {}",
+            self.head_block, previous.head_block
+        )];
+        let mut push = |name: &str, current: Option<f64>, previous: Option<f64>, unit: &str| {
+            if let (Some(current), Some(previous)) = (current, previous) {
+                let change = match previous {
+                    0.0 => "n/a".to_string(),
+                    previous => format!("{:+.1}%", (current - previous) / previous.abs() * 100.0),
+                };
+                let (delta, prefix) = si_prefix(current - previous);
+                let unit = match unit {
+                    "" => prefix.to_string(),
+                    unit => format!(" {prefix}{unit}"),
+                };
+                segments.push(format!(", {name}: {delta:+.1}{unit} ({change})"));
+            }
+        };
+        push(
+            "Mini-block interval",
+            Some(self.mini_block_interval_ms),
+            Some(previous.mini_block_interval_ms),
+            "ms",
+        );
+        push(
+            "TPS",
+            self.transactions_per_second,
+            previous.transactions_per_second,
+            "",
+        );
+        push(
+            "Gas",
+            Some(self.gas_per_second),
+            Some(previous.gas_per_second),
+            "gas/s",
+        );
+        push(
+            "Fetch latency",
+            self.fetch_latency_ms,
+            previous.fetch_latency_ms,
+            "ms",
+        );
+        push(
+            "Processing lag",
+            self.processing_lag_ms,
+            previous.processing_lag_ms,
+            "ms",
+        );
+        // Already a percentage, so its change is in percentage points.
+        segments.push(format!(
+            ", Empty: {:+.1} pp",
+            self.empty_block_percentage - previous.empty_block_percentage
+        ));
+        while segments.len() > 1
+            && segments.iter().map(|s| s.chars().count()).sum::<usize>() > width
+        {
+            segments.pop();
+        }
+        let mut line = segments.concat();
+        truncate_chars(&mut line, width);
+        line
+    }
+
+    /// The segments of the summary line, from the highest to the lowest priority.
+    fn summary_segments(&self) -> Vec<String> {
+        // The leading values are scaled to fixed-width columns, so the refreshed line does not
+        // jump around as they cross magnitudes.
+        let mut segments = vec![
+            match self.head_hash {
+                Some(hash) => {
+                    let hash = hash.to_string();
+                    format!(
+                        "Block: {} {}…{} ({:>4.0} ms ago)",
+                        self.head_block,
+                        &hash[..10],
+                        &hash[hash.len() - 8..],
+                        self.head_age_ms
+                    )
+                }
+                None => format!(
+                    "Block: {} ({:>4.0} ms ago)",
+                    self.head_block, self.head_age_ms
+                ),
+            },
+            format!(
+                ", Mini-block interval: {:>6.1} ms",
+                self.mini_block_interval_ms
+            ),
+        ];
+        if let Some(tps) = self.transactions_per_second {
+            let (tps, prefix) = si_prefix(tps);
+            segments.push(format!(", {prefix:>1}TPS: {tps:>6.1}"));
+        }
+        segments.push(format!(", Gas: {}", gas_rate(self.gas_per_second)));
+        if let Some(split) = &self.gas_split {
+            segments.push(format!(
+                " (exec: {}, calldata: {}, blob: {})",
+                gas_rate(split.execution_gas_per_second),
+                gas_rate(split.calldata_gas_per_second),
+                gas_rate(split.blob_gas_per_second),
+            ));
+        }
+        segments.push(format!(", Empty: {:>5.1}%", self.empty_block_percentage));
+        if let Some(percentiles) = &self.mini_block_interval_percentiles {
+            segments.push(format!(
+                ", Mini-block p50/p99: {:.1}/{:.1} ms",
+                percentiles.p50_ms, percentiles.p99_ms
+            ));
+        }
+        if let Some(gaps) = &self.gaps {
+            segments.push(format!(
+                ", Slow/stalled gaps: {}/{}",
+                gaps.window.slow, gaps.window.stall
+            ));
+        }
+        if let Some(events) = self.events_per_second {
+            segments.push(format!(", Events: {events:.1}/s"));
+        }
+        if let Some(failed) = &self.failed_gas {
+            let (gas, prefix) = si_prefix(failed.gas_used as f64);
+            segments.push(format!(
+                ", Reverted: {gas:.2} {prefix}gas ({:.1}%)",
+                failed.percentage
+            ));
+        }
+        if let Some(average) = self
+            .transaction_fees
+            .as_ref()
+            .and_then(|fees| fees.average_wei)
+        {
+            let mut segment = format!(", Fee: {:.4} gwei/tx", average / 1e9);
+            if let Some(usd) = self.transaction_fees.as_ref().and_then(|f| f.average_usd) {
+                segment.push_str(&format!(" (${usd:.6})"));
+            }
+            segments.push(segment);
+        }
+        if let Some(truncated) = self.truncated_blocks.filter(|&truncated| truncated > 0) {
+            segments.push(format!(", Truncated: {truncated} blocks"));
+        }
+        if let Some(bridge) = &self.bridge {
+            segments.push(format!(
+                ", Bridge: {:.2} dep/s, {:.2} wd/s",
+                bridge.deposits_per_second, bridge.withdrawals_per_second
+            ));
+        }
+        if let Some(ordering) = &self.ordering {
+            segments.push(format!(
+                ", Nonce chains: {} ({:.1}% of txs)",
+                ordering.nonce_chains, ordering.chained_percentage
+            ));
+        }
+        if let Some(workload) = self.workload.as_ref().filter(|w| !w.is_empty()) {
+            let mut shares = workload.iter().collect::<Vec<_>>();
+            shares.sort_by(|a, b| {
+                b.1.transaction_percentage
+                    .total_cmp(&a.1.transaction_percentage)
+            });
+            let mix = shares
+                .iter()
+                .take(3)
+                .map(|(category, share)| format!("{category} {:.0}%", share.transaction_percentage))
+                .collect::<Vec<_>>();
+            segments.push(format!(", Mix: {}", mix.join(", ")));
+        }
+        if let Some((latency, skew)) = self
+            .clock
+            .as_ref()
+            .and_then(|clock| Some((clock.propagation_latency_ms?, clock.clock_skew_ms?)))
+        {
+            segments.push(format!(", Latency: {latency:.0} ms (skew: {skew:+.0} ms)"));
+        }
+        if let Some(interval) = self.event.as_ref().and_then(|event| event.interval_ms) {
+            segments.push(format!(", Event interval: {:.1} s", interval / 1000.0));
+        }
+        if let Some(ramp) = &self.ramp {
+            let mut slopes = Vec::new();
+            if let Some(tps) = &ramp.transactions_per_second {
+                slopes.push(format!("{:+.0} TPS/min", tps.per_minute));
+            }
+            if let Some(gas) = &ramp.gas_per_second {
+                slopes.push(format!("{:+.2} Mgas/s/min", gas.per_minute / 1_000_000.0));
+            }
+            if !slopes.is_empty() {
+                segments.push(format!(", Ramp: {}", slopes.join(", ")));
+            }
+        }
+        if let Some(producers) = &self.producers {
+            let top =
+                producers
+                    .iter()
+                    .max_by_key(|(_, share)| share.blocks)
+                    .map(|(producer, share)| {
+                        let producer = producer.to_string();
+                        format!(
+                            " (top {}…{}: {:.0}%)",
+                            &producer[..6],
+                            &producer[producer.len() - 4..],
+                            share.share_percentage
+                        )
+                    });
+            segments.push(format!(
+                ", Producers: {}{}",
+                producers.len(),
+                top.unwrap_or_default()
+            ));
+        }
+        if let Some(top) = self.topics.as_ref().and_then(|topics| topics.first()) {
+            let topic = match &top.name {
+                Some(name) => name.clone(),
+                None => {
+                    let topic0 = top.topic0.to_string();
+                    format!("{}…{}", &topic0[..6], &topic0[topic0.len() - 4..])
+                }
+            };
+            segments.push(format!(
+                ", Top topic: {topic} ({:.0}%)",
+                top.share_percentage
+            ));
+        }
+        if let Some(stages) = &self.stages {
+            let spent = match stages.bottleneck {
+                Some("network") => stages.propagation.as_ref().map(|p| p.mean_ms),
+                Some("node") => stages.fetch.as_ref().map(|f| f.mean_ms),
+                Some(_) => Some(
+                    stages.queue.as_ref().map_or(0.0, |q| q.mean_ms)
+                        + stages.output.as_ref().map_or(0.0, |o| o.mean_ms),
+                ),
+                None => None,
+            };
+            if let (Some(bottleneck), Some(spent)) = (stages.bottleneck, spent) {
+                segments.push(format!(", Bottleneck: {bottleneck} ({spent:.1} ms)"));
+            }
+        }
+        if let Some(indexing) = self
+            .indexing
+            .as_ref()
+            .filter(|indexing| indexing.unavailable_blocks > 0)
+        {
+            segments.push(format!(
+                ", Unindexed: {} blocks (max {:.0} ms)",
+                indexing.unavailable_blocks, indexing.max_delay_ms
+            ));
+        }
+        if let Some(sampling) = self
+            .sampling
+            .as_ref()
+            .filter(|sampling| sampling.ratio < 1.0)
+        {
+            segments.push(format!(", Sampled: {:.0}%", 100.0 * sampling.ratio));
+        }
+        if let Some(rpc) = &self.rpc {
+            segments.push(match &rpc.bandwidth {
+                Some(bandwidth) => format!(
+                    ", RPC: {} req/min, {:.3} MB/s",
+                    rpc.requests_per_minute, bandwidth.megabytes_per_second
+                ),
+                None => format!(", RPC: {} req/min", rpc.requests_per_minute),
+            });
+        }
+        if let Some(self_load) = &self.self_load {
+            if let Some(r) = self_load.latency_correlation {
+                let overloaded = if self_load.overloaded {
+                    " (overloaded)"
+                } else {
+                    ""
+                };
+                segments.push(format!(", Self-load r: {r:+.2}{overloaded}"));
+            }
+        }
+        if let Some(age) = self.mempool.as_ref().and_then(|m| m.pending_age.as_ref()) {
+            segments.push(format!(
+                ", Pending age: p50 {:.0} ms, p95 {:.0} ms",
+                age.p50_ms, age.p95_ms
+            ));
+        }
+        let dropped = &self.dropped_blocks;
+        if dropped.total() > 0 {
+            segments.push(format!(
+                ", Dropped: {} duplicate, {} stale, {} out of order",
+                dropped.duplicate, dropped.stale, dropped.out_of_order
+            ));
+        }
+        for delivery in self.sinks.iter().flatten() {
+            if delivery.dropped > 0 || delivery.delayed > 0 {
+                segments.push(format!(
+                    ", {}: {} dropped, {} delayed",
+                    delivery.sink, delivery.dropped, delivery.delayed
+                ));
+            }
+        }
+        if let Some(check) = &self.gas_check {
+            segments.push(format!(", Gas mismatches: {}", check.total_mismatches));
+        }
+        if let Some(quality) = &self.data_quality {
+            segments.push(format!(
+                ", Inconsistent blocks: {}",
+                quality.total_inconsistent_blocks
+            ));
+        }
+        if let Some(probe) = &self.read_probe {
+            if let Some(latency) = probe.call_latency_ms {
+                segments.push(match probe.tps_correlation {
+                    Some(r) => format!(", Read: {latency:.1} ms (r_tps: {r:+.2})"),
+                    None => format!(", Read: {latency:.1} ms"),
+                });
+            }
+        }
+        if let Some(providers) = &self.first_delivery {
+            let rates = providers
+                .iter()
+                .map(|p| format!("{} {:.0}%", p.provider, p.win_rate))
+                .collect::<Vec<_>>();
+            segments.push(format!(", First: {}", rates.join(", ")));
+        }
+        if let Some(probe) = &self.state_probe {
+            if let (Some(proof), Some(batch)) =
+                (probe.proof_latency_ms, probe.storage_batch_latency_ms)
+            {
+                segments.push(format!(
+                    ", State: proof {proof:.1} ms, {} slots {batch:.1} ms",
+                    probe.slots
+                ));
+            }
+        }
+        if let Some(token) = &self.token {
+            let rate = token
+                .transfers_per_second
+                .map(|rate| format!("{rate:.1}/s, "))
+                .unwrap_or_default();
+            segments.push(format!(
+                ", Token: {rate}{} transfers, {} holders, {:.2} Mgas",
+                token.transfers,
+                token.unique_holders,
+                token.gas_used as f64 / 1_000_000.0
+            ));
+        }
+        if let Some(address) = &self.address {
+            segments.push(format!(
+                ", Watched: {} txs, {:.2} Mgas",
+                address.transactions,
+                address.gas_used as f64 / 1_000_000.0
+            ));
+            if let (Some(nonce), Some(balance)) = (address.nonce_delta, address.balance_change_eth)
+            {
+                segments.push(format!(" (nonce {nonce:+}, balance {balance:+.4} ETH)"));
+            }
+            if let Some(runway) = address.runway_minutes {
+                segments.push(format!(", Runway: {runway:.0} min"));
+            }
+        }
+        if let Some(node) = &self.node {
+            if let Some(gas) = node.gas_per_second {
+                segments.push(match node.observed_gas_ratio {
+                    Some(ratio) => {
+                        format!(", Node: {} (observed x{ratio:.2})", gas_rate(gas))
+                    }
+                    None => format!(", Node: {}", gas_rate(gas)),
+                });
+            }
+            if let Some(block_time) = node.block_time_ms {
+                segments.push(format!(", Node block time: {block_time:.1} ms"));
+            }
+        }
+        if let Some(count) = self.peers.as_ref().and_then(|peers| peers.peer_count) {
+            segments.push(format!(", Peers: {count}"));
+        }
+        if let Some(fees) = &self.fees {
+            segments.push(format!(
+                ", Base fee: {:.3} gwei ({:+.1}%)",
+                fees.base_fee_gwei, fees.base_fee_change_percentage
+            ));
+            if let Some((percentile, tip)) = fees.reward_gwei.iter().nth(fees.reward_gwei.len() / 2)
+            {
+                segments.push(format!(", Tip {percentile}: {tip:.3} gwei"));
+            }
+        }
+        for (name, value) in self.derived.iter().flatten() {
+            if let Some(value) = value.as_f64() {
+                segments.push(format!(", {name}: {value:.2}"));
+            }
+        }
+        segments.push(match self.totals.transactions {
+            Some(transactions) => format!(
+                ", Total: {transactions} txs in {} blocks",
+                self.totals.blocks
+            ),
+            None => format!(", Total: {} blocks", self.totals.blocks),
+        });
+        segments
+    }
+
+    /// Format the run-wide totals as a single line.
+    pub fn totals_line(&self) -> String {
+        let totals = &self.totals;
+        let run = totals.run_seconds as u64;
+        let mut line = format!(
+            "Run {:02}:{:02}:{:02}: {} blocks",
+            run / 3600,
+            run / 60 % 60,
+            run % 60,
+            totals.blocks
+        );
+        if let (Some(transactions), Some(tps)) =
+            (totals.transactions, totals.transactions_per_second)
+        {
+            line.push_str(&format!(", {transactions} txs (avg TPS: {tps:.1})"));
+        }
+        let (gas, prefix) = si_prefix(totals.gas_used as f64);
+        line.push_str(&format!(
+            ", {gas:.2} {prefix}gas (avg {})",
+            gas_rate(totals.gas_per_second).trim_start()
+        ));
+        if let Some(gaps) = &self.gaps {
+            line.push_str(&format!(
+                ", {} slow and {} stalled gaps",
+                gaps.total.slow, gaps.total.stall
+            ));
+        }
+        for peak in self.sustained.iter().flatten() {
+            let Some(gas_per_second) = peak.gas_per_second else {
+                continue;
+            };
+            line.push_str(&format!(
+                ", best {:.0} s: {}",
+                peak.interval_s,
+                gas_rate(gas_per_second).trim_start()
+            ));
+            if let Some(tps) = peak.transactions_per_second {
+                line.push_str(&format!(" ({tps:.1} TPS)"));
+            }
+        }
+        line
+    }
+}
+
+/// The blocks `record` dropped because they were not newer than the last recorded one.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DroppedBlocks {
+    /// Blocks already in the window, delivered again.
+    pub duplicate: u64,
+    /// Blocks older than the window, or differing from the recorded block of their number.
+    pub stale: u64,
+    /// Blocks missing from the window which arrived after a later block.
+    pub out_of_order: u64,
+}
+
+impl DroppedBlocks {
+    pub fn total(&self) -> u64 {
+        self.duplicate + self.stale + self.out_of_order
+    }
+}
+
+/// The counters of the live blocks since the start or the last reset.
+struct Totals {
+    started: Instant,
+    blocks: u64,
+    /// Unknown once a block without its transactions was recorded.
+    transactions: Option<u64>,
+    gas_used: u64,
+}
+
+/// The run-wide totals and averages, independent of the sliding window.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunTotals {
+    pub blocks: u64,
+    pub transactions: Option<u64>,
+    pub gas_used: u64,
+    pub run_seconds: f64,
+    pub transactions_per_second: Option<f64>,
+    pub gas_per_second: f64,
+}
+
+impl Totals {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            blocks: 0,
+            transactions: Some(0),
+            gas_used: 0,
+        }
+    }
+
+    #[inline]
+    fn record(&mut self, datapoint: &Datapoint) {
+        self.blocks += 1;
+        self.transactions = self
+            .transactions
+            .zip(datapoint.transactions())
+            .map(|(total, transactions)| total + transactions as u64);
+        self.gas_used += datapoint.gas_used();
+    }
+
+    fn metrics(&self) -> RunTotals {
+        let run_seconds = self.started.elapsed().as_secs_f64();
+        RunTotals {
+            blocks: self.blocks,
+            transactions: self.transactions,
+            gas_used: self.gas_used,
+            run_seconds,
+            transactions_per_second: self
+                .transactions
+                .map(|transactions| transactions as f64 / run_seconds),
+            gas_per_second: self.gas_used as f64 / run_seconds,
+        }
+    }
+}
+
+/// Scale a value by an SI prefix (k, M, G), so it has at most three integer digits.
+#[inline]
+fn si_prefix(value: f64) -> (f64, &'static str) {
+    match value.abs() {
+        v if v >= 1e9 => (value / 1e9, "G"),
+        v if v >= 1e6 => (value / 1e6, "M"),
+        v if v >= 1e3 => (value / 1e3, "k"),
+        _ => (value, ""),
+    }
+}
+
+/// Format a gas rate with a scaled unit at a fixed width, e.g. `  1.23 Mgas/s`.
+#[inline]
+pub fn gas_rate(gas_per_second: f64) -> String {
+    let (gas, prefix) = si_prefix(gas_per_second);
+    format!("{gas:>6.2} {prefix:>1}gas/s")
+}
+
+/// The result of verifying the header gas against the receipts.
+#[derive(Debug, Clone, Serialize)]
+pub struct GasCheck {
+    /// Blocks in the window whose header gas disagrees with their receipts.
+    pub window_mismatches: usize,
+    /// Mismatching blocks since the start.
+    pub total_mismatches: u64,
+}
+
+/// The gas burned by reverted transactions in the window, which uses up throughput without
+/// changing any state.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedGas {
+    /// Reverted transactions in the window.
+    pub transactions: usize,
+    /// Gas used by the reverted transactions.
+    pub gas_used: u64,
+    /// Share of the gas used by all the transactions of the window.
+    pub percentage: f64,
+}
+
+/// The fees paid by the transactions of the window.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionFees {
+    pub transactions: usize,
+    pub total_wei: f64,
+    /// Unknown for a window without transactions.
+    pub average_wei: Option<f64>,
+    /// In USD at the price of the feed, filled in by the caller which polls it.
+    pub total_usd: Option<f64>,
+    pub average_usd: Option<f64>,
+}
+
+impl TransactionFees {
+    /// Convert the fees to USD at the price of the native token.
+    pub fn set_price(&mut self, usd: f64) {
+        self.total_usd = Some(self.total_wei / 1e18 * usd);
+        self.average_usd = self.average_wei.map(|average| average / 1e18 * usd);
+    }
+}
+
+/// Percentiles of the intervals between the mini-blocks, within 1% of the exact values.
+#[derive(Debug, Clone, Serialize)]
+pub struct IntervalPercentiles {
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// The gas rate split by what the gas is spent on.
+#[derive(Debug, Clone, Serialize)]
+pub struct GasSplit {
+    /// Gas spent on execution, i.e. the block gas minus the intrinsic calldata cost.
+    pub execution_gas_per_second: f64,
+    /// Intrinsic calldata gas (EIP-2028 pricing) of the included transactions.
+    pub calldata_gas_per_second: f64,
+    /// Blob gas reported by the header, which is accounted separately from the block gas.
+    pub blob_gas_per_second: f64,
+}
+
+/// The blocks of a producer in the window.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProducerShare {
+    pub blocks: usize,
+    pub share_percentage: f64,
+    /// Unknown if only the headers were fetched.
+    pub transactions_per_second: Option<f64>,
+    pub gas_per_second: f64,
+}
+
+/// Contains the data we sample from the blockchain.
+pub struct Datapoint {
+    /// The time the rates count the block at, see [`RateClock`].
+    pub timestamp: Instant,
+    /// The time the header was received.
+    pub received: Instant,
+    /// The time fetching the block data completed, unknown for backfilled blocks and blocks
+    /// taken from their header alone.
+    pub fetched: Option<Instant>,
+    /// The wall-clock time the block was received, comparable with the header timestamp.
+    pub received_at: SystemTime,
+    pub block: AnyRpcBlock,
+    /// The receipts of the block's transactions, if fetched.
+    pub receipts: Option<Vec<AnyTransactionReceipt>>,
+    /// The number of transactions counted apart from the transaction list, if enabled.
+    transaction_count: Option<usize>,
+    /// Whether the detailed analyses cover the block, rather than sampling skipping it.
+    sampled: bool,
+    /// The calldata gas of the block, known only when full transactions were fetched.
+    calldata_gas: Option<u64>,
+    /// The user deposits in the block, known only when full transactions were fetched.
+    deposits: Option<usize>,
+    /// The same-sender patterns of the block, known only when full transactions were fetched.
+    ordering: Option<BlockOrdering>,
+    /// The transaction categories of the block, known only when full transactions and receipts
+    /// were fetched.
+    workload: Option<BlockWorkload>,
+    /// The time from receiving the header until the block was recorded, unknown for backfilled
+    /// blocks.
+    processing_lag: Option<Duration>,
+    /// The interval since the previous block per mini-block, unknown for the first block.
+    mini_block_interval_ms: Option<f64>,
+    /// The interval since the previous block, unknown for the first block.
+    gap_ms: Option<f64>,
+    /// Whether the block failed a consistency check.
+    inconsistent: bool,
+}
+
+/// A buffered block as written to the state file, with its receive time on the wall clock since
+/// instants do not survive a restart.
+#[derive(Serialize, Deserialize)]
+pub struct SavedBlock {
+    pub block: AnyRpcBlock,
+    receipts: Option<Vec<AnyTransactionReceipt>>,
+    transaction_count: Option<usize>,
+    /// Seconds since the Unix epoch.
+    received_at: f64,
+    /// Seconds from receiving the header until the block was fetched, if it was.
+    fetch_latency: Option<f64>,
+    processing_lag: Option<f64>,
+    /// Whether sampling skipped the data of the detailed analyses.
+    #[serde(default)]
+    skipped: bool,
+}
+
+impl SavedBlock {
+    fn new(datapoint: &Datapoint) -> Self {
+        let received_at = datapoint
+            .received_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        Self {
+            block: datapoint.block.clone(),
+            receipts: datapoint.receipts.clone(),
+            transaction_count: datapoint.transaction_count,
+            received_at,
+            fetch_latency: datapoint
+                .fetched
+                .map(|fetched| (fetched - datapoint.received).as_secs_f64()),
+            processing_lag: datapoint.processing_lag.map(|lag| lag.as_secs_f64()),
+            skipped: !datapoint.sampled,
+        }
+    }
+}
+
+/// A fetched block with the analysis of its transactions, which is CPU-bound for full blocks
+/// and may run off the thread reading the subscription.
+pub struct Decoded {
+    fetched: Fetched,
+    calldata_gas: Option<u64>,
+    deposits: Option<usize>,
+    ordering: Option<BlockOrdering>,
+    workload: Option<BlockWorkload>,
+}
+
+impl Decoded {
+    pub fn new(fetched: Fetched) -> Self {
+        let block = &fetched.block;
+        let calldata_gas = block
+            .transactions
+            .as_transactions()
+            .map(|txs| txs.iter().map(|tx| calldata_gas(tx.input())).sum::<u64>());
+        let deposits = bridge::deposits(block);
+        let ordering = BlockOrdering::new(block);
+        let workload = BlockWorkload::new(block, fetched.receipts.as_deref());
+        Self {
+            fetched,
+            calldata_gas,
+            deposits,
+            ordering,
+            workload,
+        }
+    }
+}
+
+impl Datapoint {
+    fn new(
+        decoded: Decoded,
+        received: Instant,
+        fetched: Option<Instant>,
+        received_at: SystemTime,
+        rate_clock: RateClock,
+    ) -> Self {
+        let Decoded {
+            fetched:
+                Fetched {
+                    block,
+                    receipts,
+                    transaction_count,
+                    sampled,
+                },
+            calldata_gas,
+            deposits,
+            ordering,
+            workload,
+        } = decoded;
+        Self {
+            timestamp: match (rate_clock, fetched) {
+                (RateClock::Fetch, Some(fetched)) => fetched,
+                _ => received,
+            },
+            received,
+            fetched,
+            received_at,
+            block,
+            receipts,
+            transaction_count,
+            sampled,
+            calldata_gas,
+            deposits,
+            ordering,
+            workload,
+            processing_lag: None,
+            mini_block_interval_ms: None,
+            gap_ms: None,
+            inconsistent: false,
+        }
+    }
+
+    /// Get the gas used by the block.
+    #[inline]
+    pub fn gas_used(&self) -> u64 {
+        self.block.header.gas_used
+    }
+
+    /// Get the sum of the gas used by the block's receipts, if fetched.
+    #[inline]
+    pub fn receipt_gas_used(&self) -> Option<u64> {
+        self.receipts
+            .as_ref()
+            .map(|receipts| receipts.iter().map(|r| r.gas_used).sum())
+    }
+
+    /// Get the number of logs emitted by the block's transactions, if the receipts were fetched.
+    #[inline]
+    pub fn logs(&self) -> Option<usize> {
+        self.receipts
+            .as_ref()
+            .map(|receipts| receipts.iter().map(|r| r.inner.inner.logs().len()).sum())
+    }
+
+    /// Get the blob gas used by the block.
+    #[inline]
+    pub fn blob_gas_used(&self) -> u64 {
+        self.block.header.blob_gas_used.unwrap_or_default()
+    }
+
+    /// Get the number of transactions in the block, preferring the separate count if enabled,
+    /// unknown if only its header was fetched.
+    #[inline]
+    pub fn transactions(&self) -> Option<usize> {
+        self.transaction_count.or_else(|| {
+            (!self.block.transactions.is_uncle()).then(|| self.block.transactions.len())
+        })
+    }
+
+    /// Check whether the block's fetched transaction list is shorter than its counted
+    /// transactions.
+    #[inline]
+    pub fn is_truncated(&self) -> bool {
+        !self.block.transactions.is_uncle()
+            && self
+                .transaction_count
+                .is_some_and(|count| count > self.block.transactions.len())
+    }
+
+    /// Check whether the block has no transactions, from its transactions root if only its
+    /// header was fetched.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        match self.transactions() {
+            Some(transactions) => transactions == 0,
+            None => self.block.header.transactions_root == EMPTY_ROOT_HASH,
+        }
+    }
+
+    /// Calculate the number of mini-blocks in the block.
+    #[inline]
+    pub fn mini_blocks(&self) -> u64 {
+        let mut buf = self.block.header.extra_data.clone();
+        let fragment_count = buf.get_u8();
+        fragment_count as u64
+    }
+}
+
+/// Calculate the intrinsic gas charged for the calldata of a transaction (EIP-2028).
+#[inline]
+fn calldata_gas(input: &[u8]) -> u64 {
+    input
+        .iter()
+        .map(|&byte| if byte == 0 { 4 } else { 16 })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        network::{AnyHeader, AnyRpcHeader},
+        primitives::U256,
+    };
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::fetch::header_block;
+
+    /// Record blocks with the given gas, received the given milliseconds after the previous one.
+    fn measure(window: u64, blocks: &[(u64, u64)]) -> (Measurement, Vec<(Instant, u64)>) {
+        measure_with(Measurement::new(window), blocks)
+    }
+
+    /// Record blocks as [`measure`] does, into a configured measurement.
+    fn measure_with(
+        mut measurement: Measurement,
+        blocks: &[(u64, u64)],
+    ) -> (Measurement, Vec<(Instant, u64)>) {
+        let mut received = Instant::now();
+        let mut recorded = Vec::new();
+        for (number, &(interval_ms, gas_used)) in (1u64..).zip(blocks) {
+            received += Duration::from_millis(interval_ms);
+            let header = AnyRpcHeader {
+                hash: U256::from(number).into(),
+                inner: AnyHeader {
+                    number,
+                    gas_used,
+                    extra_data: vec![1].into(),
+                    ..Default::default()
+                },
+                total_difficulty: None,
+                size: None,
+            };
+            let fetched = Fetched {
+                block: header_block(header),
+                receipts: None,
+                transaction_count: None,
+                sampled: true,
+            };
+            measurement.record(Decoded::new(fetched), received, None, SystemTime::now());
+            recorded.push((received, gas_used));
+        }
+        (measurement, recorded)
+    }
+
+    fn blocks() -> impl Strategy<Value = Vec<(u64, u64)>> {
+        prop::collection::vec((1u64..1_000, 0u64..30_000_000), 0..64)
+    }
+
+    proptest! {
+        #[test]
+        fn redelivered_blocks_are_no_reorg(n in 2u64..16, interval_ms in 1u64..1_000) {
+            let (measurement, _) = measure(32, &vec![(interval_ms, 21_000); n as usize]);
+            let hash = |number: u64| B256::from(U256::from(number));
+            let other = B256::repeat_byte(0xee);
+            for number in 1..=n {
+                prop_assert_eq!(
+                    measurement.reorg_depth(number, hash(number), hash(number - 1)),
+                    None
+                );
+            }
+            prop_assert_eq!(measurement.reorg_depth(n + 1, hash(n + 1), hash(n)), None);
+            prop_assert_eq!(measurement.reorg_depth(n + 1, hash(n + 1), other), Some(1));
+            prop_assert_eq!(measurement.reorg_depth(n, other, hash(n - 1)), Some(1));
+            prop_assert_eq!(measurement.reorg_depth(2, other, other), Some(n));
+            prop_assert_eq!(measurement.reorg_depth(n + 5, hash(n + 5), other), None);
+        }
+
+        #[test]
+        fn window_starts_at_oldest_block(window in 2u64..32, blocks in blocks()) {
+            let (measurement, recorded) = measure(window, &blocks);
+            let kept = &recorded[recorded.len().saturating_sub(window as usize)..];
+            prop_assert_eq!(measurement.buffer.len(), kept.len());
+            match (kept.first(), kept.last()) {
+                (Some((first, _)), Some((last, _))) if kept.len() > 1 => {
+                    let (rated, span) = measurement.rate_window().unwrap();
+                    prop_assert_eq!(rated.len(), kept.len() - 1);
+                    prop_assert_eq!(span, (*last - *first).as_secs_f64());
+                }
+                _ => prop_assert!(measurement.snapshot().is_none()),
+            }
+        }
+
+        #[test]
+        fn gas_rate_counts_blocks_after_window_start(window in 2u64..32, blocks in blocks()) {
+            let (measurement, recorded) = measure(window, &blocks);
+            let kept = &recorded[recorded.len().saturating_sub(window as usize)..];
+            prop_assume!(kept.len() > 1);
+            let gas = kept[1..].iter().map(|(_, gas)| gas).sum::<u64>() as f64;
+            let span = (kept[kept.len() - 1].0 - kept[0].0).as_secs_f64();
+            let rate = measurement.gas_per_second();
+            prop_assert!((rate - gas / span).abs() <= 1e-9 * rate.max(1.0));
+        }
+
+        #[test]
+        fn steady_chain_rate_is_exact(
+            window in 2u64..32,
+            n in 2usize..64,
+            interval_ms in 1u64..1_000,
+            gas_used in 1u64..30_000_000,
+        ) {
+            let (measurement, _) = measure(window, &vec![(interval_ms, gas_used); n]);
+            let expected = gas_used as f64 * 1000.0 / interval_ms as f64;
+            let rate = measurement.gas_per_second();
+            prop_assert!((rate - expected).abs() <= 1e-9 * expected);
+            let interval = measurement.metrics().mini_block_interval_ms;
+            prop_assert!((interval - interval_ms as f64).abs() <= 1e-9 * interval_ms as f64);
+        }
+
+        #[test]
+        fn interval_percentiles_follow_window(
+            window in 2u64..32,
+            shrunk in 2u64..32,
+            blocks in blocks(),
+        ) {
+            let (mut measurement, recorded) = measure(window, &blocks);
+            measurement.set_window_size(shrunk);
+            let kept = recorded.len().saturating_sub(window.min(shrunk) as usize);
+            // The kept blocks keep the interval to their predecessor, even once it is evicted;
+            // only the first block recorded has none.
+            let mut intervals = blocks[kept.max(1).min(blocks.len())..]
+                .iter()
+                .map(|&(interval_ms, _)| interval_ms as f64)
+                .collect::<Vec<_>>();
+            intervals.sort_by(f64::total_cmp);
+            let percentiles = measurement.mini_block_interval_percentiles();
+            prop_assert_eq!(percentiles.is_some(), !intervals.is_empty());
+            if let Some(percentiles) = percentiles {
+                let exact = |p: f64| intervals[((intervals.len() - 1) as f64 * p).round() as usize];
+                let estimates = [
+                    (percentiles.p50_ms, 0.5),
+                    (percentiles.p90_ms, 0.9),
+                    (percentiles.p99_ms, 0.99),
+                ];
+                for (estimate, p) in estimates {
+                    prop_assert!((estimate - exact(p)).abs() <= INTERVAL_ACCURACY * exact(p) + 1e-9);
+                }
+            }
+        }
+
+        #[test]
+        fn gaps_are_classified_by_boundaries(
+            window in 2u64..32,
+            slow_ms in 1u64..500,
+            stall_ms in 500u64..1_000,
+            blocks in blocks(),
+        ) {
+            prop_assume!(blocks.len() > 1);
+            let classes = GapClasses { slow_ms: slow_ms as f64, stall_ms: stall_ms as f64 };
+            let measurement = Measurement::new(window).with_gap_classes(classes);
+            let (measurement, _) = measure_with(measurement, &blocks);
+            let gaps = measurement.metrics().gaps.unwrap();
+            let mut expected = GapCounts::default();
+            for &(interval_ms, _) in blocks.iter().skip(1) {
+                expected.add(classes.classify(interval_ms as f64));
+            }
+            prop_assert_eq!(gaps.total.normal, expected.normal);
+            prop_assert_eq!(gaps.total.slow, expected.slow);
+            prop_assert_eq!(gaps.total.stall, expected.stall);
+            let window_gaps = gaps.window.normal + gaps.window.slow + gaps.window.stall;
+            // Only the first block recorded has no gap.
+            let buffered = if blocks.len() > window as usize { window as usize } else { blocks.len() - 1 };
+            prop_assert_eq!(window_gaps as usize, buffered);
+        }
+
+        #[test]
+        fn summary_fits_width_around_hash_ellipsis(width in 0usize..64) {
+            let measurement = Measurement::new(4).with_hashes();
+            let (measurement, _) = measure_with(measurement, &[(100, 21_000); 4]);
+            let metrics = measurement.metrics();
+            let line = metrics.summary_within(width);
+            prop_assert!(line.chars().count() <= width);
+            prop_assert!(metrics.summary().starts_with(&line));
+            let diff = metrics.diff_within(&metrics, width);
+            prop_assert!(diff.chars().count() <= width);
+        }
+
+        #[test]
+        fn shrinking_keeps_window_at_oldest_block(
+            window in 2u64..32,
+            shrunk in 2u64..32,
+            blocks in blocks(),
+        ) {
+            let (mut measurement, recorded) = measure(window, &blocks);
+            measurement.set_window_size(shrunk);
+            let kept = &recorded[recorded.len().saturating_sub(window.min(shrunk) as usize)..];
+            prop_assert_eq!(measurement.buffer.len(), kept.len());
+            if let [(first, _), .., (last, _)] = kept {
+                let (_, span) = measurement.rate_window().unwrap();
+                prop_assert_eq!(span, (*last - *first).as_secs_f64());
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use alloy::{
+    consensus::{Transaction, Typed2718},
+    network::{AnyRpcBlock, TransactionResponse},
+    primitives::TxHash,
+    providers::Provider,
+};
+use eyre::Result;
+use futures_util::StreamExt;
+use serde::Serialize;
+
+use crate::rpc::RpcClient;
+
+/// Maximum number of pending transactions whose first-seen time is remembered.
+const SEEN_CAPACITY: usize = 100_000;
+
+/// Number of recent inclusions the latency of every group is averaged over.
+const SAMPLES: usize = 256;
+
+/// Maximum number of pending transactions whose age is sampled for every snapshot.
+const AGE_SAMPLES: usize = 4096;
+
+/// Upper bounds (in wei) of the priority fee bands, from 0.001 to 1 gwei.
+const FEE_BANDS_WEI: [u128; 4] = [1_000_000, 10_000_000, 100_000_000, 1_000_000_000];
+
+/// Measures how long transactions take from the mempool into a block, by transaction type and
+/// priority fee band.
+pub struct Mempool {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    seen: HashMap<TxHash, Instant>,
+    /// The seen transactions in arrival order, to forget the oldest ones.
+    order: VecDeque<TxHash>,
+    by_type: BTreeMap<&'static str, VecDeque<f64>>,
+    by_fee_band: BTreeMap<String, VecDeque<f64>>,
+}
+
+/// The inclusion latency of the recent transactions.
+#[derive(Debug, Clone, Serialize)]
+pub struct MempoolMetrics {
+    /// Pending transactions currently tracked.
+    pub pending: usize,
+    /// How long the pending transactions have waited so far, unknown without any pending.
+    pub pending_age: Option<PendingAge>,
+    pub by_type: BTreeMap<&'static str, InclusionLatency>,
+    pub by_fee_band: BTreeMap<String, InclusionLatency>,
+}
+
+/// The time since the pending transactions were first seen, including those that never land.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingAge {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    /// The pending transactions the percentiles were taken over.
+    pub samples: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InclusionLatency {
+    /// Average time from first seeing the transaction until its block arrived in milliseconds.
+    pub mean_ms: f64,
+    pub samples: usize,
+}
+
+impl Mempool {
+    /// Subscribe to the pending transactions of the node.
+    pub async fn spawn(rpc: Arc<RpcClient>) -> Result<Self> {
+        let mut stream = rpc
+            .provider()
+            .subscribe_pending_transactions()
+            .await?
+            .into_stream();
+        let state = Arc::new(Mutex::new(State::default()));
+        let seen_state = state.clone();
+        tokio::spawn(async move {
+            while let Some(hash) = stream.next().await {
+                rpc.account_received("newPendingTransactions", &hash);
+                let mut state = seen_state.lock().unwrap();
+                if state.seen.insert(hash, Instant::now()).is_none() {
+                    state.order.push_back(hash);
+                }
+                if state.order.len() > SEEN_CAPACITY {
+                    if let Some(oldest) = state.order.pop_front() {
+                        state.seen.remove(&oldest);
+                    }
+                }
+            }
+        });
+        Ok(Self { state })
+    }
+
+    /// Record the inclusion of the seen transactions of a block received at `received_at`.
+    ///
+    /// Only blocks with full transactions can be attributed to a type and fee band.
+    pub fn observe(&self, block: &AnyRpcBlock, received_at: Instant) {
+        let Some(txs) = block.transactions.as_transactions() else {
+            return;
+        };
+        let base_fee = block.header.base_fee_per_gas.unwrap_or_default();
+        let mut state = self.state.lock().unwrap();
+        for tx in txs {
+            let Some(seen) = state.seen.remove(&tx.tx_hash()) else {
+                continue;
+            };
+            let latency_ms = received_at.saturating_duration_since(seen).as_secs_f64() * 1000.0;
+            push(
+                state.by_type.entry(tx_type_name(tx.ty())).or_default(),
+                latency_ms,
+            );
+            push(
+                state
+                    .by_fee_band
+                    .entry(fee_band(tx.effective_tip_per_gas(base_fee)))
+                    .or_default(),
+                latency_ms,
+            );
+        }
+    }
+
+    /// Collect the inclusion latency of every group seen so far.
+    pub fn metrics(&self) -> MempoolMetrics {
+        let state = self.state.lock().unwrap();
+        let latency = |samples: &VecDeque<f64>| InclusionLatency {
+            mean_ms: samples.iter().sum::<f64>() / samples.len() as f64,
+            samples: samples.len(),
+        };
+        // The hash map's order is as good as a random sample of the pending transactions.
+        let now = Instant::now();
+        let mut ages = state
+            .seen
+            .values()
+            .take(AGE_SAMPLES)
+            .map(|seen| now.saturating_duration_since(*seen).as_secs_f64() * 1000.0)
+            .collect::<Vec<_>>();
+        ages.sort_by(f64::total_cmp);
+        let percentile =
+            |p: f64| ages[((ages.len() as f64 * p).ceil() as usize).clamp(1, ages.len()) - 1];
+        let pending_age = (!ages.is_empty()).then(|| PendingAge {
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            max_ms: ages[ages.len() - 1],
+            samples: ages.len(),
+        });
+        MempoolMetrics {
+            pending: state.seen.len(),
+            pending_age,
+            by_type: state
+                .by_type
+                .iter()
+                .map(|(name, samples)| (*name, latency(samples)))
+                .collect(),
+            by_fee_band: state
+                .by_fee_band
+                .iter()
+                .map(|(band, samples)| (band.clone(), latency(samples)))
+                .collect(),
+        }
+    }
+}
+
+#[inline]
+fn push(samples: &mut VecDeque<f64>, latency_ms: f64) {
+    samples.push_back(latency_ms);
+    if samples.len() > SAMPLES {
+        samples.pop_front();
+    }
+}
+
+/// Name the EIP-2718 transaction type.
+#[inline]
+fn tx_type_name(ty: u8) -> &'static str {
+    match ty {
+        0 => "legacy",
+        1 => "eip2930",
+        2 => "eip1559",
+        3 => "eip4844",
+        4 => "eip7702",
+        0x7e => "deposit",
+        _ => "other",
+    }
+}
+
+/// Name the band of the priority fee (wei), e.g. `1e6_1e7` for 0.001 to 0.01 gwei.
+#[inline]
+fn fee_band(tip: Option<u128>) -> String {
+    let Some(tip) = tip else {
+        return "unknown".to_string();
+    };
+    let bound = |i: usize| format!("{:e}", FEE_BANDS_WEI[i] as f64);
+    match FEE_BANDS_WEI.iter().position(|bound| tip < *bound) {
+        Some(0) => format!("lt_{}", bound(0)),
+        Some(i) => format!("{}_{}", bound(i - 1), bound(i)),
+        None => format!("ge_{}", bound(FEE_BANDS_WEI.len() - 1)),
+    }
+}
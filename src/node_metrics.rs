@@ -0,0 +1,142 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use eyre::Result;
+use serde::Serialize;
+
+use crate::journal::{Journal, Severity};
+
+/// Scrapes the node's own Prometheus endpoint, to compare what the node reports with what
+/// telescope observes as a subscriber.
+pub struct NodeMetricsScraper {
+    state: Arc<Mutex<NodeMetrics>>,
+}
+
+/// Which of the node's metrics are compared.
+#[derive(Debug, Clone)]
+pub struct NodeMetricNames {
+    /// The gas rate: a gauge in gas per second, or a counter of gas (`_total`) whose rate
+    /// between the scrapes is taken.
+    pub gas: String,
+    /// The block production time in seconds: a gauge, or a histogram or summary whose mean
+    /// between the scrapes is taken.
+    pub block_time: Option<String>,
+}
+
+/// The values reported by the node, next to the observed ones.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NodeMetrics {
+    pub gas_per_second: Option<f64>,
+    pub block_time_ms: Option<f64>,
+    /// Observed gas per second relative to the node-reported one.
+    pub observed_gas_ratio: Option<f64>,
+}
+
+impl NodeMetricsScraper {
+    /// Scrape `url` every `interval`.
+    pub fn spawn(
+        url: String,
+        names: NodeMetricNames,
+        interval: Duration,
+        journal: Journal,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(NodeMetrics::default()));
+        let scraped = state.clone();
+        tokio::spawn(async move {
+            let http = reqwest::Client::new();
+            let mut ticker = tokio::time::interval(interval);
+            let mut previous: Option<(Instant, HashMap<String, f64>)> = None;
+            // Log only when the scraping starts failing or recovers, not on every scrape.
+            let mut failing = false;
+            loop {
+                ticker.tick().await;
+                let samples = match scrape(&http, &url).await {
+                    Ok(samples) => samples,
+                    Err(err) => {
+                        if !std::mem::replace(&mut failing, true) {
+                            journal.log(
+                                Severity::Warn,
+                                "node-metrics",
+                                format!("Failed to scrape node metrics: {err}"),
+                            );
+                        }
+                        continue;
+                    }
+                };
+                if std::mem::take(&mut failing) {
+                    journal.log(Severity::Info, "node-metrics", "Scraped node metrics again");
+                }
+                let now = Instant::now();
+                let delta = |name: &str| {
+                    let (time, previous) = previous.as_ref()?;
+                    Some((
+                        samples.get(name)? - previous.get(name)?,
+                        now.duration_since(*time).as_secs_f64(),
+                    ))
+                };
+                let gas_per_second = if names.gas.ends_with("_total") {
+                    delta(&names.gas).map(|(gas, elapsed)| gas / elapsed)
+                } else {
+                    samples.get(&names.gas).copied()
+                };
+                let block_time = names.block_time.as_ref().and_then(|name| {
+                    samples.get(name).copied().or_else(|| {
+                        let (sum, _) = delta(&format!("{name}_sum"))?;
+                        let (count, _) = delta(&format!("{name}_count"))?;
+                        (count > 0.0).then(|| sum / count)
+                    })
+                });
+                {
+                    let mut state = scraped.lock().unwrap();
+                    state.gas_per_second = gas_per_second;
+                    state.block_time_ms = block_time.map(|seconds| seconds * 1000.0);
+                }
+                previous = Some((now, samples));
+            }
+        });
+        Self { state }
+    }
+
+    /// Get the latest node-reported values, compared with the observed gas rate.
+    pub fn metrics(&self, observed_gas_per_second: f64) -> NodeMetrics {
+        let mut metrics = self.state.lock().unwrap().clone();
+        metrics.observed_gas_ratio = metrics
+            .gas_per_second
+            .filter(|gas| *gas > 0.0)
+            .map(|gas| observed_gas_per_second / gas);
+        metrics
+    }
+}
+
+/// Fetch the metrics in the Prometheus text format, summing the series of every metric.
+pub async fn scrape(http: &reqwest::Client, url: &str) -> Result<HashMap<String, f64>> {
+    let body = http
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let mut samples = HashMap::new();
+    for line in body.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        // `name{labels} value [timestamp]`, where the labels may contain spaces.
+        let (series, rest) = match line.find('}') {
+            Some(end) => (&line[..=end], &line[end + 1..]),
+            None => line.split_once(' ').unwrap_or((line, "")),
+        };
+        let name = series.split('{').next().unwrap_or_default().trim();
+        let Some(Ok(value)) = rest.split_whitespace().next().map(str::parse::<f64>) else {
+            continue;
+        };
+        if !name.is_empty() && value.is_finite() {
+            *samples.entry(name.to_string()).or_default() += value;
+        }
+    }
+    Ok(samples)
+}
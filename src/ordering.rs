@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use alloy::{
+    consensus::{Transaction, Typed2718},
+    network::{AnyRpcBlock, TransactionResponse},
+    primitives::Address,
+};
+use serde::Serialize;
+
+/// The type of deposit transactions, whose nonces are not chosen by the sender.
+const DEPOSIT_TX_TYPE: u8 = 0x7e;
+
+/// Same-sender patterns within a block, a proxy for bot and bundle activity.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockOrdering {
+    /// Runs of at least two transactions of a sender with sequential nonces.
+    pub nonce_chains: usize,
+    /// Transactions belonging to a nonce chain.
+    pub chained_transactions: usize,
+    /// Adjacent transactions from the same sender.
+    pub back_to_back: usize,
+    /// Transactions considered, i.e. all but deposits.
+    pub transactions: usize,
+}
+
+/// The same-sender patterns of the current window.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderingMetrics {
+    pub nonce_chains: usize,
+    /// Share of the transactions belonging to a nonce chain in percent.
+    pub chained_percentage: f64,
+    pub back_to_back: usize,
+}
+
+impl BlockOrdering {
+    /// Analyze the block, known only when full transactions were fetched.
+    pub fn new(block: &AnyRpcBlock) -> Option<Self> {
+        let txs = block.transactions.as_transactions()?;
+        let mut ordering = Self::default();
+        let mut nonces = HashMap::<Address, Vec<u64>>::new();
+        let mut previous = None;
+        for tx in txs.iter().filter(|tx| tx.ty() != DEPOSIT_TX_TYPE) {
+            let sender = tx.from();
+            if previous == Some(sender) {
+                ordering.back_to_back += 1;
+            }
+            previous = Some(sender);
+            nonces.entry(sender).or_default().push(tx.nonce());
+            ordering.transactions += 1;
+        }
+        for nonces in nonces.values_mut() {
+            nonces.sort_unstable();
+            let mut run = 1;
+            for pair in nonces.windows(2) {
+                if pair[1] == pair[0] + 1 {
+                    run += 1;
+                    continue;
+                }
+                ordering.add_run(run);
+                run = 1;
+            }
+            ordering.add_run(run);
+        }
+        Some(ordering)
+    }
+
+    #[inline]
+    fn add_run(&mut self, run: usize) {
+        if run > 1 {
+            self.nonce_chains += 1;
+            self.chained_transactions += run;
+        }
+    }
+}
+
+impl OrderingMetrics {
+    /// Sum up the patterns of the blocks in the window.
+    pub fn new(blocks: impl IntoIterator<Item = BlockOrdering>) -> Self {
+        let total = blocks
+            .into_iter()
+            .fold(BlockOrdering::default(), |total, block| BlockOrdering {
+                nonce_chains: total.nonce_chains + block.nonce_chains,
+                chained_transactions: total.chained_transactions + block.chained_transactions,
+                back_to_back: total.back_to_back + block.back_to_back,
+                transactions: total.transactions + block.transactions,
+            });
+        Self {
+            nonce_chains: total.nonce_chains,
+            chained_percentage: if total.transactions == 0 {
+                0.0
+            } else {
+                100.0 * total.chained_transactions as f64 / total.transactions as f64
+            },
+            back_to_back: total.back_to_back,
+        }
+    }
+}
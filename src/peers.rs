@@ -0,0 +1,135 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy::{primitives::U64, providers::Provider};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    journal::{Journal, Severity},
+    rpc::{ErrorResponse, RpcClient},
+};
+
+/// Polls the P2P peers of the node, as block production problems often start as network ones.
+pub struct PeerMonitor {
+    state: Arc<Mutex<PeerMetrics>>,
+}
+
+/// The peers of the node as of the latest poll.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PeerMetrics {
+    /// Reported by `net_peerCount`.
+    pub peer_count: Option<u64>,
+    /// Peers listed by `admin_peers`, unknown if the admin namespace is not exposed.
+    pub admin_peers: Option<usize>,
+    /// Listed peers which connected to the node.
+    pub inbound_peers: Option<usize>,
+}
+
+impl PeerMonitor {
+    /// Poll the peers every `interval`, logging a warning when the peer count drops below
+    /// `min_peers`.
+    pub fn spawn(
+        rpc: Arc<RpcClient>,
+        interval: Duration,
+        min_peers: Option<u64>,
+        journal: Journal,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(PeerMetrics::default()));
+        let polled = state.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // Stop asking for `admin_peers` once the node refused it, as nodes often disable the
+            // admin namespace.
+            let mut admin = true;
+            let mut low = false;
+            // Log only when the polling starts failing or recovers, not on every poll.
+            let mut failing = false;
+            loop {
+                ticker.tick().await;
+                let peer_count = rpc
+                    .request("net_peerCount", |provider| async move {
+                        provider
+                            .raw_request::<_, U64>("net_peerCount".into(), ())
+                            .await
+                    })
+                    .await
+                    .map(|count| count.to::<u64>());
+                let peers = if admin {
+                    match rpc
+                        .request("admin_peers", |provider| async move {
+                            provider
+                                .raw_request::<_, Option<Vec<Value>>>("admin_peers".into(), ())
+                                .await
+                        })
+                        .await
+                    {
+                        Ok(peers) => {
+                            admin = peers.is_some();
+                            peers
+                        }
+                        Err(err) => {
+                            admin = !err.is::<ErrorResponse>();
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                let peer_count = match peer_count {
+                    Ok(count) => {
+                        if std::mem::take(&mut failing) {
+                            journal.log(Severity::Info, "peers", "Got the peer count again");
+                        }
+                        Some(count)
+                    }
+                    Err(err) => {
+                        if !std::mem::replace(&mut failing, true) {
+                            journal.log(
+                                Severity::Warn,
+                                "peers",
+                                format!("Failed to get the peer count: {err}"),
+                            );
+                        }
+                        None
+                    }
+                };
+                if let (Some(count), Some(min_peers)) = (peer_count, min_peers) {
+                    if count < min_peers && !low {
+                        journal.log(
+                            Severity::Warn,
+                            "peers",
+                            format!("Peer count dropped to {count} (below {min_peers})"),
+                        );
+                    } else if count >= min_peers && low {
+                        journal.log(
+                            Severity::Info,
+                            "peers",
+                            format!("Peer count recovered to {count}"),
+                        );
+                    }
+                    low = count < min_peers;
+                }
+                *polled.lock().unwrap() = PeerMetrics {
+                    peer_count,
+                    inbound_peers: peers.as_ref().map(|peers| {
+                        peers
+                            .iter()
+                            .filter(|peer| peer["network"]["inbound"].as_bool() == Some(true))
+                            .count()
+                    }),
+                    admin_peers: peers.map(|peers| peers.len()),
+                };
+            }
+        });
+        Self { state }
+    }
+
+    /// Get the peers as of the latest poll.
+    pub fn metrics(&self) -> PeerMetrics {
+        self.state.lock().unwrap().clone()
+    }
+}
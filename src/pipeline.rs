@@ -0,0 +1,113 @@
+use std::{
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use alloy::{network::AnyRpcBlock, primitives::B256};
+use eyre::Result;
+use futures::{
+    stream::{self, BoxStream},
+    Stream, StreamExt,
+};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    fetch::{Fetched, Fetcher},
+    measurement::Decoded,
+    rpc::RpcClient,
+};
+
+/// Number of received blocks queued for processing before the reader waits.
+const QUEUE: usize = 1024;
+
+/// A block as received from the subscription.
+struct Received {
+    block: AnyRpcBlock,
+    received: Instant,
+    received_at: SystemTime,
+}
+
+/// A received block after fetching and decoding.
+pub struct Processed {
+    pub number: u64,
+    pub hash: B256,
+    pub parent_hash: B256,
+    pub received: Instant,
+    pub received_at: SystemTime,
+    /// When the block data was fetched, unless the subscribed block was used as is.
+    pub fetched: Option<Instant>,
+    pub decoded: Result<Option<Decoded>>,
+}
+
+/// Read the subscription on its own task, so the receive times are not delayed by the
+/// processing of earlier blocks, and fetch and decode up to `depth` blocks concurrently.
+///
+/// The blocks are yielded in the order they were received.
+pub fn spawn<'a>(
+    mut subscription: BoxStream<'static, AnyRpcBlock>,
+    rpc: Arc<RpcClient>,
+    fetcher: &'a Fetcher,
+    depth: usize,
+) -> impl Stream<Item = Processed> + 'a {
+    let (sender, receiver) = mpsc::channel(QUEUE);
+    tokio::spawn(async move {
+        while let Some(block) = subscription.next().await {
+            let received = Received {
+                block,
+                received: Instant::now(),
+                received_at: SystemTime::now(),
+            };
+            rpc.account_received("newHeads", &received.block);
+            if sender.send(received).await.is_err() {
+                break;
+            }
+        }
+    });
+    stream::unfold(receiver, |mut receiver| async move {
+        Some((receiver.recv().await?, receiver))
+    })
+    .map(move |received| process(received, fetcher))
+    .buffered(depth.max(1))
+}
+
+async fn process(received: Received, fetcher: &Fetcher) -> Processed {
+    let Received {
+        block,
+        received,
+        received_at,
+    } = received;
+    let (number, hash, parent_hash) = (
+        block.header.number,
+        block.header.hash,
+        block.header.parent_hash,
+    );
+    let fetched = fetcher.subscribed(block).await;
+    // A block taken from its header alone was never fetched.
+    let fetched_at = match &fetched {
+        Ok(Some(f)) if f.block.transactions.is_uncle() => None,
+        _ => Some(Instant::now()),
+    };
+    let decoded = match fetched {
+        Ok(Some(fetched)) => Ok(Some(decode(fetched).await)),
+        Ok(None) => Ok(None),
+        Err(err) => Err(err),
+    };
+    Processed {
+        number,
+        hash,
+        parent_hash,
+        received,
+        received_at,
+        fetched: fetched_at,
+        decoded,
+    }
+}
+
+/// Decode a block on the rayon pool, keeping the runtime threads free for I/O.
+async fn decode(fetched: Fetched) -> Decoded {
+    let (sender, receiver) = oneshot::channel();
+    rayon::spawn(move || {
+        let _ = sender.send(Decoded::new(fetched));
+    });
+    receiver.await.expect("Block decoder panicked")
+}
@@ -0,0 +1,101 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use eyre::{eyre, Result};
+use serde_json::Value;
+
+use crate::journal::{Journal, Severity};
+
+/// Polls the USD price of the native token from a JSON price feed, e.g.
+/// `https://api.coinbase.com/v2/prices/ETH-USD/spot`, to convert the fees.
+#[derive(Clone)]
+pub struct PriceFeed {
+    price: Arc<Mutex<Option<f64>>>,
+}
+
+impl PriceFeed {
+    /// Poll `url` every `interval`, reading the price at the dot-separated `field` of the response
+    /// (e.g. `data.amount`), or the first number in it if not given.
+    pub fn spawn(url: String, field: Option<String>, interval: Duration, journal: Journal) -> Self {
+        let feed = Self {
+            price: Arc::new(Mutex::new(None)),
+        };
+        let price = feed.price.clone();
+        tokio::spawn(async move {
+            let http = reqwest::Client::new();
+            let mut ticker = tokio::time::interval(interval);
+            // Log only when the polling starts failing or recovers, not on every poll.
+            let mut failing = false;
+            loop {
+                ticker.tick().await;
+                match poll(&http, &url, field.as_deref()).await {
+                    Ok(polled) => {
+                        *price.lock().unwrap() = Some(polled);
+                        if std::mem::take(&mut failing) {
+                            journal.log(Severity::Info, "price", "Polled the price feed again");
+                        }
+                    }
+                    Err(err) if !failing => {
+                        failing = true;
+                        journal.log(
+                            Severity::Warn,
+                            "price",
+                            format!("Failed to poll the price feed: {err}"),
+                        );
+                    }
+                    Err(_) => {}
+                }
+            }
+        });
+        feed
+    }
+
+    /// Get the latest price in USD, unknown until the feed was polled.
+    pub fn usd(&self) -> Option<f64> {
+        *self.price.lock().unwrap()
+    }
+}
+
+async fn poll(http: &reqwest::Client, url: &str, field: Option<&str>) -> Result<f64> {
+    let response: Value = http
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let value = match field {
+        Some(field) => field
+            .split('.')
+            .try_fold(&response, |value, key| match value {
+                Value::Array(values) => values.get(key.parse::<usize>().ok()?),
+                value => value.get(key),
+            })
+            .and_then(number),
+        None => first_number(&response),
+    };
+    value
+        .filter(|price| price.is_finite() && *price > 0.0)
+        .ok_or_else(|| eyre!("no price in the response of {url}"))
+}
+
+/// Read a number, also given as a string.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(number) => number.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Find the first number in a response, depth first.
+fn first_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Array(values) => values.iter().find_map(first_number),
+        Value::Object(map) => map.values().find_map(first_number),
+        value => number(value),
+    }
+}
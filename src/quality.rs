@@ -0,0 +1,88 @@
+use alloy::network::{AnyRpcBlock, AnyTransactionReceipt, ReceiptResponse};
+use serde::Serialize;
+
+/// Cross-checks the data the endpoint serves for internal consistency, as caching RPC gateways
+/// sometimes serve receipts or blocks that do not belong together.
+#[derive(Debug, Clone, Default)]
+pub struct QualityCheck {
+    totals: Violations,
+    inconsistent_blocks: u64,
+}
+
+/// The number of inconsistencies found per check.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Violations {
+    /// Blocks whose number of receipts differs from their number of transactions.
+    pub receipt_count: u64,
+    /// Blocks whose header gas differs from the sum of their receipts' gas.
+    pub gas_used: u64,
+    /// Blocks with receipts naming another block.
+    pub receipt_block: u64,
+    /// Blocks with an earlier timestamp than their predecessor.
+    pub timestamp: u64,
+}
+
+/// The data quality of the endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct DataQuality {
+    /// Blocks in the window failing any check.
+    pub window_inconsistent_blocks: usize,
+    /// Blocks failing any check since the start.
+    pub total_inconsistent_blocks: u64,
+    /// The violations per check since the start.
+    pub violations: Violations,
+}
+
+impl QualityCheck {
+    /// Check a block against its receipts and its predecessor's timestamp, returning whether it
+    /// failed any check.
+    pub fn check(
+        &mut self,
+        block: &AnyRpcBlock,
+        receipts: Option<&[AnyTransactionReceipt]>,
+        previous_timestamp: Option<u64>,
+    ) -> bool {
+        let mut found = Violations::default();
+        if let Some(receipts) = receipts {
+            if receipts.len() != block.transactions.len() {
+                found.receipt_count = 1;
+            }
+            if receipts.iter().map(|r| r.gas_used).sum::<u64>() != block.header.gas_used {
+                found.gas_used = 1;
+            }
+            if receipts
+                .iter()
+                .any(|r| r.block_hash().is_some_and(|hash| hash != block.header.hash))
+            {
+                found.receipt_block = 1;
+            }
+        }
+        if previous_timestamp.is_some_and(|previous| block.header.timestamp < previous) {
+            found.timestamp = 1;
+        }
+        self.totals.receipt_count += found.receipt_count;
+        self.totals.gas_used += found.gas_used;
+        self.totals.receipt_block += found.receipt_block;
+        self.totals.timestamp += found.timestamp;
+        let inconsistent = found.count() > 0;
+        self.inconsistent_blocks += inconsistent as u64;
+        inconsistent
+    }
+
+    /// Get the metrics, given the number of inconsistent blocks in the window.
+    pub fn metrics(&self, window_inconsistent_blocks: usize) -> DataQuality {
+        DataQuality {
+            window_inconsistent_blocks,
+            total_inconsistent_blocks: self.inconsistent_blocks,
+            violations: self.totals,
+        }
+    }
+}
+
+impl Violations {
+    /// Get the number of violations of all checks.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.receipt_count + self.gas_used + self.receipt_block + self.timestamp
+    }
+}
@@ -0,0 +1,83 @@
+use std::time::Instant;
+
+use serde::Serialize;
+
+/// Minimum absolute correlation of a rate with time for its slope to count as a trend rather
+/// than noise.
+const TREND_CORRELATION: f64 = 0.5;
+
+/// The direction of a rate over the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trend {
+    Up,
+    Down,
+    Flat,
+}
+
+/// How a rate changes over the window, from a linear regression of the per-block amounts.
+#[derive(Debug, Clone, Serialize)]
+pub struct Slope {
+    /// Change of the rate per minute.
+    pub per_minute: f64,
+    pub trend: Trend,
+}
+
+/// The trends of the throughput over the window, to follow a gradually ramped load.
+#[derive(Debug, Clone, Serialize)]
+pub struct RampMetrics {
+    /// Unknown if only the headers were fetched.
+    pub transactions_per_second: Option<Slope>,
+    pub gas_per_second: Option<Slope>,
+}
+
+impl Slope {
+    /// Fit the per-block amounts (e.g. transactions) of the blocks recorded at the given times.
+    ///
+    /// Dividing by the intervals of single blocks would amplify their jitter, so the amounts are
+    /// turned into rates with the average block interval (seconds) instead.
+    pub fn fit(samples: &[(Instant, f64)], block_interval: f64) -> Option<Self> {
+        let first = samples.first()?.0;
+        let points = samples
+            .iter()
+            .map(|(time, amount)| (time.duration_since(first).as_secs_f64(), *amount))
+            .collect::<Vec<_>>();
+        let (slope, r) = regression(&points)?;
+        Some(Self {
+            per_minute: slope / block_interval * 60.0,
+            trend: match r {
+                r if r >= TREND_CORRELATION => Trend::Up,
+                r if r <= -TREND_CORRELATION => Trend::Down,
+                _ => Trend::Flat,
+            },
+        })
+    }
+}
+
+/// Calculate the least-squares slope and the correlation coefficient of the points.
+///
+/// A constant rate has a slope of zero and a correlation of zero.
+fn regression(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.len() < 3 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in points {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x == 0.0 {
+        return None;
+    }
+    let slope = cov / var_x;
+    let r = if var_y == 0.0 {
+        0.0
+    } else {
+        cov / (var_x * var_y).sqrt()
+    };
+    (slope.is_finite() && r.is_finite()).then_some((slope, r))
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    network::TransactionBuilder,
+    primitives::{Address, Bytes, U256},
+    providers::Provider,
+    rpc::types::{serde_helpers::WithOtherFields, TransactionRequest},
+};
+use serde::Serialize;
+
+use crate::rpc::RpcClient;
+
+/// Number of recent probe samples the latency and its correlation with load are computed over.
+const SAMPLES: usize = 64;
+
+/// Periodically reads from a contract and relates the read latency to the observed chain load.
+pub struct ReadProbe {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The load of the latest snapshot, attached to every new sample.
+    load: Option<Load>,
+    samples: VecDeque<Sample>,
+}
+
+#[derive(Clone, Copy)]
+struct Load {
+    /// Unknown if only the headers were fetched.
+    transactions_per_second: Option<f64>,
+    gas_per_second: f64,
+}
+
+struct Sample {
+    storage_latency_ms: f64,
+    call_latency_ms: f64,
+    load: Load,
+}
+
+/// The read latency of the recent probe samples and how it correlates with the chain load.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadProbeMetrics {
+    /// Average `eth_getStorageAt` latency in milliseconds.
+    pub storage_latency_ms: Option<f64>,
+    /// Average `eth_call` latency in milliseconds.
+    pub call_latency_ms: Option<f64>,
+    /// Pearson correlation of the `eth_call` latency with TPS.
+    pub tps_correlation: Option<f64>,
+    /// Pearson correlation of the `eth_call` latency with gas per second.
+    pub gas_correlation: Option<f64>,
+    pub samples: usize,
+}
+
+impl ReadProbe {
+    /// Start probing `address` every `interval`, reading storage `slot` and calling it with
+    /// `calldata`.
+    pub fn spawn(
+        rpc: Arc<RpcClient>,
+        address: Address,
+        slot: U256,
+        calldata: Bytes,
+        interval: Duration,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let probe_state = state.clone();
+        let tx = WithOtherFields::new(
+            TransactionRequest::default()
+                .with_to(address)
+                .with_input(calldata),
+        );
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                let start = Instant::now();
+                let storage = rpc
+                    .request("eth_getStorageAt", |provider| async move {
+                        provider.get_storage_at(address, slot).await
+                    })
+                    .await;
+                let storage_latency = start.elapsed();
+
+                let start = Instant::now();
+                let call = rpc
+                    .request("eth_call", |provider| {
+                        let tx = tx.clone();
+                        async move { provider.call(&tx).await }
+                    })
+                    .await;
+                let call_latency = start.elapsed();
+
+                // Failed reads are not representative of the read latency.
+                if storage.is_err() || call.is_err() {
+                    continue;
+                }
+                let mut state = probe_state.lock().unwrap();
+                let Some(load) = state.load else {
+                    continue;
+                };
+                state.samples.push_back(Sample {
+                    storage_latency_ms: storage_latency.as_secs_f64() * 1000.0,
+                    call_latency_ms: call_latency.as_secs_f64() * 1000.0,
+                    load,
+                });
+                if state.samples.len() > SAMPLES {
+                    state.samples.pop_front();
+                }
+            }
+        });
+        Self { state }
+    }
+
+    /// Update the chain load the next samples are attributed to.
+    pub fn observe_load(&self, transactions_per_second: Option<f64>, gas_per_second: f64) {
+        self.state.lock().unwrap().load = Some(Load {
+            transactions_per_second,
+            gas_per_second,
+        });
+    }
+
+    /// Collect the metrics of the recent samples.
+    pub fn metrics(&self) -> ReadProbeMetrics {
+        let state = self.state.lock().unwrap();
+        let samples = &state.samples;
+        let mean = |values: Vec<f64>| {
+            (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+        };
+        let latencies = samples
+            .iter()
+            .map(|s| s.call_latency_ms)
+            .collect::<Vec<_>>();
+        ReadProbeMetrics {
+            storage_latency_ms: mean(samples.iter().map(|s| s.storage_latency_ms).collect()),
+            call_latency_ms: mean(latencies.clone()),
+            tps_correlation: samples
+                .iter()
+                .map(|s| s.load.transactions_per_second)
+                .collect::<Option<Vec<_>>>()
+                .and_then(|tps| correlation(&latencies, &tps)),
+            gas_correlation: correlation(
+                &latencies,
+                &samples
+                    .iter()
+                    .map(|s| s.load.gas_per_second)
+                    .collect::<Vec<_>>(),
+            ),
+            samples: samples.len(),
+        }
+    }
+}
+
+/// Calculate the Pearson correlation coefficient, or `None` if it is undefined.
+pub fn correlation(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len() as f64;
+    if xs.len() < 2 {
+        return None;
+    }
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    let r = cov / (var_x * var_y).sqrt();
+    r.is_finite().then_some(r)
+}
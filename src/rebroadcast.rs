@@ -0,0 +1,146 @@
+use std::net::SocketAddr;
+
+use alloy::network::AnyRpcBlock;
+use clap::ValueEnum;
+use eyre::Result;
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::measurement::{Datapoint, Metrics};
+
+/// Maximum number of messages buffered for a slow downstream consumer before it starts
+/// skipping datapoints.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// The version leading every binary frame, bumped whenever its layout changes.
+const FRAME_VERSION: u8 = 1;
+
+/// A local WebSocket server re-broadcasting the enriched datapoints to downstream consumers.
+pub struct Rebroadcaster {
+    sender: broadcast::Sender<Message>,
+    format: RebroadcastFormat,
+}
+
+/// How the datapoints are encoded for the consumers.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum RebroadcastFormat {
+    /// A text message with the block and all its metrics.
+    Json,
+    /// A fixed-layout binary message with the block's key figures, see [`encode_frame`].
+    Binary,
+}
+
+/// The message sent to every consumer for each recorded block.
+#[derive(Serialize)]
+struct EnrichedDatapoint<'a> {
+    block: &'a AnyRpcBlock,
+    metrics: &'a Metrics,
+}
+
+impl Rebroadcaster {
+    /// Bind the WebSocket server to the given address and start accepting consumers.
+    pub async fn bind(addr: SocketAddr, format: RebroadcastFormat) -> Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let accept_sender = sender.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, accept_sender.subscribe()));
+            }
+        });
+        Ok(Self { sender, format })
+    }
+
+    /// Send a datapoint to all connected consumers.
+    #[inline]
+    pub fn send(&self, datapoint: &Datapoint, metrics: &Metrics) -> Result<()> {
+        // Nobody listening is not an error, so the send result is ignored.
+        if self.sender.receiver_count() > 0 {
+            let message = match self.format {
+                RebroadcastFormat::Json => {
+                    Message::text(serde_json::to_string(&EnrichedDatapoint {
+                        block: &datapoint.block,
+                        metrics,
+                    })?)
+                }
+                RebroadcastFormat::Binary => Message::binary(encode_frame(datapoint, metrics)),
+            };
+            let _ = self.sender.send(message);
+        }
+        Ok(())
+    }
+}
+
+/// Encode the key figures of a datapoint as a binary frame, all little-endian:
+///
+/// | bytes | field |
+/// |-------|-------|
+/// | 1 | frame version (1) |
+/// | 8 | block number |
+/// | 32 | block hash |
+/// | 8 | block timestamp (seconds) |
+/// | 2 | mini-blocks |
+/// | 4 | transactions (`u32::MAX` if unknown) |
+/// | 8 | gas used |
+/// | 8 | transactions per second (`f64`, NaN if unknown) |
+/// | 8 | gas per second (`f64`) |
+/// | 8 | mini-block interval in milliseconds (`f64`) |
+/// | 8 | head age in milliseconds (`f64`) |
+/// | 4 | blocks in the window |
+/// | 8 | fetch latency in milliseconds (`f64`, NaN if unknown) |
+fn encode_frame(datapoint: &Datapoint, metrics: &Metrics) -> Vec<u8> {
+    let header = &datapoint.block.header;
+    let transactions = datapoint
+        .transactions()
+        .map_or(u32::MAX, |transactions| transactions as u32);
+    let mut frame = Vec::with_capacity(107);
+    frame.push(FRAME_VERSION);
+    frame.extend_from_slice(&header.number.to_le_bytes());
+    frame.extend_from_slice(header.hash.as_slice());
+    frame.extend_from_slice(&header.timestamp.to_le_bytes());
+    frame
+        .extend_from_slice(&(header.extra_data.first().copied().unwrap_or(0) as u16).to_le_bytes());
+    frame.extend_from_slice(&transactions.to_le_bytes());
+    frame.extend_from_slice(&header.gas_used.to_le_bytes());
+    for value in [
+        metrics.transactions_per_second.unwrap_or(f64::NAN),
+        metrics.gas_per_second,
+        metrics.mini_block_interval_ms,
+        metrics.head_age_ms,
+    ] {
+        frame.extend_from_slice(&value.to_le_bytes());
+    }
+    frame.extend_from_slice(&(metrics.window_blocks as u32).to_le_bytes());
+    frame.extend_from_slice(&metrics.fetch_latency_ms.unwrap_or(f64::NAN).to_le_bytes());
+    frame
+}
+
+/// Forward the broadcast messages to a single consumer until it disconnects.
+async fn serve(stream: TcpStream, mut receiver: broadcast::Receiver<Message>) {
+    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut write, mut read) = ws.split();
+    loop {
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Ok(message) => {
+                    if write.send(message).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            incoming = read.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => continue,
+            },
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    os::fd::AsRawFd,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, TimeDelta};
+use eyre::{eyre, Result};
+use serde::Serialize;
+use tokio::net::UdpSocket;
+
+use crate::journal::{Journal, Severity};
+
+/// Seconds between the NTP era (1900) and the Unix epoch.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Number of recent measurements the offset is chosen from, the one with the least uncertainty
+/// winning like in NTP's clock filter.
+const SAMPLES: usize = 8;
+
+/// How long an NTP server gets to answer.
+const NTP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The external reference the timestamps are corrected to, given as `ntp:<host>[:<port>]` for an
+/// (S)NTP server, e.g. a local PTP grandmaster's NTP service, or `ptp:<device>` for a PTP
+/// hardware clock such as `/dev/ptp0`.
+#[derive(Debug, Clone)]
+pub enum ClockSource {
+    Ntp { host: String, port: u16 },
+    Ptp(PathBuf),
+}
+
+impl FromStr for ClockSource {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(addr) = s.strip_prefix("ntp:") {
+            let (host, port) = match addr.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse()?),
+                None => (addr, 123),
+            };
+            if host.is_empty() {
+                return Err(eyre!("invalid reference clock `{s}`, the host is missing"));
+            }
+            Ok(Self::Ntp {
+                host: host.to_string(),
+                port,
+            })
+        } else if let Some(device) = s.strip_prefix("ptp:") {
+            Ok(Self::Ptp(PathBuf::from(device)))
+        } else {
+            Err(eyre!(
+                "invalid reference clock `{s}`, expected ntp:<host>[:<port>] or ptp:<device>"
+            ))
+        }
+    }
+}
+
+/// A measured offset of the local clock.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// Reference time minus local time.
+    offset: TimeDelta,
+    /// Half the round trip of the measurement, bounding its error.
+    uncertainty: TimeDelta,
+    measured_at: Instant,
+}
+
+/// Tracks the offset of the local clock against an external reference, so the recordings of
+/// several probes can be merged on a common time base.
+#[derive(Clone)]
+pub struct ReferenceClock {
+    source: String,
+    samples: Arc<Mutex<VecDeque<Sample>>>,
+}
+
+/// The state of the reference clock.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceClockMetrics {
+    pub source: String,
+    /// Reference time minus local time in milliseconds, added to the local timestamps.
+    pub offset_ms: f64,
+    /// The error bound of the offset in milliseconds.
+    pub uncertainty_ms: f64,
+    /// Seconds since the offset was measured.
+    pub age_s: f64,
+}
+
+impl ReferenceClock {
+    /// Measure the offset against the source, then keep measuring it every `interval`, logging
+    /// to the journal when the source becomes unreachable and when it recovers. The first
+    /// measurement is awaited, so the first snapshot already carries the reference time.
+    pub async fn start(source: ClockSource, interval: Duration, journal: Journal) -> Self {
+        let clock = Self {
+            source: match &source {
+                ClockSource::Ntp { host, port } => format!("ntp:{host}:{port}"),
+                ClockSource::Ptp(device) => format!("ptp:{}", device.display()),
+            },
+            samples: Arc::new(Mutex::new(VecDeque::with_capacity(SAMPLES + 1))),
+        };
+        let mut failing = false;
+        clock.measure(&source, &journal, &mut failing).await;
+        let task = clock.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes at once.
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                task.measure(&source, &journal, &mut failing).await;
+            }
+        });
+        clock
+    }
+
+    /// Take a measurement, logging whether the source failed or recovered.
+    async fn measure(&self, source: &ClockSource, journal: &Journal, failing: &mut bool) {
+        let measured = match source {
+            ClockSource::Ntp { host, port } => query_ntp(host, *port).await,
+            ClockSource::Ptp(device) => read_ptp(device),
+        };
+        match measured {
+            Ok(sample) => {
+                if *failing {
+                    journal.log(
+                        Severity::Info,
+                        "reference-clock",
+                        format!("Reference clock {} is reachable again", self.source),
+                    );
+                    *failing = false;
+                }
+                let mut samples = self.samples.lock().unwrap();
+                samples.push_back(sample);
+                if samples.len() > SAMPLES {
+                    samples.pop_front();
+                }
+            }
+            Err(err) if !*failing => {
+                journal.log(
+                    Severity::Warn,
+                    "reference-clock",
+                    format!("Reference clock {} failed: {err:#}", self.source),
+                );
+                *failing = true;
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Get the most certain of the recent measurements, unknown until one succeeded.
+    fn best(&self) -> Option<Sample> {
+        self.samples
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .min_by_key(|sample| sample.uncertainty)
+    }
+
+    /// Correct a local RFC 3339 timestamp to the reference, unknown until the offset was
+    /// measured.
+    pub fn correct(&self, timestamp: &str) -> Option<String> {
+        let offset = self.best()?.offset;
+        let local = DateTime::parse_from_rfc3339(timestamp).ok()?;
+        Some((local + offset).to_rfc3339())
+    }
+
+    pub fn metrics(&self) -> Option<ReferenceClockMetrics> {
+        let sample = self.best()?;
+        Some(ReferenceClockMetrics {
+            source: self.source.clone(),
+            offset_ms: milliseconds(sample.offset),
+            uncertainty_ms: milliseconds(sample.uncertainty),
+            age_s: sample.measured_at.elapsed().as_secs_f64(),
+        })
+    }
+}
+
+/// Measure the offset against an NTP server with a single SNTP request (RFC 4330).
+async fn query_ntp(host: &str, port: u16) -> Result<Sample> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).await?;
+    socket.connect((host, port)).await?;
+    let mut request = [0u8; 48];
+    // No leap indicator, version 4, client mode.
+    request[0] = 0x23;
+    let sent = SystemTime::now();
+    request[40..48].copy_from_slice(&to_ntp(sent).to_be_bytes());
+    socket.send(&request).await?;
+    let mut response = [0u8; 48];
+    let received = tokio::time::timeout(NTP_TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| eyre!("no answer within {NTP_TIMEOUT:?}"))??;
+    let arrived = SystemTime::now();
+    if received < 48 || response[0] & 0x07 != 4 {
+        return Err(eyre!("invalid answer"));
+    }
+    if response[1] == 0 {
+        return Err(eyre!("the server is not synchronized"));
+    }
+    let word = |at: usize| u64::from_be_bytes(response[at..at + 8].try_into().unwrap());
+    let t1 = nanos(sent);
+    let t2 = from_ntp(word(32));
+    let t3 = from_ntp(word(40));
+    let t4 = nanos(arrived);
+    let offset = ((t2 - t1) + (t3 - t4)) / 2;
+    let delay = (t4 - t1) - (t3 - t2);
+    Ok(Sample {
+        offset: TimeDelta::nanoseconds(offset as i64),
+        uncertainty: TimeDelta::nanoseconds(delay.max(0) as i64 / 2),
+        measured_at: Instant::now(),
+    })
+}
+
+/// Measure the offset against a PTP hardware clock, reading it between two reads of the local
+/// clock. Hardware clocks usually keep TAI, whose lead over UTC is then part of the offset.
+fn read_ptp(device: &PathBuf) -> Result<Sample> {
+    let file = File::open(device)?;
+    // The dynamic clock id of the open device (FD_TO_CLOCKID in the kernel headers).
+    let clock = ((!file.as_raw_fd()) << 3) | 3;
+    let read = |clock: libc::clockid_t| -> Result<i128> {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        if unsafe { libc::clock_gettime(clock, &mut time) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(time.tv_sec as i128 * 1_000_000_000 + time.tv_nsec as i128)
+    };
+    let before = read(libc::CLOCK_REALTIME)?;
+    let reference = read(clock)?;
+    let after = read(libc::CLOCK_REALTIME)?;
+    Ok(Sample {
+        offset: TimeDelta::nanoseconds((reference - (before + after) / 2) as i64),
+        uncertainty: TimeDelta::nanoseconds(((after - before) / 2) as i64),
+        measured_at: Instant::now(),
+    })
+}
+
+/// Get the nanoseconds since the Unix epoch.
+fn nanos(time: SystemTime) -> i128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as i128
+}
+
+/// Convert a time to a 64-bit NTP timestamp (32 bits of seconds and of fraction).
+fn to_ntp(time: SystemTime) -> u64 {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (since.subsec_nanos() as u64) * (1 << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Convert a 64-bit NTP timestamp to nanoseconds since the Unix epoch.
+fn from_ntp(timestamp: u64) -> i128 {
+    let seconds = (timestamp >> 32) as i128 - NTP_UNIX_OFFSET as i128;
+    let fraction = ((timestamp & 0xffff_ffff) as i128 * 1_000_000_000) >> 32;
+    seconds * 1_000_000_000 + fraction
+}
+
+fn milliseconds(delta: TimeDelta) -> f64 {
+    delta.num_nanoseconds().unwrap_or_default() as f64 / 1e6
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    journal::{Journal, Severity},
+    measurement::Snapshot,
+    sink,
+    sketch::QuantileSketch,
+};
+
+/// The relative accuracy of the rolled up percentiles.
+const PERCENTILE_ACCURACY: f64 = 0.01;
+
+/// Aggregates the snapshots of every period (an hour by default) into a rollup, so long runs can
+/// be analyzed without going through every snapshot again.
+///
+/// Every rollup is logged to the journal and optionally appended to a file as a JSON line.
+pub struct Rollup {
+    period: Duration,
+    journal: Journal,
+    file: Option<BufWriter<File>>,
+    started: Instant,
+    started_at: String,
+    blocks: Option<(u64, u64)>,
+    snapshots: usize,
+    /// The distribution of every numeric snapshot field within the period.
+    fields: BTreeMap<String, Field>,
+    /// Whether a full period was rolled up, i.e. the run is long enough for rollups.
+    emitted: bool,
+}
+
+/// The aggregated snapshots of a period.
+#[derive(Debug, Serialize)]
+struct Summary {
+    start: String,
+    end: String,
+    first_block: u64,
+    last_block: u64,
+    snapshots: usize,
+    metrics: BTreeMap<String, FieldStats>,
+}
+
+/// The values of a snapshot field within a period, sketched rather than kept, so a long period
+/// of frequent snapshots takes little memory.
+struct Field {
+    sum: f64,
+    min: f64,
+    max: f64,
+    sketch: QuantileSketch,
+}
+
+/// The distribution of a snapshot field within a period, its percentiles within 1% of the exact
+/// values.
+#[derive(Debug, Serialize)]
+struct FieldStats {
+    mean: f64,
+    min: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl Rollup {
+    /// Roll up every `period`, appending the rollups to the file at `path` if given.
+    pub fn new(period: Duration, path: Option<&Path>, journal: Journal) -> Result<Self> {
+        Ok(Self {
+            period,
+            journal,
+            file: path.map(File::create).transpose()?.map(BufWriter::new),
+            started: Instant::now(),
+            started_at: Local::now().to_rfc3339(),
+            blocks: None,
+            snapshots: 0,
+            fields: BTreeMap::new(),
+            emitted: false,
+        })
+    }
+
+    /// Add a snapshot, emitting the rollup once the period is over.
+    pub fn record(&mut self, snapshot: &Snapshot) -> Result<()> {
+        for (name, value) in sink::flatten(snapshot)? {
+            if let (Some(value), false) = (value.as_f64(), name == "block_number") {
+                self.fields
+                    .entry(name)
+                    .or_insert_with(Field::new)
+                    .add(value);
+            }
+        }
+        let first = self
+            .blocks
+            .map_or(snapshot.block_number, |(first, _)| first);
+        self.blocks = Some((first, snapshot.block_number));
+        self.snapshots += 1;
+        if self.started.elapsed() >= self.period {
+            self.emit()?;
+        }
+        Ok(())
+    }
+
+    /// Emit the rollup of the rest of the last period at exit, if the run was long enough for
+    /// rollups.
+    pub fn finish(&mut self) -> Result<()> {
+        if self.emitted {
+            self.emit()?;
+        }
+        Ok(())
+    }
+
+    /// Emit the rollup of the snapshots since the last one.
+    fn emit(&mut self) -> Result<()> {
+        let Some((first_block, last_block)) = self.blocks.take() else {
+            return Ok(());
+        };
+        let fields = std::mem::take(&mut self.fields);
+        let summary = Summary {
+            start: std::mem::replace(&mut self.started_at, Local::now().to_rfc3339()),
+            end: Local::now().to_rfc3339(),
+            first_block,
+            last_block,
+            snapshots: std::mem::take(&mut self.snapshots),
+            metrics: fields
+                .into_iter()
+                .map(|(name, field)| (name, FieldStats::new(field)))
+                .collect(),
+        };
+        self.started = Instant::now();
+        self.emitted = true;
+
+        let stats = |name: &str| summary.metrics.get(name);
+        let mut text = format!(
+            "Rollup of blocks {first_block}-{last_block} ({} snapshots)",
+            summary.snapshots
+        );
+        if let Some(tps) = stats("transactions_per_second") {
+            text.push_str(&format!(
+                ", TPS: mean {:.1}, p50 {:.1}, p99 {:.1}",
+                tps.mean, tps.p50, tps.p99
+            ));
+        }
+        if let Some(gas) = stats("gas_per_second") {
+            text.push_str(&format!(", Gas: mean {:.2} Mgas/s", gas.mean / 1_000_000.0));
+        }
+        if let Some(interval) = stats("mini_block_interval_ms") {
+            text.push_str(&format!(
+                ", Mini-block interval: p50 {:.1} ms, p99 {:.1} ms",
+                interval.p50, interval.p99
+            ));
+        }
+        self.journal.log(Severity::Info, "rollup", text);
+
+        if let Some(file) = &mut self.file {
+            serde_json::to_writer(&mut *file, &summary)?;
+            file.write_all(b"\n")?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Field {
+    fn new() -> Self {
+        Self {
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sketch: QuantileSketch::new(PERCENTILE_ACCURACY),
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sketch.add(value);
+    }
+}
+
+impl FieldStats {
+    fn new(field: Field) -> Self {
+        let percentile = |p: f64| field.sketch.quantile(p).unwrap_or_default();
+        Self {
+            mean: field.sum / field.sketch.count() as f64,
+            min: field.min,
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: field.max,
+        }
+    }
+}
@@ -0,0 +1,516 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    network::{AnyNetwork, AnyRpcBlock, AnyTransactionReceipt},
+    primitives::BlockHash,
+    providers::{Provider, RootProvider},
+    rpc::types::BlockTransactionsKind,
+    transports::TransportResult,
+};
+use eyre::{eyre, Result};
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::cache::BlockCache;
+
+/// Delay before the first retry, doubled on every further attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The longest delay before a retry.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Share of the request rate that may be spent on retries.
+const RETRY_BUDGET_RATIO: f64 = 0.2;
+
+/// Maximum number of retries that can be saved up in the budget.
+const RETRY_BUDGET_MAX: f64 = 10.0;
+
+/// Number of recent blocks (and of their receipts) kept to answer repeated requests.
+const CACHED_BLOCKS: usize = 64;
+
+/// The span over which the request rate is accounted.
+const RATE_SPAN: Duration = Duration::from_secs(60);
+
+/// How the RPC client protects the node from telescope's own traffic.
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    /// Maximum number of retries of a single call.
+    pub retries: u32,
+    /// Maximum number of calls in flight.
+    pub concurrency: usize,
+    /// Number of consecutive failures that open the circuit breaker.
+    pub breaker_threshold: u32,
+    /// How long the circuit breaker stays open before letting a trial call through.
+    pub breaker_cooldown: Duration,
+    /// Maximum number of requests (including retries) sent per second.
+    pub max_rps: Option<f64>,
+    /// Account the payload bytes of the responses and notifications received.
+    pub bandwidth: bool,
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            concurrency: 8,
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(5),
+            max_rps: None,
+            bandwidth: false,
+        }
+    }
+}
+
+/// A shared RPC client governing every call telescope makes to the node with a retry budget, a
+/// circuit breaker and a concurrency limit.
+pub struct RpcClient {
+    provider: RootProvider<AnyNetwork>,
+    config: RpcConfig,
+    permits: Semaphore,
+    guard: Mutex<Guard>,
+    counters: Counters,
+    /// The send time and method of every request within the last [`RATE_SPAN`].
+    recent: Mutex<VecDeque<(Instant, &'static str)>>,
+    /// The earliest time the next request may be sent under `max_rps`.
+    next_slot: tokio::sync::Mutex<Instant>,
+    bandwidth: Mutex<Bandwidth>,
+    /// The recent blocks by hash and the kind of their transactions.
+    blocks: BlockCache<(BlockHash, BlockTransactionsKind), AnyRpcBlock>,
+    receipts: BlockCache<BlockHash, Vec<AnyTransactionReceipt>>,
+}
+
+/// The mutable state deciding whether a call or a retry may go out.
+struct Guard {
+    breaker: Breaker,
+    /// Retries currently available, refilled by every request.
+    budget: f64,
+}
+
+enum Breaker {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// The cooldown passed; a single trial call decides whether the breaker closes again.
+    HalfOpen {
+        trial_in_flight: bool,
+    },
+}
+
+/// The admission of a half-open breaker's trial call, freeing the trial for another call if it
+/// ends without an outcome, e.g. when the request is cancelled.
+struct Trial<'a>(&'a Mutex<Guard>);
+
+/// The payload bytes received, if accounted.
+struct Bandwidth {
+    started: Instant,
+    /// The receive time and size of every payload within the last [`RATE_SPAN`].
+    recent: VecDeque<(Instant, usize)>,
+    /// Number of payloads and their bytes by method or subscription since the start.
+    by_source: BTreeMap<&'static str, (u64, u64)>,
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: AtomicU64,
+    retries: AtomicU64,
+    failures: AtomicU64,
+    rejected: AtomicU64,
+    breaker_opens: AtomicU64,
+    cache_hits: AtomicU64,
+}
+
+/// Counters of the RPC calls made since the start.
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcStats {
+    pub requests: u64,
+    pub retries: u64,
+    pub failures: u64,
+    /// Calls refused because the circuit breaker was open.
+    pub rejected: u64,
+    pub breaker_opens: u64,
+    /// Block and receipt requests answered from the cache instead of the node.
+    pub cache_hits: u64,
+    /// Requests (including retries) sent within the last minute.
+    pub requests_per_minute: u64,
+    /// Requests sent within the last minute by method.
+    pub methods_per_minute: BTreeMap<&'static str, u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bandwidth: Option<BandwidthStats>,
+}
+
+/// The payload bytes received from the node, i.e. the JSON of the responses and subscription
+/// notifications without the transport framing.
+#[derive(Debug, Clone, Serialize)]
+pub struct BandwidthStats {
+    /// Payload received per second within the last minute in MB/s.
+    pub megabytes_per_second: f64,
+    pub total_bytes: u64,
+    /// Average payload size by method or subscription in bytes.
+    pub mean_bytes: BTreeMap<&'static str, f64>,
+}
+
+/// The error response of the node to a call, e.g. because it does not support the method.
+#[derive(Debug)]
+pub struct ErrorResponse {
+    method: &'static str,
+    message: String,
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed: {}", self.method, self.message)
+    }
+}
+
+impl std::error::Error for ErrorResponse {}
+
+impl RpcClient {
+    pub fn new(provider: RootProvider<AnyNetwork>, config: RpcConfig) -> Self {
+        Self {
+            permits: Semaphore::new(config.concurrency),
+            provider,
+            config,
+            guard: Mutex::new(Guard {
+                breaker: Breaker::Closed { failures: 0 },
+                budget: RETRY_BUDGET_MAX,
+            }),
+            counters: Counters::default(),
+            recent: Mutex::new(VecDeque::new()),
+            next_slot: tokio::sync::Mutex::new(Instant::now()),
+            bandwidth: Mutex::new(Bandwidth {
+                started: Instant::now(),
+                recent: VecDeque::new(),
+                by_source: BTreeMap::new(),
+            }),
+            blocks: BlockCache::new(CACHED_BLOCKS),
+            receipts: BlockCache::new(CACHED_BLOCKS),
+        }
+    }
+
+    /// Get the underlying provider, e.g. for subscriptions which are not request/response calls.
+    #[inline]
+    pub fn provider(&self) -> &RootProvider<AnyNetwork> {
+        &self.provider
+    }
+
+    /// Get the counters of the calls made so far.
+    pub fn stats(&self) -> RpcStats {
+        let mut recent = self.recent.lock().unwrap();
+        prune(&mut recent);
+        let mut methods_per_minute = BTreeMap::new();
+        for (_, method) in recent.iter() {
+            *methods_per_minute.entry(*method).or_default() += 1;
+        }
+        RpcStats {
+            requests: self.counters.requests.load(Ordering::Relaxed),
+            retries: self.counters.retries.load(Ordering::Relaxed),
+            failures: self.counters.failures.load(Ordering::Relaxed),
+            rejected: self.counters.rejected.load(Ordering::Relaxed),
+            breaker_opens: self.counters.breaker_opens.load(Ordering::Relaxed),
+            cache_hits: self.counters.cache_hits.load(Ordering::Relaxed),
+            requests_per_minute: recent.len() as u64,
+            methods_per_minute,
+            bandwidth: self.config.bandwidth.then(|| {
+                let mut bandwidth = self.bandwidth.lock().unwrap();
+                bandwidth.prune();
+                let span = bandwidth.started.elapsed().min(RATE_SPAN).as_secs_f64();
+                let recent = bandwidth
+                    .recent
+                    .iter()
+                    .map(|(_, bytes)| bytes)
+                    .sum::<usize>();
+                BandwidthStats {
+                    megabytes_per_second: recent as f64 / span / 1_000_000.0,
+                    total_bytes: bandwidth.by_source.values().map(|(_, bytes)| bytes).sum(),
+                    mean_bytes: bandwidth
+                        .by_source
+                        .iter()
+                        .map(|(source, (count, bytes))| (*source, *bytes as f64 / *count as f64))
+                        .collect(),
+                }
+            }),
+        }
+    }
+
+    /// Account the payload of a response or subscription notification, if enabled.
+    ///
+    /// The transport does not expose the raw messages, so the payload is measured by serializing
+    /// it again.
+    pub fn account_received(&self, source: &'static str, payload: &impl Serialize) {
+        if !self.config.bandwidth {
+            return;
+        }
+        let bytes = serde_json::to_vec(payload).map_or(0, |json| json.len());
+        let mut bandwidth = self.bandwidth.lock().unwrap();
+        bandwidth.recent.push_back((Instant::now(), bytes));
+        let (count, total) = bandwidth.by_source.entry(source).or_default();
+        *count += 1;
+        *total += bytes as u64;
+        bandwidth.prune();
+    }
+
+    /// Get a block by its hash, from the cache if it was requested recently.
+    pub async fn get_block_by_hash(
+        &self,
+        hash: BlockHash,
+        kind: BlockTransactionsKind,
+    ) -> Result<Option<AnyRpcBlock>> {
+        let (block, cached) = self
+            .blocks
+            .get_or_fetch((hash, kind), || {
+                self.request("eth_getBlockByHash", |provider| async move {
+                    provider.get_block_by_hash(hash, kind).await
+                })
+            })
+            .await?;
+        self.count_hit(cached);
+        Ok(block)
+    }
+
+    /// Get a block by its number, caching it by its hash.
+    pub async fn get_block_by_number(
+        &self,
+        number: u64,
+        kind: BlockTransactionsKind,
+    ) -> Result<Option<AnyRpcBlock>> {
+        let block = self
+            .request("eth_getBlockByNumber", |provider| async move {
+                provider.get_block_by_number(number.into(), kind).await
+            })
+            .await?;
+        if let Some(block) = &block {
+            self.blocks.insert((block.header.hash, kind), block.clone());
+        }
+        Ok(block)
+    }
+
+    /// Get the receipts of all transactions in a block, from the cache if they were requested
+    /// recently.
+    pub async fn get_block_receipts(
+        &self,
+        hash: BlockHash,
+    ) -> Result<Option<Vec<AnyTransactionReceipt>>> {
+        let (receipts, cached) = self
+            .receipts
+            .get_or_fetch(hash, || {
+                self.request("eth_getBlockReceipts", |provider| async move {
+                    provider.get_block_receipts(hash.into()).await
+                })
+            })
+            .await?;
+        self.count_hit(cached);
+        Ok(receipts)
+    }
+
+    #[inline]
+    fn count_hit(&self, cached: bool) {
+        if cached {
+            self.counters.cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Get the number of transactions in the block with the given hash.
+    pub async fn get_block_transaction_count_by_hash(
+        &self,
+        hash: BlockHash,
+    ) -> Result<Option<u64>> {
+        self.request(
+            "eth_getBlockTransactionCountByHash",
+            |provider| async move { provider.get_block_transaction_count_by_hash(hash).await },
+        )
+        .await
+    }
+
+    /// Get the number of the latest block.
+    pub async fn get_block_number(&self) -> Result<u64> {
+        self.request("eth_blockNumber", |provider| async move {
+            provider.get_block_number().await
+        })
+        .await
+    }
+
+    /// Make a call through the retry policy, circuit breaker and concurrency limit.
+    ///
+    /// `call` is invoked once per attempt.
+    pub async fn request<T, F, Fut>(&self, method: &'static str, call: F) -> Result<T>
+    where
+        T: Serialize,
+        F: Fn(RootProvider<AnyNetwork>) -> Fut,
+        Fut: Future<Output = TransportResult<T>>,
+    {
+        let _permit = self.permits.acquire().await?;
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        self.deposit();
+
+        let mut attempt = 0;
+        loop {
+            let Some(trial) = self.admit() else {
+                self.counters.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(eyre!("{method} rejected: circuit breaker is open"));
+            };
+            let _trial = trial.then(|| Trial(&self.guard));
+            self.throttle().await;
+            self.account(method);
+            match call(self.provider.clone()).await {
+                Ok(value) => {
+                    self.on_success();
+                    self.account_received(method, &value);
+                    return Ok(value);
+                }
+                // The node answered; an error response, e.g. for an unsupported method, says
+                // nothing about its health and would only fail again.
+                Err(err) if err.is_error_resp() => {
+                    return Err(ErrorResponse {
+                        method,
+                        message: err.to_string(),
+                    }
+                    .into())
+                }
+                Err(err) => {
+                    self.on_failure();
+                    if attempt >= self.config.retries || !self.withdraw() {
+                        self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                        return Err(eyre!(
+                            "{method} failed after {} attempts: {err}",
+                            attempt + 1
+                        ));
+                    }
+                    self.counters.retries.fetch_add(1, Ordering::Relaxed);
+                    let backoff = RETRY_BACKOFF * 2u32.saturating_pow(attempt);
+                    tokio::time::sleep(backoff.min(MAX_RETRY_BACKOFF)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Wait until sending another request keeps the rate within `max_rps`.
+    async fn throttle(&self) {
+        let Some(max_rps) = self.config.max_rps else {
+            return;
+        };
+        let at = {
+            let mut next_slot = self.next_slot.lock().await;
+            let at = (*next_slot).max(Instant::now());
+            *next_slot = at + Duration::from_secs_f64(1.0 / max_rps);
+            at
+        };
+        tokio::time::sleep_until(at.into()).await;
+    }
+
+    /// Account a request that is about to be sent.
+    #[inline]
+    fn account(&self, method: &'static str) {
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_back((Instant::now(), method));
+        prune(&mut recent);
+    }
+
+    /// Refill the retry budget for a new request.
+    #[inline]
+    fn deposit(&self) {
+        let mut guard = self.guard.lock().unwrap();
+        guard.budget = (guard.budget + RETRY_BUDGET_RATIO).min(RETRY_BUDGET_MAX);
+    }
+
+    /// Take a retry from the budget, returning `false` if it is exhausted.
+    #[inline]
+    fn withdraw(&self) -> bool {
+        let mut guard = self.guard.lock().unwrap();
+        if guard.budget < 1.0 {
+            return false;
+        }
+        guard.budget -= 1.0;
+        true
+    }
+
+    /// Check whether the circuit breaker lets a call through, returning whether it is the trial
+    /// of a half-open breaker, or `None` if it is rejected.
+    #[inline]
+    fn admit(&self) -> Option<bool> {
+        let mut guard = self.guard.lock().unwrap();
+        match guard.breaker {
+            Breaker::Closed { .. } => Some(false),
+            Breaker::HalfOpen {
+                trial_in_flight: true,
+            } => None,
+            Breaker::Open { until } if Instant::now() < until => None,
+            Breaker::Open { .. } | Breaker::HalfOpen { .. } => {
+                guard.breaker = Breaker::HalfOpen {
+                    trial_in_flight: true,
+                };
+                Some(true)
+            }
+        }
+    }
+
+    #[inline]
+    fn on_success(&self) {
+        self.guard.lock().unwrap().breaker = Breaker::Closed { failures: 0 };
+    }
+
+    #[inline]
+    fn on_failure(&self) {
+        let mut guard = self.guard.lock().unwrap();
+        let open = match guard.breaker {
+            Breaker::Closed { ref mut failures } => {
+                *failures += 1;
+                *failures >= self.config.breaker_threshold
+            }
+            Breaker::HalfOpen { .. } => true,
+            Breaker::Open { .. } => false,
+        };
+        if open {
+            guard.breaker = Breaker::Open {
+                until: Instant::now() + self.config.breaker_cooldown,
+            };
+            self.counters.breaker_opens.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Trial<'_> {
+    fn drop(&mut self) {
+        // A trial with an outcome already closed or reopened the breaker.
+        if let Breaker::HalfOpen { trial_in_flight } = &mut self.0.lock().unwrap().breaker {
+            *trial_in_flight = false;
+        }
+    }
+}
+
+/// Drop the requests sent before the accounted span.
+#[inline]
+fn prune(recent: &mut VecDeque<(Instant, &'static str)>) {
+    while recent
+        .front()
+        .is_some_and(|(sent, _)| sent.elapsed() > RATE_SPAN)
+    {
+        recent.pop_front();
+    }
+}
+
+impl Bandwidth {
+    /// Drop the payloads received before the accounted span.
+    #[inline]
+    fn prune(&mut self) {
+        while self
+            .recent
+            .front()
+            .is_some_and(|(received, _)| received.elapsed() > RATE_SPAN)
+        {
+            self.recent.pop_front();
+        }
+    }
+}
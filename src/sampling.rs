@@ -0,0 +1,94 @@
+use std::{collections::VecDeque, sync::Mutex, time::Instant};
+
+use serde::Serialize;
+
+/// Number of recent blocks the block rate is measured over.
+const RATE_BLOCKS: usize = 64;
+
+/// Picks the blocks whose transactions and receipts are analyzed in detail, sampling an evenly
+/// spread fraction of them while the chain produces more blocks per second than can be analyzed.
+pub struct Sampler {
+    /// The block rate up to which every block is analyzed.
+    max_blocks_per_second: f64,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// When the recent blocks arrived.
+    arrivals: VecDeque<Instant>,
+    /// The fraction of a block carried over, a block is sampled once it reaches one.
+    credit: f64,
+    ratio: f64,
+    sampled: u64,
+    skipped: u64,
+}
+
+/// How the detailed analyses are sampled.
+#[derive(Debug, Clone, Serialize)]
+pub struct SamplingMetrics {
+    /// The block rate the sampling ratio follows.
+    pub blocks_per_second: f64,
+    /// The fraction of the blocks currently analyzed in detail (0 to 1).
+    pub ratio: f64,
+    /// Blocks analyzed and skipped since the start.
+    pub sampled_blocks: u64,
+    pub skipped_blocks: u64,
+}
+
+impl Sampler {
+    pub fn new(max_blocks_per_second: f64) -> Self {
+        Self {
+            max_blocks_per_second,
+            state: Mutex::new(State {
+                ratio: 1.0,
+                ..State::default()
+            }),
+        }
+    }
+
+    /// Note the arrival of a block, returning whether it is analyzed in detail.
+    pub fn sample(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.arrivals.push_back(Instant::now());
+        if state.arrivals.len() > RATE_BLOCKS {
+            state.arrivals.pop_front();
+        }
+        let rate = blocks_per_second(&state.arrivals);
+        state.ratio = if rate > self.max_blocks_per_second {
+            self.max_blocks_per_second / rate
+        } else {
+            1.0
+        };
+        // Capped, so a drop in the block rate does not release a burst of samples.
+        state.credit = (state.credit + state.ratio).min(1.0);
+        if state.credit >= 1.0 {
+            state.credit -= 1.0;
+            state.sampled += 1;
+            true
+        } else {
+            state.skipped += 1;
+            false
+        }
+    }
+
+    pub fn metrics(&self) -> SamplingMetrics {
+        let state = self.state.lock().unwrap();
+        SamplingMetrics {
+            blocks_per_second: blocks_per_second(&state.arrivals),
+            ratio: state.ratio,
+            sampled_blocks: state.sampled,
+            skipped_blocks: state.skipped,
+        }
+    }
+}
+
+/// Calculate the rate of the arrivals.
+fn blocks_per_second(arrivals: &VecDeque<Instant>) -> f64 {
+    match (arrivals.front(), arrivals.back()) {
+        (Some(first), Some(last)) if last > first => {
+            (arrivals.len() - 1) as f64 / (*last - *first).as_secs_f64()
+        }
+        _ => 0.0,
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    journal::{Journal, Severity},
+    measurement::Snapshot,
+    read_probe::correlation,
+};
+
+/// Number of recent snapshots the request rate is correlated with the fetch latency over.
+const SAMPLES: usize = 64;
+
+/// Number of snapshots needed before judging the load.
+const MIN_SAMPLES: usize = 16;
+
+/// The correlation from which the fetch latency counts as following the request rate.
+const MIN_CORRELATION: f64 = 0.7;
+
+/// How much slower the fetches must be at the busier half of the samples.
+const MIN_SLOWDOWN: f64 = 2.0;
+
+/// The minimum time between two warnings.
+const WARN_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Watches whether telescope's own requests slow the endpoint down, by correlating its request
+/// rate with the latency of its block fetches.
+pub struct SelfLoad {
+    /// What to change to lighten the load, given the configured metrics.
+    advice: String,
+    journal: Journal,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The requests per minute and the fetch latency of the recent snapshots.
+    samples: VecDeque<(f64, f64)>,
+    warned: Option<Instant>,
+}
+
+/// How the fetch latency relates to telescope's own request rate.
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfLoadMetrics {
+    /// Pearson correlation of the fetch latency with the request rate.
+    pub latency_correlation: Option<f64>,
+    /// How many times slower the fetches are at the busier half of the samples.
+    pub slowdown: Option<f64>,
+    /// Whether the requests likely overload the endpoint.
+    pub overloaded: bool,
+}
+
+impl SelfLoad {
+    pub fn new(advice: String, journal: Journal) -> Self {
+        Self {
+            advice,
+            journal,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Add the request rate and fetch latency of a snapshot, warning if the requests likely
+    /// overload the endpoint.
+    pub fn observe(&self, snapshot: &Snapshot) -> SelfLoadMetrics {
+        let mut state = self.state.lock().unwrap();
+        let metrics = &snapshot.metrics;
+        if let (Some(rpc), Some(latency)) = (&metrics.rpc, metrics.fetch_latency_ms) {
+            state
+                .samples
+                .push_back((rpc.requests_per_minute as f64, latency));
+            if state.samples.len() > SAMPLES {
+                state.samples.pop_front();
+            }
+        }
+        let (rates, latencies): (Vec<_>, Vec<_>) = state.samples.iter().copied().unzip();
+        let latency_correlation = correlation(&rates, &latencies);
+        let slowdown = slowdown(&state.samples);
+        let overloaded = state.samples.len() >= MIN_SAMPLES
+            && latency_correlation.is_some_and(|r| r >= MIN_CORRELATION)
+            && slowdown.is_some_and(|slowdown| slowdown >= MIN_SLOWDOWN);
+        if overloaded
+            && state
+                .warned
+                .is_none_or(|warned| warned.elapsed() > WARN_INTERVAL)
+        {
+            state.warned = Some(Instant::now());
+            self.journal.log(
+                Severity::Warn,
+                "self-load",
+                format!(
+                    "Block fetches are {:.1}x slower while telescope sends more requests \
+                     (r: {:+.2}), it likely overloads the endpoint; {}",
+                    slowdown.unwrap_or_default(),
+                    latency_correlation.unwrap_or_default(),
+                    self.advice
+                ),
+            );
+        }
+        SelfLoadMetrics {
+            latency_correlation,
+            slowdown,
+            overloaded,
+        }
+    }
+}
+
+/// Compare the mean fetch latency of the busier half of the samples with the quieter half.
+fn slowdown(samples: &VecDeque<(f64, f64)>) -> Option<f64> {
+    let mut samples = samples.iter().copied().collect::<Vec<_>>();
+    samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (quiet, busy) = samples.split_at(samples.len() / 2);
+    let mean = |half: &[(f64, f64)]| {
+        (!half.is_empty()).then(|| half.iter().map(|(_, l)| l).sum::<f64>() / half.len() as f64)
+    };
+    let (quiet, busy) = (mean(quiet)?, mean(busy)?);
+    (quiet > 0.0).then(|| busy / quiet)
+}
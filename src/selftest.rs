@@ -0,0 +1,321 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    network::{AnyNetwork, AnyRpcBlock},
+    primitives::U64,
+    providers::{Provider, RootProvider},
+    rpc::types::BlockTransactionsKind,
+};
+use clap::Args;
+use eyre::{eyre, Result};
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+
+use crate::{
+    delivery,
+    fetch::header_block,
+    header_time::HeaderTime,
+    proxy::{self, Proxy},
+};
+
+/// How long every call or subscription may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Probe an endpoint for the methods telescope relies on and print which of its features the
+/// endpoint supports.
+#[derive(Args, Debug)]
+pub struct SelftestArgs {
+    /// The WebSocket endpoint of the node.
+    #[arg(short, long, default_value = "ws://localhost:8546")]
+    endpoint: String,
+
+    /// Reach the endpoint through a SOCKS5 or HTTP proxy, as with the main command.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<Proxy>,
+
+    /// How many blocks to wait for on the subscription.
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    blocks: usize,
+
+    /// How many times to call every method for its baseline latency.
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    samples: usize,
+}
+
+/// The features whose support was probed, printed as the capability matrix.
+#[derive(Default)]
+struct Capabilities {
+    rows: Vec<(&'static str, Result<String>)>,
+}
+
+impl Capabilities {
+    fn probe(&mut self, feature: &'static str, result: Result<String>) {
+        self.rows.push((feature, result));
+    }
+
+    fn print(&self) {
+        let width = self.rows.iter().map(|(f, _)| f.len()).max().unwrap_or(0);
+        println!("\nCapabilities:");
+        for (feature, result) in &self.rows {
+            match result {
+                Ok(detail) => println!("  {feature:width$}  yes  {detail}"),
+                Err(err) => println!("  {feature:width$}  no   {err:#}"),
+            }
+        }
+    }
+}
+
+/// Run the self-test, failing if the endpoint cannot be monitored at all.
+pub async fn run(args: SelftestArgs) -> Result<()> {
+    let provider = timed(proxy::provider(args.endpoint.clone(), args.proxy)).await?;
+    let chain_id = timed(provider.get_chain_id()).await?;
+    let version = timed(provider.get_client_version())
+        .await
+        .unwrap_or_else(|_| "unknown".to_string());
+    println!(
+        "Connected to {} (chain {chain_id}, {version})",
+        delivery::label(&args.endpoint)
+    );
+
+    let headers = timed(subscription(&provider, args.blocks.max(1))).await;
+    match &headers {
+        Ok(headers) => println!(
+            "ok    subscription: {} blocks, {}",
+            headers.len(),
+            cadence(headers)
+        ),
+        Err(err) => println!("FAIL  subscription: {err:#}"),
+    }
+
+    let samples = args.samples.max(1);
+    println!("\nBaseline latencies ({samples} calls):");
+    let head = latency(samples, "eth_blockNumber", || provider.get_block_number())
+        .await
+        .unwrap_or_default();
+    let block = latency(samples, "eth_getBlockByNumber", || {
+        provider.get_block_by_number(BlockNumberOrTag::Number(head), BlockTransactionsKind::Full)
+    })
+    .await;
+    let receipts = match &block {
+        Ok(Some(block)) => {
+            let hash = block.header.hash;
+            latency(samples, "eth_getBlockReceipts", || {
+                provider.get_block_receipts(hash.into())
+            })
+            .await
+            .map(|receipts| receipts.map_or(0, |receipts| receipts.len()))
+        }
+        _ => Err(eyre!("no block to fetch the receipts of")),
+    };
+
+    let mut capabilities = Capabilities::default();
+    capabilities.probe(
+        "live blocks",
+        headers
+            .as_ref()
+            .map(|_| "newHeads subscription".to_string())
+            .map_err(|err| eyre!("{err:#}")),
+    );
+    capabilities.probe(
+        "full-block subscription",
+        timed(async {
+            let _ = provider
+                .subscribe::<_, AnyRpcBlock>(("newHeads", json!({ "includeTransactions": true })))
+                .await?;
+            eyre::Ok("--subscribe-mode full".to_string())
+        })
+        .await,
+    );
+    capabilities.probe(
+        "block details",
+        match &block {
+            Ok(Some(block)) => Ok(format!(
+                "{} transactions in block {head}",
+                block.transactions.len()
+            )),
+            Ok(None) => Err(eyre!("block {head} not served")),
+            Err(err) => Err(eyre!("{err:#}")),
+        },
+    );
+    capabilities.probe(
+        "receipts",
+        receipts.map(|count| {
+            format!("{count} receipts; --topics, --transaction-fees, --watch-token, --data-quality")
+        }),
+    );
+    capabilities.probe(
+        "mini-blocks",
+        mini_blocks(headers.as_deref().unwrap_or_default()),
+    );
+    capabilities.probe(
+        "millisecond header times",
+        match &block {
+            Ok(Some(block)) => header_time(block),
+            _ => Err(eyre!("no block to inspect")),
+        },
+    );
+    capabilities.probe(
+        "fee history",
+        timed(async {
+            provider
+                .get_fee_history(4, BlockNumberOrTag::Latest, &[50.0])
+                .await?;
+            eyre::Ok("--fee-history".to_string())
+        })
+        .await,
+    );
+    capabilities.probe(
+        "pending transactions",
+        timed(async {
+            let _ = provider.subscribe_pending_transactions().await?;
+            eyre::Ok("--mempool".to_string())
+        })
+        .await,
+    );
+    capabilities.probe(
+        "peers",
+        timed(async {
+            let count = provider
+                .raw_request::<_, U64>("net_peerCount".into(), ())
+                .await?;
+            let admin = provider
+                .raw_request::<_, Option<Vec<Value>>>("admin_peers".into(), ())
+                .await
+                .is_ok_and(|peers| peers.is_some());
+            eyre::Ok(format!(
+                "{count} peers{}; --peers",
+                if admin { ", admin_peers exposed" } else { "" }
+            ))
+        })
+        .await,
+    );
+    capabilities.probe(
+        "chain config",
+        timed(async {
+            provider
+                .raw_request::<_, Option<Value>>("eth_config".into(), ())
+                .await?
+                .ok_or_else(|| eyre!("eth_config returned nothing"))?;
+            eyre::Ok("eth_config; --watch-upgrades".to_string())
+        })
+        .await,
+    );
+    capabilities.print();
+
+    headers.map(|_| ())
+}
+
+/// Limit a call to [`TIMEOUT`].
+async fn timed<T, E: Into<eyre::Report>>(call: impl Future<Output = Result<T, E>>) -> Result<T> {
+    tokio::time::timeout(TIMEOUT, call)
+        .await
+        .map_err(|_| eyre!("timed out after {} s", TIMEOUT.as_secs()))?
+        .map_err(Into::into)
+}
+
+/// Wait for `count` headers on the block subscription, checking that they follow each other.
+async fn subscription(
+    provider: &RootProvider<AnyNetwork>,
+    count: usize,
+) -> Result<Vec<(AnyRpcBlock, Instant)>> {
+    let mut stream = provider.subscribe_blocks().await?.into_stream();
+    let mut headers: Vec<(AnyRpcBlock, Instant)> = Vec::with_capacity(count);
+    while headers.len() < count {
+        let header = stream
+            .next()
+            .await
+            .ok_or_else(|| eyre!("the subscription ended"))?;
+        let block = header_block(header);
+        if let Some((last, _)) = headers.last() {
+            if block.header.number <= last.header.number {
+                return Err(eyre!(
+                    "block {} arrived after block {}",
+                    block.header.number,
+                    last.header.number
+                ));
+            }
+        }
+        headers.push((block, Instant::now()));
+    }
+    Ok(headers)
+}
+
+/// Describe how often the subscribed headers arrived.
+fn cadence(headers: &[(AnyRpcBlock, Instant)]) -> String {
+    match (headers.first(), headers.last()) {
+        (Some((first, received)), Some((last, last_received))) if headers.len() > 1 => format!(
+            "blocks {} to {} at {:.0} ms intervals",
+            first.header.number,
+            last.header.number,
+            (*last_received - *received).as_secs_f64() * 1000.0 / (headers.len() - 1) as f64
+        ),
+        _ => "no interval yet".to_string(),
+    }
+}
+
+/// Call a method `samples` times, printing its latency, and return the last response.
+async fn latency<T, E, F>(samples: usize, method: &str, call: impl Fn() -> F) -> Result<T>
+where
+    E: Into<eyre::Report>,
+    F: Future<Output = Result<T, E>>,
+{
+    let mut latencies = Vec::with_capacity(samples);
+    let mut response = None;
+    for _ in 0..samples {
+        let started = Instant::now();
+        match timed(call()).await {
+            Ok(value) => {
+                latencies.push(started.elapsed().as_secs_f64() * 1000.0);
+                response = Some(value);
+            }
+            Err(err) => {
+                println!("  {method:22} failed: {err:#}");
+                return Err(err);
+            }
+        }
+    }
+    latencies.sort_by(f64::total_cmp);
+    println!(
+        "  {method:22} min {:.1} ms, median {:.1} ms, max {:.1} ms",
+        latencies[0],
+        latencies[latencies.len() / 2],
+        latencies[latencies.len() - 1]
+    );
+    response.ok_or_else(|| eyre!("no response"))
+}
+
+/// Check that the extra data of the headers starts with their mini-block count, as on MegaETH.
+fn mini_blocks(headers: &[(AnyRpcBlock, Instant)]) -> Result<String> {
+    if headers.is_empty() {
+        return Err(eyre!("no headers to inspect"));
+    }
+    let counts = headers
+        .iter()
+        .map(|(block, _)| match block.header.extra_data.first() {
+            Some(&count) if count > 0 => Ok(count as u64),
+            Some(_) => Err(eyre!("block {} has no mini-blocks", block.header.number)),
+            None => Err(eyre!("block {} has empty extra data", block.header.number)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(format!(
+        "{:.1} mini-blocks per block in the extra data",
+        counts.iter().sum::<u64>() as f64 / counts.len() as f64
+    ))
+}
+
+/// Look for an extra header field carrying the block time in milliseconds.
+fn header_time(block: &AnyRpcBlock) -> Result<String> {
+    block
+        .other
+        .keys()
+        .find_map(|name| {
+            HeaderTime::Field(name.clone())
+                .millis(block)
+                .map(|_| format!("--header-time field:{name} --rate-clock header"))
+        })
+        .ok_or_else(|| eyre!("no millisecond time field in the header"))
+}
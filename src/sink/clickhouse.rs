@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use eyre::Result;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use super::Sink;
+use crate::{
+    journal::{Journal, Severity},
+    measurement::Snapshot,
+};
+
+/// The table the snapshots are inserted into.
+const TABLE: &str = "telescope_snapshots";
+
+/// The columns of the core metrics; the complete snapshot is kept as JSON in `snapshot`.
+const DDL: &str = "CREATE TABLE IF NOT EXISTS telescope_snapshots (
+    run_id String,
+    timestamp DateTime64(6),
+    block_number UInt64,
+    block_hash String,
+    block_transactions Nullable(UInt64),
+    block_gas_used UInt64,
+    block_interval_ms Nullable(Float64),
+    transactions_per_second Nullable(Float64),
+    gas_per_second Float64,
+    mini_block_interval_ms Float64,
+    window_blocks UInt64,
+    empty_block_percentage Float64,
+    fetch_latency_ms Nullable(Float64),
+    processing_lag_ms Nullable(Float64),
+    snapshot String
+) ENGINE = MergeTree ORDER BY (run_id, block_number)";
+
+/// Maximum number of rows per insert.
+const BATCH_SIZE: usize = 256;
+
+/// How long rows wait at most before they are inserted.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Inserts the snapshots into ClickHouse over its HTTP interface, in batches on a background
+/// task so a slow server does not hold up the measurement.
+pub struct ClickHouseSink {
+    rows: mpsc::UnboundedSender<Value>,
+}
+
+impl ClickHouseSink {
+    /// Create the table if it does not exist and start the inserting task.
+    pub async fn connect(url: &str, journal: Journal) -> Result<Self> {
+        let http = reqwest::Client::new();
+        http.post(url).body(DDL).send().await?.error_for_status()?;
+
+        let (rows, mut rx) = mpsc::unbounded_channel();
+        let url = url.to_string();
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                let flush = tokio::select! {
+                    row = rx.recv() => match row {
+                        Some(row) => {
+                            batch.push(row);
+                            batch.len() >= BATCH_SIZE
+                        }
+                        None => break,
+                    },
+                    _ = ticker.tick() => true,
+                };
+                if flush && !batch.is_empty() {
+                    if let Err(err) = insert(&http, &url, &batch).await {
+                        journal.log(
+                            Severity::Warn,
+                            "clickhouse",
+                            format!(
+                                "Failed to insert {} rows into ClickHouse: {err}",
+                                batch.len()
+                            ),
+                        );
+                    }
+                    batch.clear();
+                }
+            }
+            // Insert what is left once the sink was dropped.
+            if !batch.is_empty() {
+                let _ = insert(&http, &url, &batch).await;
+            }
+        });
+        Ok(Self { rows })
+    }
+}
+
+impl Sink for ClickHouseSink {
+    fn write(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let m = &snapshot.metrics;
+        self.rows.send(json!({
+            "run_id": snapshot.run_id,
+            "timestamp": snapshot.timestamp,
+            "block_number": snapshot.block_number,
+            "block_hash": snapshot.block_hash,
+            "block_transactions": snapshot.block_transactions,
+            "block_gas_used": snapshot.block_gas_used,
+            "block_interval_ms": snapshot.block_interval_ms,
+            "transactions_per_second": m.transactions_per_second,
+            "gas_per_second": m.gas_per_second,
+            "mini_block_interval_ms": m.mini_block_interval_ms,
+            "window_blocks": m.window_blocks,
+            "empty_block_percentage": m.empty_block_percentage,
+            "fetch_latency_ms": m.fetch_latency_ms,
+            "processing_lag_ms": m.processing_lag_ms,
+            "snapshot": serde_json::to_string(snapshot)?,
+        }))?;
+        Ok(())
+    }
+}
+
+/// Insert the rows in one request.
+async fn insert(http: &reqwest::Client, url: &str, rows: &[Value]) -> Result<()> {
+    let mut body = String::new();
+    for row in rows {
+        body.push_str(&row.to_string());
+        body.push('\n');
+    }
+    http.post(url)
+        .query(&[
+            ("query", format!("INSERT INTO {TABLE} FORMAT JSONEachRow")),
+            ("date_time_input_format", "best_effort".to_string()),
+        ])
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,230 @@
+use std::{
+    borrow::Cow,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use eyre::Result;
+use serde_json::Value;
+
+use super::{flatten, Sink};
+use crate::{journal::Entry, measurement::Snapshot};
+
+/// The columns of the events, appended to those of the snapshots.
+const EVENT_COLUMNS: [&str; 3] = ["event_severity", "event_tag", "event_text"];
+
+/// Writes one row per snapshot.
+///
+/// The columns are taken from the first snapshot (or the header of a resumed recording); fields
+/// missing from later snapshots are left empty. A later snapshot with new fields, e.g. of a metric
+/// only known after a while, appends their columns, rewriting the recording with them left empty
+/// in the earlier rows. Events are rows of their own that only fill the `timestamp` and the event
+/// columns.
+pub struct CsvSink {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    columns: Option<Vec<String>>,
+    /// Whether the columns include the events.
+    events: bool,
+    /// The events logged before the header was written.
+    pending: Vec<Entry>,
+    /// The last block of the recording being resumed; snapshots up to it are skipped.
+    resume_after: Option<u64>,
+}
+
+impl CsvSink {
+    /// Create the recording, or append to it if `resume` is set; with `events`, the logged events
+    /// are recorded too (unless a resumed recording has no columns for them).
+    pub fn create(path: &Path, resume: bool, events: bool) -> Result<Self> {
+        let existing = if resume && path.exists() {
+            fs::read_to_string(path)?
+        } else {
+            String::new()
+        };
+        let mut records = parse(&existing).into_iter();
+        let Some(columns) = records.next() else {
+            return Ok(Self {
+                path: path.to_path_buf(),
+                writer: BufWriter::new(File::create(path)?),
+                columns: None,
+                events,
+                pending: Vec::new(),
+                resume_after: None,
+            });
+        };
+        let block_column = columns.iter().position(|c| c == "block_number");
+        let resume_after = records
+            .filter_map(|record| record.get(block_column?)?.parse::<u64>().ok())
+            .max();
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(OpenOptions::new().append(true).open(path)?),
+            events: events
+                && EVENT_COLUMNS
+                    .iter()
+                    .all(|c| columns.iter().any(|col| col == c)),
+            columns: Some(columns),
+            pending: Vec::new(),
+            resume_after,
+        })
+    }
+
+    /// Write the row of an event.
+    fn write_row(&mut self, entry: &Entry) -> Result<()> {
+        let Some(columns) = &self.columns else {
+            return Ok(());
+        };
+        let row = columns
+            .iter()
+            .map(|column| match column.as_str() {
+                "timestamp" => escape(&entry.time),
+                "event_severity" => entry.severity.to_string().into(),
+                "event_tag" => escape(entry.tag),
+                "event_text" => escape(&entry.text),
+                _ => "".into(),
+            })
+            .collect::<Vec<_>>();
+        writeln!(self.writer, "{}", row.join(","))?;
+        Ok(())
+    }
+
+    /// Append the columns of new fields, rewriting the recording with them left empty in the
+    /// earlier rows.
+    fn widen(&mut self, added: Vec<String>) -> Result<()> {
+        self.writer.flush()?;
+        let existing = fs::read_to_string(&self.path)?;
+        let columns = self.columns.get_or_insert_with(Vec::new);
+        columns.extend(added);
+        // The recording is replaced only once it was rewritten completely.
+        let mut rewritten = self.path.clone().into_os_string();
+        rewritten.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&rewritten)?);
+        writeln!(writer, "{}", columns.join(","))?;
+        for record in parse(&existing).into_iter().skip(1) {
+            let mut row = record.iter().map(|field| escape(field)).collect::<Vec<_>>();
+            row.resize(columns.len(), "".into());
+            writeln!(writer, "{}", row.join(","))?;
+        }
+        writer.flush()?;
+        fs::rename(&rewritten, &self.path)?;
+        self.writer = BufWriter::new(OpenOptions::new().append(true).open(&self.path)?);
+        Ok(())
+    }
+}
+
+impl Sink for CsvSink {
+    fn write(&mut self, snapshot: &Snapshot) -> Result<()> {
+        if self
+            .resume_after
+            .is_some_and(|last| snapshot.block_number <= last)
+        {
+            return Ok(());
+        }
+        let fields = flatten(snapshot)?;
+        match &self.columns {
+            Some(columns) => {
+                let added = fields
+                    .iter()
+                    .map(|(name, _)| name)
+                    .filter(|name| !columns.contains(name))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !added.is_empty() {
+                    self.widen(added)?;
+                }
+            }
+            None => {
+                let mut columns = fields
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect::<Vec<_>>();
+                if self.events {
+                    columns.extend(EVENT_COLUMNS.map(String::from));
+                }
+                writeln!(self.writer, "{}", columns.join(","))?;
+                self.columns = Some(columns);
+                for entry in std::mem::take(&mut self.pending) {
+                    self.write_row(&entry)?;
+                }
+            }
+        }
+        let Some(columns) = &self.columns else {
+            return Ok(());
+        };
+        let row = columns
+            .iter()
+            .map(|column| {
+                match fields
+                    .iter()
+                    .find(|(name, _)| name == column)
+                    .map(|(_, v)| v)
+                {
+                    Some(Value::String(s)) => escape(s).into_owned(),
+                    Some(Value::Null) | None => String::new(),
+                    Some(value) => escape(&value.to_string()).into_owned(),
+                }
+            })
+            .collect::<Vec<_>>();
+        writeln!(self.writer, "{}", row.join(","))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_event(&mut self, entry: &Entry) -> Result<()> {
+        if !self.events {
+            return Ok(());
+        }
+        if self.columns.is_none() {
+            self.pending.push(entry.clone());
+            return Ok(());
+        }
+        self.write_row(entry)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Quote a field containing a separator, quote or line break.
+fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// Split a recording into its records of fields, unquoting the fields quoted by [`escape`].
+fn parse(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    // Blank lines hold no record.
+    records.retain(|record| record.len() > 1 || record.first().is_some_and(|f| !f.is_empty()));
+    records
+}
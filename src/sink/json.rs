@@ -0,0 +1,78 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use eyre::Result;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::Sink;
+use crate::{journal::Entry, measurement::Snapshot};
+
+/// Writes one JSON object per snapshot and line.
+///
+/// Events are written as objects with `"record": "event"` in between, so they lack a
+/// `block_number`.
+pub struct JsonSink {
+    writer: BufWriter<File>,
+    /// The last block of the recording being resumed; snapshots up to it are skipped.
+    resume_after: Option<u64>,
+}
+
+impl JsonSink {
+    /// Create the recording, or append to it if `resume` is set.
+    pub fn create(path: &Path, resume: bool) -> Result<Self> {
+        if !resume || !path.exists() {
+            return Ok(Self {
+                writer: BufWriter::new(File::create(path)?),
+                resume_after: None,
+            });
+        }
+        let resume_after = fs::read_to_string(path)?
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter_map(|snapshot| snapshot.get("block_number")?.as_u64())
+            .max();
+        Ok(Self {
+            writer: BufWriter::new(OpenOptions::new().append(true).open(path)?),
+            resume_after,
+        })
+    }
+}
+
+impl Sink for JsonSink {
+    fn write(&mut self, snapshot: &Snapshot) -> Result<()> {
+        if self
+            .resume_after
+            .is_some_and(|last| snapshot.block_number <= last)
+        {
+            return Ok(());
+        }
+        serde_json::to_writer(&mut self.writer, snapshot)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write_event(&mut self, entry: &Entry) -> Result<()> {
+        #[derive(Serialize)]
+        struct Event<'a> {
+            record: &'static str,
+            #[serde(flatten)]
+            entry: &'a Entry,
+        }
+
+        serde_json::to_writer(
+            &mut self.writer,
+            &Event {
+                record: "event",
+                entry,
+            },
+        )?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
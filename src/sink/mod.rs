@@ -0,0 +1,749 @@
+use std::{
+    fmt, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use chrono::{Local, NaiveDateTime};
+use eyre::{eyre, Result};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    journal::{Entry, Journal, Severity},
+    measurement::Snapshot,
+};
+
+mod clickhouse;
+mod csv;
+mod json;
+mod mqtt;
+mod prometheus;
+mod stdout;
+
+pub use self::{
+    clickhouse::ClickHouseSink,
+    csv::CsvSink,
+    json::JsonSink,
+    mqtt::MqttSink,
+    prometheus::{HistogramBuckets, PrometheusSink},
+    stdout::StdoutSink,
+};
+
+/// The timestamp suffix of rotated recordings, followed by `-<n>` from the second rotation
+/// within the same second on.
+const ROTATED_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// How long after it was queued a snapshot may be written before it counts as delayed.
+const DELAYED_AFTER: Duration = Duration::from_secs(1);
+
+/// How the sinks are opened.
+#[derive(Debug, Clone)]
+pub struct SinkOptions {
+    /// Overwrite the previous stdout line instead of printing a new one.
+    pub refresh: bool,
+    /// Print the run-wide totals on a second stdout line.
+    pub totals: bool,
+    /// Chart the TPS of this many recent windows below the stdout line.
+    pub chart: Option<usize>,
+    /// Print the change of the metrics versus the previous window on the stdout line.
+    pub diff: bool,
+    /// The events rendered below the refreshed stdout line.
+    pub journal: Journal,
+    /// Interleave the logged events with the snapshots of the JSON and CSV recordings.
+    pub events: bool,
+    /// Append to existing recordings instead of truncating them, skipping the blocks they already
+    /// contain.
+    pub resume: bool,
+    /// The bucket boundaries of the Prometheus histograms.
+    pub buckets: HistogramBuckets,
+    /// The number of snapshots queued for a sink before further ones are dropped.
+    pub queue: usize,
+}
+
+/// An output receiving every window snapshot.
+pub trait Sink: Send {
+    /// Write a snapshot.
+    fn write(&mut self, snapshot: &Snapshot) -> Result<()>;
+
+    /// Write an event logged since the previous snapshot; only recordings keep events.
+    fn write_event(&mut self, _entry: &Entry) -> Result<()> {
+        Ok(())
+    }
+
+    /// Redraw the output of the last snapshot in place, e.g. while no new block arrives; only
+    /// the refreshed stdout panel does.
+    fn repaint(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// An output specification given on the command line as `<kind>[:<target>]`.
+#[derive(Debug, Clone)]
+pub enum SinkSpec {
+    /// `stdout`: the human-readable metrics line.
+    Stdout,
+    /// `json:<path>`: one JSON object per line.
+    Json(PathBuf),
+    /// `csv:<path>`: one row per snapshot.
+    Csv(PathBuf),
+    /// `prometheus:<addr>`: gauges and per-block histograms served at `/metrics` and the snapshot
+    /// at `/snapshot`.
+    Prometheus(SocketAddr),
+    /// `clickhouse:<url>`: rows inserted in batches over the ClickHouse HTTP interface.
+    ClickHouse(String),
+    /// `mqtt:<host>:<port>/<topic>`: a compact retained message per snapshot published to an
+    /// MQTT broker.
+    Mqtt { broker: String, topic: String },
+}
+
+impl FromStr for SinkSpec {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, target) = s.split_once(':').unwrap_or((s, ""));
+        match (kind, target) {
+            ("stdout", "") => Ok(Self::Stdout),
+            ("json", path) if !path.is_empty() => Ok(Self::Json(path.into())),
+            ("csv", path) if !path.is_empty() => Ok(Self::Csv(path.into())),
+            ("prometheus", addr) if !addr.is_empty() => Ok(Self::Prometheus(addr.parse()?)),
+            ("clickhouse", url) if !url.is_empty() => Ok(Self::ClickHouse(url.to_string())),
+            ("mqtt", target) => match target.split_once('/') {
+                Some((broker, topic)) if !broker.is_empty() && !topic.is_empty() => {
+                    Ok(Self::Mqtt {
+                        broker: broker.to_string(),
+                        topic: topic.to_string(),
+                    })
+                }
+                _ => Err(eyre!(
+                    "invalid sink `{s}`, expected mqtt:<host>:<port>/<topic>"
+                )),
+            },
+            _ => Err(eyre!(
+                "invalid sink `{s}`, expected stdout, json:<path>, csv:<path>, prometheus:<addr>, \
+                 clickhouse:<url> or mqtt:<host>:<port>/<topic>"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SinkSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "stdout"),
+            Self::Json(path) => write!(f, "json:{}", path.display()),
+            Self::Csv(path) => write!(f, "csv:{}", path.display()),
+            Self::Prometheus(addr) => write!(f, "prometheus:{addr}"),
+            Self::ClickHouse(url) => write!(f, "clickhouse:{url}"),
+            Self::Mqtt { broker, topic } => write!(f, "mqtt:{broker}/{topic}"),
+        }
+    }
+}
+
+impl SinkSpec {
+    /// Get the file the sink writes to, if any.
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            Self::Json(path) | Self::Csv(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Open the sink described by the specification.
+    pub async fn open(&self, options: &SinkOptions) -> Result<Box<dyn Sink>> {
+        Ok(match self {
+            Self::Stdout => {
+                let mut sink =
+                    StdoutSink::new(options.refresh, options.totals, options.journal.clone());
+                if let Some(windows) = options.chart {
+                    sink = sink.with_chart(windows);
+                }
+                if options.diff {
+                    sink = sink.with_diff();
+                }
+                Box::new(sink)
+            }
+            Self::Json(path) => Box::new(JsonSink::create(path, options.resume)?),
+            Self::Csv(path) => Box::new(CsvSink::create(path, options.resume, options.events)?),
+            Self::Prometheus(addr) => {
+                Box::new(PrometheusSink::bind(*addr, options.buckets.clone()).await?)
+            }
+            Self::ClickHouse(url) => {
+                Box::new(ClickHouseSink::connect(url, options.journal.clone()).await?)
+            }
+            Self::Mqtt { broker, topic } => {
+                Box::new(MqttSink::connect(broker, topic, options.journal.clone()).await?)
+            }
+        })
+    }
+}
+
+/// When the JSON and CSV recordings are rotated automatically, given as an age (`30m`, `1h`,
+/// `1d`) or a size (`500MB`, `2GB`).
+#[derive(Debug, Clone, Copy)]
+pub enum Rotation {
+    Every(Duration),
+    Size(u64),
+}
+
+impl FromStr for Rotation {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || eyre!("invalid rotation `{s}`, expected e.g. 30m, 1h, 1d or 500MB, 2GB");
+        let split = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let (value, unit) = s.split_at(split);
+        let value = value.parse::<f64>().map_err(|_| invalid())?;
+        // An age too long for a duration is rejected rather than panicking.
+        let every = |seconds: f64| {
+            Duration::try_from_secs_f64(value * seconds)
+                .map(Self::Every)
+                .map_err(|_| invalid())
+        };
+        let rotation = match unit.trim() {
+            "s" => every(1.0)?,
+            "m" => every(60.0)?,
+            "h" => every(3600.0)?,
+            "d" => every(86400.0)?,
+            "B" => Self::Size(value as u64),
+            "kB" | "KB" => Self::Size((value * 1e3) as u64),
+            "MB" => Self::Size((value * 1e6) as u64),
+            "GB" => Self::Size((value * 1e9) as u64),
+            _ => return Err(invalid()),
+        };
+        match rotation {
+            Self::Every(age) if age.is_zero() => Err(invalid()),
+            Self::Size(0) => Err(invalid()),
+            rotation => Ok(rotation),
+        }
+    }
+}
+
+/// Fans every snapshot out to all configured sinks.
+///
+/// Every sink writes on a thread of its own behind a bounded queue, so a slow sink drops snapshots
+/// instead of stalling the recording of the blocks.
+pub struct Sinks {
+    sinks: Vec<Opened>,
+    options: SinkOptions,
+    /// When the recordings are rotated automatically, if at all.
+    rotation: Option<Rotation>,
+    /// The number of rotated files kept per recording, if limited.
+    retain: Option<usize>,
+    rotated_at: Instant,
+}
+
+/// An open sink, the queue of its delivery thread and whether it currently receives the
+/// snapshots.
+struct Opened {
+    spec: SinkSpec,
+    jobs: mpsc::Sender<Job>,
+    delivery: Arc<Mutex<SinkDelivery>>,
+    thread: JoinHandle<()>,
+    enabled: bool,
+    /// The events of the snapshots dropped for a full queue, written before the next snapshot.
+    pending: Vec<Entry>,
+}
+
+/// Work for the delivery thread of a sink.
+enum Job {
+    /// Write the events logged before a snapshot and then the snapshot.
+    Write {
+        events: Arc<[Entry]>,
+        snapshot: Arc<Snapshot>,
+        queued: Instant,
+    },
+    /// Acknowledge once the jobs queued before are done.
+    Sync(oneshot::Sender<()>),
+    /// Continue with another sink, e.g. a rotated recording.
+    Replace(Box<dyn Sink>),
+    /// Redraw the output of the last snapshot.
+    Repaint,
+}
+
+/// Whether a sink currently receives the snapshots.
+#[derive(Debug, Clone, Serialize)]
+pub struct SinkStatus {
+    pub sink: String,
+    pub enabled: bool,
+}
+
+/// How the snapshots were delivered to a sink over the run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SinkDelivery {
+    pub sink: String,
+    pub delivered: u64,
+    /// Snapshots dropped because the queue of the sink was full.
+    pub dropped: u64,
+    /// Snapshots written more than a second after they were queued.
+    pub delayed: u64,
+    /// Snapshots the sink failed to write.
+    pub failed: u64,
+    /// Snapshots waiting in the queue.
+    pub queued: usize,
+}
+
+impl Opened {
+    /// Start the delivery thread of an open sink.
+    fn start(spec: &SinkSpec, sink: Box<dyn Sink>, queue: usize, journal: Journal) -> Self {
+        let (jobs, receiver) = mpsc::channel(queue.max(1));
+        let delivery = Arc::new(Mutex::new(SinkDelivery {
+            sink: spec.to_string(),
+            ..SinkDelivery::default()
+        }));
+        // A thread of its own leaves the sink free to block on files and terminals for as long
+        // as it takes, without holding up the runtime's blocking pool; it still reaches the
+        // runtime, e.g. for the journal's Grafana annotations.
+        let runtime = tokio::runtime::Handle::current();
+        let thread = thread::spawn({
+            let delivery = delivery.clone();
+            move || {
+                let _runtime = runtime.enter();
+                deliver(sink, receiver, &delivery, &journal)
+            }
+        });
+        Self {
+            spec: spec.clone(),
+            jobs,
+            delivery,
+            thread,
+            enabled: true,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Wait for the delivery thread to finish the jobs queued so far.
+    async fn sync(&self) -> Result<()> {
+        let stopped = || eyre!("the delivery to {} stopped", self.spec);
+        let (done, synced) = oneshot::channel();
+        self.jobs
+            .send(Job::Sync(done))
+            .await
+            .map_err(|_| stopped())?;
+        synced.await.map_err(|_| stopped())
+    }
+}
+
+/// Run the jobs of a sink until its queue is closed.
+fn deliver(
+    mut sink: Box<dyn Sink>,
+    mut jobs: mpsc::Receiver<Job>,
+    delivery: &Mutex<SinkDelivery>,
+    journal: &Journal,
+) {
+    while let Some(job) = jobs.blocking_recv() {
+        match job {
+            Job::Write {
+                events,
+                snapshot,
+                queued,
+            } => {
+                let result = events
+                    .iter()
+                    .try_for_each(|entry| sink.write_event(entry))
+                    .and_then(|()| sink.write(&snapshot));
+                let mut delivery = delivery.lock().unwrap();
+                match result {
+                    Ok(()) => delivery.delivered += 1,
+                    Err(err) => {
+                        delivery.failed += 1;
+                        journal.log(
+                            Severity::Warn,
+                            "sink",
+                            format!("Failed to write snapshot to {}: {err}", delivery.sink),
+                        );
+                    }
+                }
+                if queued.elapsed() > DELAYED_AFTER {
+                    delivery.delayed += 1;
+                }
+            }
+            Job::Sync(done) => {
+                let _ = done.send(());
+            }
+            Job::Replace(next) => sink = next,
+            Job::Repaint => {
+                if let Err(err) = sink.repaint() {
+                    journal.log(
+                        Severity::Warn,
+                        "sink",
+                        format!("Failed to repaint {}: {err}", delivery.lock().unwrap().sink),
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Sinks {
+    /// Open all sinks of the given specifications.
+    pub async fn open(specs: &[SinkSpec], options: &SinkOptions) -> Result<Self> {
+        let mut sinks = Vec::with_capacity(specs.len());
+        for spec in specs {
+            sinks.push(Opened::start(
+                spec,
+                spec.open(options).await?,
+                options.queue,
+                options.journal.clone(),
+            ));
+        }
+        Ok(Self {
+            sinks,
+            options: options.clone(),
+            rotation: None,
+            retain: None,
+            rotated_at: Instant::now(),
+        })
+    }
+
+    /// Rotate the recordings automatically.
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    /// Delete all but the `retain` newest rotated files of every recording after a rotation.
+    pub fn with_retention(mut self, retain: usize) -> Self {
+        self.retain = Some(retain);
+        self
+    }
+
+    /// Queue the events logged since the previous snapshot and then the snapshot for every
+    /// enabled sink, dropping the snapshot for the sinks whose queue is full and keeping their
+    /// events for the next one.
+    pub fn write(&mut self, snapshot: &Snapshot) {
+        let events: Arc<[Entry]> = self.options.journal.take_unrecorded().into();
+        let snapshot = Arc::new(snapshot.clone());
+        let queued = Instant::now();
+        for opened in self.sinks.iter_mut().filter(|opened| opened.enabled) {
+            let events = if opened.pending.is_empty() {
+                events.clone()
+            } else {
+                std::mem::take(&mut opened.pending)
+                    .into_iter()
+                    .chain(events.iter().cloned())
+                    .collect()
+            };
+            let job = Job::Write {
+                events,
+                snapshot: snapshot.clone(),
+                queued,
+            };
+            if let Err(err) = opened.jobs.try_send(job) {
+                opened.delivery.lock().unwrap().dropped += 1;
+                if let Job::Write { events, .. } = err.into_inner() {
+                    opened.pending = events.to_vec();
+                }
+            }
+        }
+    }
+
+    /// Ask every enabled sink to redraw its last output, skipping the sinks which are busy anyway.
+    pub fn repaint(&self) {
+        for opened in self.sinks.iter().filter(|opened| opened.enabled) {
+            let _ = opened.jobs.try_send(Job::Repaint);
+        }
+    }
+
+    /// Enable or disable the sink at `index`, in the order the sinks were given.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> Result<()> {
+        let opened = self
+            .sinks
+            .get_mut(index)
+            .ok_or_else(|| eyre!("no sink {index}"))?;
+        opened.enabled = enabled;
+        Ok(())
+    }
+
+    /// Move the files of the JSON and CSV sinks aside with a timestamp suffix and start new
+    /// ones, returning the moved files.
+    pub async fn rotate(&mut self) -> Result<Vec<PathBuf>> {
+        let suffix = free_suffix(
+            &self.paths(),
+            &Local::now().format(ROTATED_FORMAT).to_string(),
+        );
+        self.rotated_at = Instant::now();
+        // A rotated recording starts afresh rather than resuming the moved one.
+        let options = SinkOptions {
+            resume: false,
+            ..self.options.clone()
+        };
+        let mut rotated = Vec::new();
+        for opened in &mut self.sinks {
+            let Some(path) = opened.spec.path() else {
+                continue;
+            };
+            // The moved file is complete once the snapshots queued before are written.
+            opened.sync().await?;
+            let moved = rotated_path(path, &suffix);
+            fs::rename(path, &moved)?;
+            let sink = opened.spec.open(&options).await?;
+            opened
+                .jobs
+                .send(Job::Replace(sink))
+                .await
+                .map_err(|_| eyre!("the delivery to {} stopped", opened.spec))?;
+            if let Some(retain) = self.retain {
+                prune(path, retain)?;
+            }
+            rotated.push(moved);
+        }
+        Ok(rotated)
+    }
+
+    /// Rotate the recordings if they reached the age or size of the automatic rotation,
+    /// returning the moved files.
+    pub async fn rotate_if_due(&mut self) -> Result<Vec<PathBuf>> {
+        let due = match self.rotation {
+            None => false,
+            Some(Rotation::Every(age)) => self.rotated_at.elapsed() >= age,
+            Some(Rotation::Size(size)) => self
+                .paths()
+                .iter()
+                .any(|path| fs::metadata(path).is_ok_and(|metadata| metadata.len() >= size)),
+        };
+        if !due {
+            return Ok(Vec::new());
+        }
+        self.rotate().await
+    }
+
+    /// Let every sink write its queued snapshots and close it.
+    pub async fn close(self) {
+        for opened in self.sinks {
+            drop(opened.jobs);
+            let thread = opened.thread;
+            let joined = tokio::task::spawn_blocking(move || thread.join()).await;
+            if !matches!(joined, Ok(Ok(()))) {
+                self.options.journal.log(
+                    Severity::Warn,
+                    "sink",
+                    format!("Failed to close {}: its delivery panicked", opened.spec),
+                );
+            }
+        }
+    }
+
+    /// Get the files written by the JSON and CSV sinks.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.sinks
+            .iter()
+            .filter_map(|opened| opened.spec.path().cloned())
+            .collect()
+    }
+
+    /// Get the sinks and whether they are enabled, in the order they were given.
+    pub fn status(&self) -> Vec<SinkStatus> {
+        self.sinks
+            .iter()
+            .map(|opened| SinkStatus {
+                sink: opened.spec.to_string(),
+                enabled: opened.enabled,
+            })
+            .collect()
+    }
+
+    /// Get how the snapshots were delivered to every sink, unknown without sinks.
+    pub fn delivery(&self) -> Option<Vec<SinkDelivery>> {
+        if self.sinks.is_empty() {
+            return None;
+        }
+        let delivery = self
+            .sinks
+            .iter()
+            .map(|opened| SinkDelivery {
+                queued: opened.jobs.max_capacity() - opened.jobs.capacity(),
+                ..opened.delivery.lock().unwrap().clone()
+            })
+            .collect();
+        Some(delivery)
+    }
+}
+
+/// The path the recording at `path` is moved to when rotated with `suffix`.
+fn rotated_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut moved = path.as_os_str().to_owned();
+    moved.push(format!(".{suffix}"));
+    moved.into()
+}
+
+/// Pick the suffix for rotating the recordings at `paths` at the formatted time `stamp`, counting
+/// up if an earlier rotation within the same second took it.
+fn free_suffix(paths: &[PathBuf], stamp: &str) -> String {
+    (1..)
+        .map(|n| match n {
+            1 => stamp.to_string(),
+            n => format!("{stamp}-{n}"),
+        })
+        .find(|suffix| {
+            paths
+                .iter()
+                .all(|path| !rotated_path(path, suffix).exists())
+        })
+        .unwrap()
+}
+
+/// Get the time and the count within its second of a rotated file's suffix, in the order the
+/// files were rotated, or nothing if it is not such a suffix.
+fn rotated_order(suffix: &str) -> Option<(NaiveDateTime, u32)> {
+    if let Ok(time) = NaiveDateTime::parse_from_str(suffix, ROTATED_FORMAT) {
+        return Some((time, 1));
+    }
+    let (stamp, n) = suffix.rsplit_once('-')?;
+    let time = NaiveDateTime::parse_from_str(stamp, ROTATED_FORMAT).ok()?;
+    Some((time, n.parse().ok().filter(|&n| n > 1)?))
+}
+
+/// Delete all but the `retain` newest rotated files of the recording at `path`.
+fn prune(path: &Path, retain: usize) -> Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return Ok(());
+    };
+    let mut rotated = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let rotated = entry.ok()?.path();
+            let suffix = rotated
+                .file_name()?
+                .to_str()?
+                .strip_prefix(name)?
+                .strip_prefix('.')?;
+            Some((rotated_order(suffix)?, rotated))
+        })
+        .collect::<Vec<_>>();
+    rotated.sort();
+    let excess = rotated.len().saturating_sub(retain);
+    for (_, old) in &rotated[..excess] {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Get the file extension of a recording, seeing through the timestamp suffix of rotated ones.
+pub fn recording_extension(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let name = match name.rsplit_once('.') {
+        Some((stem, suffix)) if rotated_order(suffix).is_some() => stem,
+        _ => name,
+    };
+    Path::new(name).extension()?.to_str()
+}
+
+/// Flatten a snapshot into `(name, value)` pairs, joining nested field names with `_`.
+pub fn flatten(snapshot: &Snapshot) -> Result<Vec<(String, Value)>> {
+    fn visit(prefix: &str, value: Value, out: &mut Vec<(String, Value)>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let name = if prefix.is_empty() {
+                        key
+                    } else {
+                        format!("{prefix}_{key}")
+                    };
+                    visit(&name, value, out);
+                }
+            }
+            value => out.push((prefix.to_string(), value)),
+        }
+    }
+
+    let mut out = Vec::new();
+    visit("", serde_json::to_value(snapshot)?, &mut out);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rotation_ages_and_sizes() {
+        for (s, seconds) in [("90s", 90), ("30m", 1800), ("1.5h", 5400), ("1d", 86400)] {
+            assert!(
+                matches!(s.parse(), Ok(Rotation::Every(age)) if age == Duration::from_secs(seconds)),
+                "`{s}`"
+            );
+        }
+        for (s, bytes) in [
+            ("512B", 512),
+            ("2kB", 2000),
+            ("2KB", 2000),
+            ("500MB", 500_000_000),
+            ("2GB", 2_000_000_000),
+        ] {
+            assert!(
+                matches!(s.parse(), Ok(Rotation::Size(size)) if size == bytes),
+                "`{s}`"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_zero_unknown_and_overflowing_rotations() {
+        for s in [
+            "0s",
+            "0d",
+            "0MB",
+            "0.1B",
+            "",
+            "30",
+            "m",
+            "30y",
+            "-1h",
+            "99999999999999999999d",
+        ] {
+            assert!(s.parse::<Rotation>().is_err(), "accepted `{s}`");
+        }
+    }
+
+    #[test]
+    fn counts_up_rotations_within_the_same_second() {
+        let dir = std::env::temp_dir().join(format!("telescope-rotate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths = [dir.join("run.json"), dir.join("run.csv")];
+        let stamp = "20261014-081455";
+        assert_eq!(free_suffix(&paths, stamp), stamp);
+        fs::write(rotated_path(&paths[0], stamp), "").unwrap();
+        assert_eq!(free_suffix(&paths, stamp), "20261014-081455-2");
+        fs::write(rotated_path(&paths[1], "20261014-081455-2"), "").unwrap();
+        assert_eq!(free_suffix(&paths, stamp), "20261014-081455-3");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn orders_rotated_suffixes_by_time_and_count() {
+        let mut suffixes = [
+            "20261014-081455-10",
+            "20261014-081456",
+            "20261014-081455-2",
+            "20261014-081455",
+        ];
+        suffixes.sort_by_key(|suffix| rotated_order(suffix));
+        assert_eq!(
+            suffixes,
+            [
+                "20261014-081455",
+                "20261014-081455-2",
+                "20261014-081455-10",
+                "20261014-081456",
+            ]
+        );
+        for suffix in ["20261014-081455-1", "20261014-081455-x", "20261014", "csv"] {
+            assert_eq!(rotated_order(suffix), None, "`{suffix}`");
+        }
+        assert_eq!(
+            recording_extension(Path::new("run.csv.20261014-081455-2")),
+            Some("csv")
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use eyre::{eyre, Result};
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc,
+};
+
+use super::Sink;
+use crate::{
+    journal::{Journal, Severity},
+    measurement::Snapshot,
+};
+
+/// The keep-alive interval announced to the broker, pinged at half of it when idle.
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// The delay before reconnecting to a broker which dropped the connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Publishes a compact JSON message per snapshot to an MQTT broker (MQTT 3.1.1, QoS 0), so
+/// cheap display devices can subscribe to the live metrics.
+///
+/// The messages are retained, so a device that connects shows the latest metrics right away.
+pub struct MqttSink {
+    messages: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+impl MqttSink {
+    /// Connect to the broker at `addr` and start the publishing task.
+    pub async fn connect(addr: &str, topic: &str, journal: Journal) -> Result<Self> {
+        let client_id = format!("telescope-{}", std::process::id());
+        let mut stream = open(addr, &client_id).await?;
+
+        let (messages, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+        let (addr, topic) = (addr.to_string(), topic.to_string());
+        tokio::spawn(async move {
+            let mut ping = tokio::time::interval(KEEP_ALIVE / 2);
+            let mut buf = [0; 256];
+            loop {
+                let sent = tokio::select! {
+                    message = rx.recv() => match message {
+                        Some(message) => stream.write_all(&publish(&topic, &message)).await,
+                        None => break,
+                    },
+                    _ = ping.tick() => stream.write_all(&[0xc0, 0x00]).await,
+                    // Discard the ping responses, noticing when the broker hangs up.
+                    read = stream.read(&mut buf) => match read {
+                        Ok(0) => Err(std::io::ErrorKind::UnexpectedEof.into()),
+                        Ok(_) => Ok(()),
+                        Err(err) => Err(err),
+                    },
+                };
+                if let Err(err) = sent {
+                    journal.log(
+                        Severity::Warn,
+                        "mqtt",
+                        format!("Lost the connection to the MQTT broker {addr}: {err}"),
+                    );
+                    // Only the first failed attempt is logged, the broker may stay away long.
+                    let mut attempts = 0;
+                    loop {
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                        attempts += 1;
+                        match open(&addr, &client_id).await {
+                            Ok(reconnected) => break stream = reconnected,
+                            Err(err) if attempts == 1 => journal.log(
+                                Severity::Warn,
+                                "mqtt",
+                                format!("Failed to reconnect to {addr}: {err}"),
+                            ),
+                            Err(_) => {}
+                        }
+                    }
+                    journal.log(
+                        Severity::Info,
+                        "mqtt",
+                        format!("Reconnected to the MQTT broker {addr}"),
+                    );
+                    // Drop the messages queued meanwhile, only the latest one matters.
+                    while rx.try_recv().is_ok() {}
+                }
+            }
+            let _ = stream.write_all(&[0xe0, 0x00]).await;
+        });
+        Ok(Self { messages })
+    }
+}
+
+impl Sink for MqttSink {
+    fn write(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let m = &snapshot.metrics;
+        let message = json!({
+            "block": snapshot.block_number,
+            "tps": m.transactions_per_second,
+            "gas_per_second": m.gas_per_second,
+            "mini_block_interval_ms": m.mini_block_interval_ms,
+            "block_interval_ms": snapshot.block_interval_ms,
+        });
+        self.messages.send(serde_json::to_vec(&message)?)?;
+        Ok(())
+    }
+}
+
+/// Connect to the broker and wait for it to accept the session.
+async fn open(addr: &str, client_id: &str) -> Result<TcpStream> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut connect = Vec::new();
+    connect.extend(string("MQTT"));
+    // Protocol level 4 (3.1.1), clean session.
+    connect.extend([0x04, 0x02]);
+    connect.extend((KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+    connect.extend(string(client_id));
+    stream.write_all(&packet(0x10, &connect)).await?;
+
+    let mut connack = [0; 4];
+    stream.read_exact(&mut connack).await?;
+    match connack {
+        [0x20, 0x02, _, 0x00] => Ok(stream),
+        [0x20, 0x02, _, code] => Err(eyre!("MQTT broker {addr} refused the connection ({code})")),
+        _ => Err(eyre!("unexpected response from MQTT broker {addr}")),
+    }
+}
+
+/// Build a retained QoS 0 `PUBLISH` packet.
+fn publish(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = string(topic);
+    body.extend(payload);
+    packet(0x31, &body)
+}
+
+/// Prefix a packet body with its fixed header.
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    // The remaining length is encoded 7 bits at a time, lowest first.
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            packet.push(byte);
+            break;
+        }
+        packet.push(byte | 0x80);
+    }
+    packet.extend(body);
+    packet
+}
+
+/// Encode a length-prefixed UTF-8 string.
+fn string(s: &str) -> Vec<u8> {
+    let mut out = (s.len() as u16).to_be_bytes().to_vec();
+    out.extend(s.as_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fixed header of a packet with a body of `len` bytes.
+    fn header(len: usize) -> Vec<u8> {
+        let packet = packet(0x30, &vec![0; len]);
+        packet[..packet.len() - len].to_vec()
+    }
+
+    #[test]
+    fn encodes_the_remaining_length_seven_bits_at_a_time() {
+        assert_eq!(header(0), [0x30, 0x00]);
+        assert_eq!(header(127), [0x30, 0x7f]);
+        assert_eq!(header(128), [0x30, 0x80, 0x01]);
+        assert_eq!(header(16383), [0x30, 0xff, 0x7f]);
+        assert_eq!(header(16384), [0x30, 0x80, 0x80, 0x01]);
+    }
+
+    #[test]
+    fn publishes_retained_messages() {
+        assert_eq!(string("ab"), [0x00, 0x02, b'a', b'b']);
+        // Type 3 (`PUBLISH`) in the high nibble, QoS 0 and the retain flag in the low one.
+        assert_eq!(
+            publish("t/x", b"{}"),
+            [0x31, 0x07, 0x00, 0x03, b't', b'/', b'x', b'{', b'}']
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    fmt::Write,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use eyre::Result;
+use serde_json::Value;
+
+use super::{flatten, Sink};
+use crate::{
+    http::{self, Response},
+    measurement::Snapshot,
+};
+
+/// Serves the numeric fields of the latest snapshot as Prometheus gauges and the per-block values
+/// as histograms at `/metrics`, and the complete snapshot as JSON at `/snapshot`.
+pub struct PrometheusSink {
+    exposition: Arc<Mutex<Exposition>>,
+    transactions: Histogram,
+    gas: Histogram,
+    interval: Histogram,
+}
+
+/// The upper bounds of the histogram buckets of the per-block values.
+#[derive(Debug, Clone)]
+pub struct HistogramBuckets {
+    pub transactions: Vec<f64>,
+    pub gas: Vec<f64>,
+    pub interval_ms: Vec<f64>,
+}
+
+/// A cumulative Prometheus histogram.
+struct Histogram {
+    name: &'static str,
+    bounds: Vec<f64>,
+    /// Observations per bucket, not cumulated; the last one counts those above all bounds.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+#[derive(Default)]
+struct Exposition {
+    metrics: String,
+    snapshot: Option<String>,
+}
+
+impl PrometheusSink {
+    /// Bind the scrape endpoint to the given address.
+    pub async fn bind(addr: SocketAddr, buckets: HistogramBuckets) -> Result<Self> {
+        let exposition = Arc::new(Mutex::new(Exposition::default()));
+        let served = exposition.clone();
+        http::serve(
+            addr,
+            Arc::new(move |request| {
+                let served = served.lock().unwrap();
+                match request.path.as_str() {
+                    "/metrics" => Response::ok("text/plain; version=0.0.4", served.metrics.clone()),
+                    "/snapshot" => Response::ok(
+                        "application/json",
+                        served
+                            .snapshot
+                            .clone()
+                            .unwrap_or_else(|| "null".to_string()),
+                    ),
+                    _ => Response::not_found(),
+                }
+            }),
+        )
+        .await?;
+        Ok(Self {
+            exposition,
+            transactions: Histogram::new("block_transactions", buckets.transactions),
+            gas: Histogram::new("block_gas_used", buckets.gas),
+            interval: Histogram::new("block_interval_ms", buckets.interval_ms),
+        })
+    }
+}
+
+impl Histogram {
+    fn new(name: &'static str, mut bounds: Vec<f64>) -> Self {
+        bounds.sort_by(f64::total_cmp);
+        Self {
+            name,
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            sum: 0.0,
+        }
+    }
+
+    #[inline]
+    fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    fn write(&self, out: &mut String) -> std::fmt::Result {
+        let name = self.name;
+        writeln!(out, "# TYPE telescope_{name}_histogram histogram")?;
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            writeln!(
+                out,
+                "telescope_{name}_histogram_bucket{{le=\"{bound}\"}} {cumulative}"
+            )?;
+        }
+        cumulative += self.counts[self.bounds.len()];
+        writeln!(
+            out,
+            "telescope_{name}_histogram_bucket{{le=\"+Inf\"}} {cumulative}"
+        )?;
+        writeln!(out, "telescope_{name}_histogram_sum {}", self.sum)?;
+        writeln!(out, "telescope_{name}_histogram_count {cumulative}")
+    }
+}
+
+impl Sink for PrometheusSink {
+    fn write(&mut self, snapshot: &Snapshot) -> Result<()> {
+        if let Some(transactions) = snapshot.block_transactions {
+            self.transactions.observe(transactions as f64);
+        }
+        self.gas.observe(snapshot.block_gas_used as f64);
+        if let Some(interval) = snapshot.block_interval_ms {
+            self.interval.observe(interval);
+        }
+
+        let mut out = String::new();
+        for (name, value) in flatten(snapshot)? {
+            let value = match value {
+                Value::Number(n) => n.as_f64().unwrap_or_default(),
+                Value::Bool(b) => b as u8 as f64,
+                _ => continue,
+            };
+            // Keys like the RPC method names may hold characters metric names cannot.
+            let name = name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>();
+            writeln!(out, "# TYPE telescope_{name} gauge")?;
+            writeln!(out, "telescope_{name} {value}")?;
+        }
+        for histogram in [&self.transactions, &self.gas, &self.interval] {
+            histogram.write(&mut out)?;
+        }
+        *self.exposition.lock().unwrap() = Exposition {
+            metrics: out,
+            snapshot: Some(serde_json::to_string(snapshot)?),
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    collections::VecDeque,
+    io::{stdout, Write},
+    time::SystemTime,
+};
+
+use chrono::Local;
+use eyre::Result;
+
+use super::Sink;
+use crate::{
+    journal::Journal,
+    measurement::{Metrics, Snapshot},
+    tui::{terminal_size, SPARKS},
+};
+
+/// The number of rows the TPS chart is drawn on.
+const CHART_ROWS: usize = 3;
+
+/// The number of recent events shown in the refreshed panel.
+const EVENT_ROWS: usize = 3;
+
+/// Prints the human-readable metrics line, or repaints a panel of the metrics, the connection
+/// and the latest events in place.
+pub struct StdoutSink {
+    /// Repaint the panel instead of printing a new line.
+    refresh: bool,
+    /// The rows of the panel last painted, to move below it at the end.
+    panel_rows: usize,
+    /// The snapshot of the panel last painted, to repaint it while no block arrives.
+    last: Option<Snapshot>,
+    /// Print the run-wide totals on a second line.
+    totals: bool,
+    /// The events, whose latest are shown in the panel.
+    journal: Journal,
+    /// Charts the TPS of the recent windows below the metrics, if enabled.
+    chart: Option<TpsChart>,
+    /// The recent metrics by block, back to a window ago, to print the change versus the
+    /// previous window instead of the values, if enabled.
+    diff: Option<VecDeque<(u64, Metrics)>>,
+}
+
+/// The TPS of the last windows, sampled whenever a window's worth of new blocks was recorded.
+struct TpsChart {
+    windows: usize,
+    samples: VecDeque<f64>,
+    /// The block the last sample was taken at.
+    sampled_at: Option<u64>,
+}
+
+impl StdoutSink {
+    pub fn new(refresh: bool, totals: bool, journal: Journal) -> Self {
+        Self {
+            refresh,
+            panel_rows: 0,
+            last: None,
+            totals,
+            journal,
+            chart: None,
+            diff: None,
+        }
+    }
+
+    /// Print the change of the metrics versus the previous window.
+    pub fn with_diff(mut self) -> Self {
+        self.diff = Some(VecDeque::new());
+        self
+    }
+
+    /// Format the metrics line: the change versus a window ago if diffed and already known,
+    /// otherwise the values.
+    fn line(&mut self, snapshot: &Snapshot, width: usize) -> String {
+        let metrics = &snapshot.metrics;
+        let Some(history) = &mut self.diff else {
+            return metrics.summary_within(width);
+        };
+        let start = snapshot
+            .block_number
+            .saturating_sub(metrics.window_blocks.max(1) as u64);
+        // Keep the newest metrics at or before the start of the window.
+        while history.get(1).is_some_and(|(block, _)| *block <= start) {
+            history.pop_front();
+        }
+        let line = match history.front() {
+            Some((block, previous)) if *block <= start => metrics.diff_within(previous, width),
+            _ => metrics.summary_within(width),
+        };
+        // A repainted panel formats the same block again.
+        if history
+            .back()
+            .is_none_or(|(block, _)| *block != snapshot.block_number)
+        {
+            history.push_back((snapshot.block_number, metrics.clone()));
+        }
+        line
+    }
+
+    /// Chart the TPS of the last `windows` windows.
+    pub fn with_chart(mut self, windows: usize) -> Self {
+        self.chart = Some(TpsChart {
+            windows: windows.max(1),
+            samples: VecDeque::with_capacity(windows + 1),
+            sampled_at: None,
+        });
+        self
+    }
+
+    /// Paint the panel of the snapshot in place.
+    fn paint(&mut self, snapshot: &Snapshot) {
+        let prefix = format!("[{}] ", Local::now().format("%Y-%m-%d %H:%M:%S%.6f"));
+        // A row wider than the terminal wraps and would shift the panel. Leave a column for the
+        // cursor and clear what the previous panel left on every row.
+        let (width, _) = terminal_size();
+        let summary = self.line(snapshot, width.saturating_sub(prefix.len() + 1));
+        let connection = self.journal.connection();
+        let mut rows = vec![format!("{prefix}{summary}"), status(snapshot, &connection)];
+        if self.totals {
+            rows.push(snapshot.metrics.totals_line());
+        }
+        if let Some(chart) = self
+            .chart
+            .as_ref()
+            .filter(|chart| !chart.samples.is_empty())
+        {
+            rows.extend(chart.rows());
+        }
+        // A fixed number of event rows, so the panel does not grow with the first events.
+        let mut events = self.journal.recent(EVENT_ROWS);
+        events.reverse();
+        rows.extend((0..EVENT_ROWS).map(|_| match events.pop() {
+            Some(entry) => format!("{}{}", entry.color(), entry.line()),
+            None => String::new(),
+        }));
+        print!("\r");
+        for (i, row) in rows.iter().enumerate() {
+            let row = row
+                .char_indices()
+                .nth(width.saturating_sub(1))
+                .map_or(row.as_str(), |(end, _)| &row[..end]);
+            let newline = if i > 0 { "\n" } else { "" };
+            print!("{newline}{row}\x1b[0m\x1b[K");
+        }
+        // Return to the top of the panel to repaint it in place.
+        print!("\x1b[{}A\r", rows.len() - 1);
+        self.panel_rows = rows.len();
+    }
+}
+
+impl Drop for StdoutSink {
+    fn drop(&mut self) {
+        // Leave the panel in place, continuing below it.
+        if self.panel_rows > 0 {
+            println!("\x1b[{}B", self.panel_rows - 1);
+            let _ = stdout().flush();
+        }
+    }
+}
+
+/// Describe the connection: the state of the subscription, how fresh the head is and how the
+/// RPC requests fare.
+fn status(snapshot: &Snapshot, connection: &str) -> String {
+    let metrics = &snapshot.metrics;
+    let mut line = format!(
+        "Connection: {connection}, head {:.0} ms old",
+        metrics.head_age_ms
+    );
+    if let Some(rpc) = &metrics.rpc {
+        line.push_str(&format!(
+            ", RPC: {} requests/min, {} retries, {} failures",
+            rpc.requests_per_minute, rpc.retries, rpc.failures
+        ));
+        if rpc.breaker_opens > 0 {
+            line.push_str(&format!(", breaker opened {} times", rpc.breaker_opens));
+        }
+    }
+    line
+}
+
+impl TpsChart {
+    /// Sample the TPS of the snapshot if a window passed since the last sample, returning whether
+    /// it was sampled.
+    fn sample(&mut self, snapshot: &Snapshot) -> bool {
+        let Some(tps) = snapshot.metrics.transactions_per_second else {
+            return false;
+        };
+        let window = snapshot.metrics.window_blocks.max(1) as u64;
+        if self
+            .sampled_at
+            .is_some_and(|block| snapshot.block_number < block + window)
+        {
+            return false;
+        }
+        self.sampled_at = Some(snapshot.block_number);
+        self.samples.push_back(tps);
+        if self.samples.len() > self.windows {
+            self.samples.pop_front();
+        }
+        true
+    }
+
+    /// Draw the samples as bars of eighth-row steps, scaled to the highest, e.g.
+    /// `▂▅█▇  max 1234.5 TPS`.
+    fn rows(&self) -> Vec<String> {
+        let max = self.samples.iter().copied().fold(0.0, f64::max);
+        let mut rows = (0..CHART_ROWS)
+            .map(|row| {
+                let base = ((CHART_ROWS - 1 - row) * SPARKS.len()) as f64;
+                self.samples
+                    .iter()
+                    .map(|&tps| {
+                        let level = if max > 0.0 {
+                            tps / max * (CHART_ROWS * SPARKS.len()) as f64
+                        } else {
+                            0.0
+                        };
+                        match (level - base).round() {
+                            fill if fill < 1.0 => ' ',
+                            fill => SPARKS[(fill as usize).min(SPARKS.len()) - 1],
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        rows[0].push_str(&format!("  max {max:.1} TPS"));
+        rows[CHART_ROWS - 1].push_str(&format!("  last {} windows", self.samples.len()));
+        rows
+    }
+}
+
+impl Sink for StdoutSink {
+    fn write(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let sampled = self
+            .chart
+            .as_mut()
+            .is_some_and(|chart| chart.sample(snapshot));
+        if self.refresh {
+            self.paint(snapshot);
+            self.last = Some(snapshot.clone());
+        } else {
+            let prefix = format!("[{}] ", Local::now().format("%Y-%m-%d %H:%M:%S%.6f"));
+            println!("{prefix}{} ", self.line(snapshot, usize::MAX));
+            if self.totals {
+                println!("{prefix}{}", snapshot.metrics.totals_line());
+            }
+            // Redrawn only when it changed, to not bury the metrics.
+            if let Some(chart) = self.chart.as_ref().filter(|_| sampled) {
+                for row in chart.rows() {
+                    println!("{row}");
+                }
+            }
+        }
+        stdout().flush()?;
+        Ok(())
+    }
+
+    fn repaint(&mut self) -> Result<()> {
+        let Some(mut snapshot) = self.last.clone() else {
+            return Ok(());
+        };
+        // The head keeps aging while no block arrives.
+        if let Some(received_at) = snapshot.metrics.head_received_at {
+            let age = SystemTime::now().duration_since(received_at);
+            snapshot.metrics.head_age_ms = age.unwrap_or_default().as_secs_f64() * 1000.0;
+        }
+        self.paint(&snapshot);
+        stdout().flush()?;
+        Ok(())
+    }
+}
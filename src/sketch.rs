@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+/// The magnitude below which values count as zero.
+const MIN_MAGNITUDE: f64 = 1e-9;
+
+/// A streaming quantile sketch (DDSketch): values are counted in logarithmic buckets, so every
+/// quantile is accurate to a relative error while the memory is bounded by the range of the
+/// values rather than their number.
+///
+/// Values can be removed again, so the sketch can follow a sliding window.
+#[derive(Debug, Clone)]
+pub struct QuantileSketch {
+    /// The ratio between the bounds of a bucket.
+    gamma: f64,
+    ln_gamma: f64,
+    /// The counts of the positive and, by magnitude, the negative values per bucket index.
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+}
+
+impl QuantileSketch {
+    /// Create a sketch whose quantiles are off by at most `relative_accuracy` (e.g. 0.01 for 1%).
+    pub fn new(relative_accuracy: f64) -> Self {
+        let gamma = (1.0 + relative_accuracy) / (1.0 - relative_accuracy);
+        Self {
+            gamma,
+            ln_gamma: gamma.ln(),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+        }
+    }
+
+    /// Get the number of values in the sketch.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Add a value; values which are not finite are ignored.
+    pub fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        match self.bucket(value) {
+            Some((buckets, index)) => *buckets.entry(index).or_default() += 1,
+            None => self.zeros += 1,
+        }
+        self.count += 1;
+    }
+
+    /// Remove a value added before; values which were never added are ignored.
+    pub fn remove(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        let removed = match self.bucket(value) {
+            Some((buckets, index)) => match buckets.get_mut(&index) {
+                Some(1) => buckets.remove(&index).is_some(),
+                Some(count) => {
+                    *count -= 1;
+                    true
+                }
+                None => false,
+            },
+            None => match self.zeros {
+                0 => false,
+                _ => {
+                    self.zeros -= 1;
+                    true
+                }
+            },
+        };
+        if removed {
+            self.count -= 1;
+        }
+    }
+
+    /// Estimate the `q` quantile (0 to 1), i.e. the value ranked `q * (count - 1)` once rounded,
+    /// or `None` if the sketch is empty.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64).round() as u64;
+        let mut seen = 0;
+        // From the most negative values up to the most positive.
+        for (&index, &count) in self.negative.iter().rev() {
+            seen += count;
+            if seen > rank {
+                return Some(-self.value(index));
+            }
+        }
+        seen += self.zeros;
+        if seen > rank {
+            return Some(0.0);
+        }
+        for (&index, &count) in &self.positive {
+            seen += count;
+            if seen > rank {
+                return Some(self.value(index));
+            }
+        }
+        None
+    }
+
+    /// Get the buckets and the bucket index of a value, or `None` if it counts as zero.
+    fn bucket(&mut self, value: f64) -> Option<(&mut BTreeMap<i32, u64>, i32)> {
+        if value.abs() <= MIN_MAGNITUDE {
+            return None;
+        }
+        let index = (value.abs().ln() / self.ln_gamma).ceil() as i32;
+        let buckets = if value > 0.0 {
+            &mut self.positive
+        } else {
+            &mut self.negative
+        };
+        Some((buckets, index))
+    }
+
+    /// Get the representative magnitude of a bucket, within the relative accuracy of all of its
+    /// values.
+    #[inline]
+    fn value(&self, index: i32) -> f64 {
+        2.0 * self.gamma.powi(index) / (self.gamma + 1.0)
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy::{providers::Provider, rpc::types::BlockTransactionsKind};
+use clap::Args;
+use eyre::{eyre, Result};
+use futures_util::StreamExt;
+use serde::Serialize;
+
+use crate::{
+    fetch::{header_block, FetchConfig, Fetcher},
+    proxy::{self, Proxy},
+    rpc::{RpcClient, RpcConfig},
+    trends::escape,
+};
+
+/// How long without a new block until the subscription counts as stalled, as in the journal.
+const STALL_AFTER: Duration = Duration::from_secs(10);
+
+/// Watch the chain for a fixed time and fail if its throughput and block cadence were not
+/// stable, as a release qualification of node builds.
+#[derive(Args, Debug)]
+pub struct SoakArgs {
+    /// The WebSocket endpoint of the node.
+    #[arg(short, long, default_value = "ws://localhost:8546")]
+    endpoint: String,
+
+    /// Reach the endpoint through a SOCKS5 or HTTP proxy, as with the main command.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<Proxy>,
+
+    /// How long to run.
+    #[arg(long, value_name = "N", value_parser = crate::positive)]
+    hours: f64,
+
+    /// The largest coefficient of variation (percent) of the TPS and of the mini-block interval
+    /// across the samples that still passes.
+    #[arg(long, value_name = "PCT")]
+    max_variance: f64,
+
+    /// The most stalls (no new block for 10 s) that still pass.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    max_stalls: u64,
+
+    /// The length (seconds) of a sample the TPS and mini-block interval are averaged over.
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    sample_secs: u64,
+
+    /// Also write the verdict to this file as a JUnit XML report with a test case per checked
+    /// threshold, for CI systems to render.
+    #[arg(long, value_name = "PATH")]
+    junit: Option<PathBuf>,
+}
+
+/// The throughput and cadence of one sample.
+#[derive(Default)]
+struct Sample {
+    transactions: usize,
+    blocks: usize,
+}
+
+/// A threshold checked at the end of the soak, a test case of the JUnit report.
+struct Check {
+    name: &'static str,
+    /// The measured value and its threshold.
+    detail: String,
+    /// Why the check failed, if it did.
+    failure: Option<String>,
+}
+
+/// The outcome of the soak, printed as JSON.
+#[derive(Debug, Serialize)]
+struct Verdict {
+    passed: bool,
+    /// Why the soak failed.
+    failures: Vec<String>,
+    hours: f64,
+    samples: usize,
+    blocks: usize,
+    mean_transactions_per_second: Option<f64>,
+    /// The coefficient of variation of the per-sample TPS in percent.
+    transactions_per_second_variance: Option<f64>,
+    mean_mini_block_interval_ms: Option<f64>,
+    /// The coefficient of variation of the per-sample mini-block interval in percent.
+    mini_block_interval_variance: Option<f64>,
+    stalls: u64,
+}
+
+/// Run the soak and print its verdict, failing if it did not pass.
+pub async fn run(args: SoakArgs) -> Result<()> {
+    let duration = Duration::try_from_secs_f64(args.hours * 3600.0)
+        .map_err(|_| eyre!("--hours is too long"))?;
+    let provider = proxy::provider(args.endpoint, args.proxy).await?;
+    let rpc = Arc::new(RpcClient::new(provider, RpcConfig::default()));
+    let fetcher = Fetcher::new(
+        rpc.clone(),
+        FetchConfig {
+            headers_only: false,
+            transactions: BlockTransactionsKind::Hashes,
+            receipts: false,
+            count_transactions: false,
+        },
+    );
+    let mut blocks = rpc.provider().subscribe_blocks().await?.into_stream();
+
+    let sample_length = Duration::from_secs(args.sample_secs.max(1));
+    let deadline = tokio::time::sleep(duration);
+    tokio::pin!(deadline);
+    let started = Instant::now();
+    let mut sampling =
+        tokio::time::interval_at(tokio::time::Instant::now() + sample_length, sample_length);
+    let mut stall_check = tokio::time::interval(Duration::from_secs(1));
+    let mut samples = Vec::new();
+    let mut current = Sample::default();
+    let (mut last_block, mut stalled, mut stalls) = (Instant::now(), false, 0);
+    let mut interrupted = None;
+
+    loop {
+        tokio::select! {
+            header = blocks.next() => {
+                let Some(header) = header else {
+                    interrupted = Some("the block subscription ended");
+                    break;
+                };
+                last_block = Instant::now();
+                stalled = false;
+                let number = header.number;
+                match fetcher.subscribed(header_block(header)).await {
+                    Ok(Some(fetched)) => {
+                        current.transactions += fetched.block.transactions.len();
+                        current.blocks += 1;
+                    }
+                    Ok(None) => {}
+                    Err(err) => eprintln!("Failed to get block {number}: {err}"),
+                }
+            }
+            _ = sampling.tick() => {
+                let sample = std::mem::take(&mut current);
+                eprintln!(
+                    "[{:.2} h] {:.1} TPS, {} blocks, {stalls} stalls",
+                    started.elapsed().as_secs_f64() / 3600.0,
+                    sample.transactions as f64 / sample_length.as_secs_f64(),
+                    sample.blocks,
+                );
+                samples.push(sample);
+            }
+            _ = stall_check.tick() => {
+                if !stalled && last_block.elapsed() > STALL_AFTER {
+                    stalled = true;
+                    stalls += 1;
+                    eprintln!("No new block for {:.0} s", last_block.elapsed().as_secs_f64());
+                }
+            }
+            _ = &mut deadline => break,
+            _ = tokio::signal::ctrl_c() => {
+                interrupted = Some("the soak was interrupted");
+                break;
+            }
+        }
+    }
+
+    let seconds = sample_length.as_secs_f64();
+    let tps = samples
+        .iter()
+        .map(|sample| sample.transactions as f64 / seconds)
+        .collect::<Vec<_>>();
+    let intervals = samples
+        .iter()
+        .filter(|sample| sample.blocks > 0)
+        .map(|sample| 1000.0 * seconds / sample.blocks as f64)
+        .collect::<Vec<_>>();
+    let (mean_tps, tps_variance) = spread(&tps).unzip();
+    let (mean_interval, interval_variance) = spread(&intervals).unzip();
+
+    let mut checks = vec![
+        Check {
+            name: "completed",
+            detail: format!(
+                "{:.2} of {} hours",
+                started.elapsed().as_secs_f64() / 3600.0,
+                args.hours
+            ),
+            failure: interrupted.map(String::from),
+        },
+        Check {
+            name: "samples",
+            detail: format!("{} samples of {} s", samples.len(), sample_length.as_secs()),
+            failure: samples
+                .is_empty()
+                .then(|| "no sample was completed".to_string()),
+        },
+    ];
+    for (name, variance) in [
+        ("TPS variance", tps_variance),
+        ("mini-block interval variance", interval_variance),
+    ] {
+        checks.push(Check {
+            name,
+            detail: match variance {
+                Some(variance) => format!("{variance:.1}% (at most {}%)", args.max_variance),
+                None => "no samples".to_string(),
+            },
+            failure: variance
+                .filter(|variance| *variance > args.max_variance)
+                .map(|variance| format!("{name} {variance:.1}% exceeds {}%", args.max_variance)),
+        });
+    }
+    checks.push(Check {
+        name: "stalls",
+        detail: format!("{stalls} stalls (at most {})", args.max_stalls),
+        failure: (stalls > args.max_stalls)
+            .then(|| format!("{stalls} stalls exceed {}", args.max_stalls)),
+    });
+    let failures = checks
+        .iter()
+        .filter_map(|check| check.failure.clone())
+        .collect::<Vec<_>>();
+    if let Some(path) = &args.junit {
+        fs::write(path, junit(&checks, started.elapsed()))?;
+    }
+
+    let verdict = Verdict {
+        passed: failures.is_empty(),
+        hours: started.elapsed().as_secs_f64() / 3600.0,
+        samples: samples.len(),
+        blocks: samples.iter().map(|sample| sample.blocks).sum(),
+        mean_transactions_per_second: mean_tps,
+        transactions_per_second_variance: tps_variance,
+        mean_mini_block_interval_ms: mean_interval,
+        mini_block_interval_variance: interval_variance,
+        stalls,
+        failures,
+    };
+    println!("{}", serde_json::to_string(&verdict)?);
+    if verdict.passed {
+        eprintln!("PASS");
+        Ok(())
+    } else {
+        Err(eyre!("FAIL: {}", verdict.failures.join(", ")))
+    }
+}
+
+/// Get the mean of the values and their coefficient of variation in percent.
+fn spread(values: &[f64]) -> Option<(f64, f64)> {
+    if values.is_empty() {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    let cv = if mean > 0.0 {
+        100.0 * variance.sqrt() / mean
+    } else {
+        0.0
+    };
+    Some((mean, cv))
+}
+
+/// Render the checks as a JUnit XML report.
+fn junit(checks: &[Check], elapsed: Duration) -> String {
+    let failures = checks
+        .iter()
+        .filter(|check| check.failure.is_some())
+        .count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"telescope soak\" tests=\"{}\" failures=\"{failures}\" time=\"{:.3}\">",
+        checks.len(),
+        elapsed.as_secs_f64()
+    );
+    for check in checks {
+        let _ = writeln!(
+            xml,
+            "  <testcase classname=\"telescope.soak\" name=\"{}\">",
+            escape(check.name)
+        );
+        if let Some(failure) = &check.failure {
+            let _ = writeln!(xml, "    <failure message=\"{}\"/>", escape(failure));
+        }
+        let _ = writeln!(
+            xml,
+            "    <system-out>{}</system-out>",
+            escape(&check.detail)
+        );
+        xml.push_str("  </testcase>\n");
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
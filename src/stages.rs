@@ -0,0 +1,140 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+/// Number of recent blocks the stage latencies are measured over.
+const SAMPLES: usize = 256;
+
+/// Times every stage a block passes through, from its production to the written snapshot, to
+/// tell whether a lag behind the chain stems from the network, the node or telescope itself.
+#[derive(Default)]
+pub struct StageTimer {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    propagation: VecDeque<f64>,
+    fetch: VecDeque<f64>,
+    queue: VecDeque<f64>,
+    output: VecDeque<f64>,
+}
+
+/// The latency of a stage over the recent blocks.
+#[derive(Debug, Clone, Serialize)]
+pub struct StageLatency {
+    pub mean_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// The latencies of the stages of the recent blocks, unknown for stages no block went through.
+#[derive(Debug, Clone, Serialize)]
+pub struct StageMetrics {
+    /// From the header timestamp until the header was received, i.e. block production and the
+    /// network. Includes the local clock skew, and block times in seconds round it up.
+    pub propagation: Option<StageLatency>,
+    /// From receiving the header until its block data was fetched from the node.
+    pub fetch: Option<StageLatency>,
+    /// From the fetched data until telescope recorded the block, i.e. queued behind earlier
+    /// blocks and decoded.
+    pub queue: Option<StageLatency>,
+    /// From recording the block until its snapshot was shown and written to the sinks.
+    pub output: Option<StageLatency>,
+    /// Where the most time is spent: `network`, `node` or `telescope`.
+    pub bottleneck: Option<&'static str>,
+}
+
+impl StageTimer {
+    /// Time the stages of a block up to its recording, given its header time in milliseconds.
+    pub fn observe_block(
+        &self,
+        header_ms: f64,
+        received_at: SystemTime,
+        received: Instant,
+        fetched: Option<Instant>,
+        recorded: Instant,
+    ) {
+        let received_ms = received_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0;
+        let mut state = self.state.lock().unwrap();
+        push(&mut state.propagation, (received_ms - header_ms).max(0.0));
+        if let Some(fetched) = fetched {
+            push(
+                &mut state.fetch,
+                milliseconds(fetched.saturating_duration_since(received)),
+            );
+        }
+        let queued = fetched.unwrap_or(received);
+        push(
+            &mut state.queue,
+            milliseconds(recorded.saturating_duration_since(queued)),
+        );
+    }
+
+    /// Time the output of the snapshot of a block recorded at `recorded`.
+    pub fn observe_output(&self, recorded: Instant) {
+        push(
+            &mut self.state.lock().unwrap().output,
+            milliseconds(recorded.elapsed()),
+        );
+    }
+
+    pub fn metrics(&self) -> StageMetrics {
+        let state = self.state.lock().unwrap();
+        let propagation = latency(&state.propagation);
+        let fetch = latency(&state.fetch);
+        let queue = latency(&state.queue);
+        let output = latency(&state.output);
+        let telescope = (queue.is_some() || output.is_some()).then(|| {
+            queue.as_ref().map_or(0.0, |q| q.mean_ms) + output.as_ref().map_or(0.0, |o| o.mean_ms)
+        });
+        let bottleneck = [
+            ("network", propagation.as_ref().map(|p| p.mean_ms)),
+            ("node", fetch.as_ref().map(|f| f.mean_ms)),
+            ("telescope", telescope),
+        ]
+        .into_iter()
+        .filter_map(|(stage, mean)| Some((stage, mean?)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(stage, _)| stage);
+        StageMetrics {
+            propagation,
+            fetch,
+            queue,
+            output,
+            bottleneck,
+        }
+    }
+}
+
+fn push(samples: &mut VecDeque<f64>, sample: f64) {
+    samples.push_back(sample);
+    if samples.len() > SAMPLES {
+        samples.pop_front();
+    }
+}
+
+fn milliseconds(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Get the mean and 99th percentile of the samples.
+fn latency(samples: &VecDeque<f64>) -> Option<StageLatency> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.iter().copied().collect::<Vec<_>>();
+    sorted.sort_by(f64::total_cmp);
+    let rank = ((sorted.len() as f64 * 0.99).ceil() as usize).clamp(1, sorted.len());
+    Some(StageLatency {
+        mean_ms: sorted.iter().sum::<f64>() / sorted.len() as f64,
+        p99_ms: sorted[rank - 1],
+    })
+}
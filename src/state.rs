@@ -0,0 +1,50 @@
+use std::{fs, io, path::Path};
+
+use alloy::rpc::types::BlockTransactionsKind;
+use eyre::{eyre, Result};
+
+use crate::{
+    measurement::{Measurement, SavedBlock},
+    rpc::RpcClient,
+};
+
+/// Read the blocks saved by a previous run, checking they still form the canonical chain.
+///
+/// Only the newest run of consecutive blocks is kept. Returns no blocks if the file does not
+/// exist, and an error if the saved head is no longer part of the chain.
+pub async fn load(path: &Path, rpc: &RpcClient) -> Result<Vec<SavedBlock>> {
+    let json = match fs::read(path) {
+        Ok(json) => json,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut blocks: Vec<SavedBlock> = serde_json::from_slice(&json)?;
+    let continuous = blocks
+        .windows(2)
+        .rposition(|pair| {
+            let (parent, child) = (&pair[0].block.header, &pair[1].block.header);
+            child.number != parent.number + 1 || child.parent_hash != parent.hash
+        })
+        .map_or(0, |gap| gap + 1);
+    blocks.drain(..continuous);
+
+    let Some(head) = blocks.last().map(|saved| &saved.block.header) else {
+        return Ok(blocks);
+    };
+    let canonical = rpc
+        .get_block_by_number(head.number, BlockTransactionsKind::Hashes)
+        .await?;
+    match canonical {
+        Some(block) if block.header.hash == head.hash => Ok(blocks),
+        Some(_) => Err(eyre!("saved block {} was reorged out", head.number)),
+        None => Err(eyre!("saved block {} is unknown to the node", head.number)),
+    }
+}
+
+/// Write the buffered blocks, replacing the previous state file at once.
+pub fn save(path: &Path, measurement: &Measurement) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec(&measurement.save())?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    primitives::{Address, B256, U256},
+    providers::Provider,
+    rpc::client::BatchRequest,
+};
+use futures_util::future::try_join_all;
+use serde::Serialize;
+
+use crate::{read_probe::correlation, rpc::RpcClient};
+
+/// Number of recent probe samples the latencies are computed over.
+const SAMPLES: usize = 64;
+
+/// Periodically proves and reads a batch of storage slots of a contract, following how the state
+/// access latency holds up under the chain's write rate.
+pub struct StateProbe {
+    slots: usize,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// The gas rate of the latest snapshot, attached to every new sample.
+    gas_per_second: Option<f64>,
+    samples: VecDeque<Sample>,
+    failures: u64,
+}
+
+struct Sample {
+    proof_latency_ms: f64,
+    batch_latency_ms: f64,
+    gas_per_second: f64,
+}
+
+/// The state access latency of the recent probe samples.
+#[derive(Debug, Clone, Serialize)]
+pub struct StateProbeMetrics {
+    /// Average `eth_getProof` latency in milliseconds.
+    pub proof_latency_ms: Option<f64>,
+    pub proof_p95_ms: Option<f64>,
+    /// Average latency of the batch of `eth_getStorageAt` calls in milliseconds.
+    pub storage_batch_latency_ms: Option<f64>,
+    pub storage_batch_p95_ms: Option<f64>,
+    /// The storage slots proven and read by every probe.
+    pub slots: usize,
+    /// Pearson correlation of the `eth_getProof` latency with gas per second.
+    pub gas_correlation: Option<f64>,
+    pub samples: usize,
+    /// Probes of the run which failed.
+    pub failures: u64,
+}
+
+impl StateProbe {
+    /// Start probing `address` every `interval`, proving its first `slots` storage slots with
+    /// `eth_getProof` and reading them in one batch of `eth_getStorageAt` calls.
+    pub fn spawn(rpc: Arc<RpcClient>, address: Address, slots: usize, interval: Duration) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let probe_state = state.clone();
+        let keys = (0..slots as u64)
+            .map(|slot| B256::from(U256::from(slot)))
+            .collect::<Vec<_>>();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                let start = Instant::now();
+                let proof = rpc
+                    .request("eth_getProof", |provider| {
+                        let keys = keys.clone();
+                        async move { provider.get_proof(address, keys).await }
+                    })
+                    .await;
+                let proof_latency = start.elapsed();
+
+                let start = Instant::now();
+                let batch = rpc
+                    .request("eth_getStorageAt", |provider| {
+                        let keys = keys.clone();
+                        async move {
+                            let mut batch = BatchRequest::new(provider.client());
+                            let reads = keys
+                                .iter()
+                                .map(|key| {
+                                    batch.add_call::<_, U256>(
+                                        "eth_getStorageAt",
+                                        &(address, *key, BlockNumberOrTag::Latest),
+                                    )
+                                })
+                                .collect::<Result<Vec<_>, _>>()?;
+                            batch.send().await?;
+                            try_join_all(reads).await
+                        }
+                    })
+                    .await;
+                let batch_latency = start.elapsed();
+
+                let mut state = probe_state.lock().unwrap();
+                // Failed reads are not representative of the access latency.
+                if proof.is_err() || batch.is_err() {
+                    state.failures += 1;
+                    continue;
+                }
+                let Some(gas_per_second) = state.gas_per_second else {
+                    continue;
+                };
+                state.samples.push_back(Sample {
+                    proof_latency_ms: proof_latency.as_secs_f64() * 1000.0,
+                    batch_latency_ms: batch_latency.as_secs_f64() * 1000.0,
+                    gas_per_second,
+                });
+                if state.samples.len() > SAMPLES {
+                    state.samples.pop_front();
+                }
+            }
+        });
+        Self { slots, state }
+    }
+
+    /// Update the gas rate the next samples are attributed to.
+    pub fn observe_load(&self, gas_per_second: f64) {
+        self.state.lock().unwrap().gas_per_second = Some(gas_per_second);
+    }
+
+    /// Collect the metrics of the recent samples.
+    pub fn metrics(&self) -> StateProbeMetrics {
+        let state = self.state.lock().unwrap();
+        let samples = &state.samples;
+        let proofs = samples
+            .iter()
+            .map(|s| s.proof_latency_ms)
+            .collect::<Vec<_>>();
+        let batches = samples
+            .iter()
+            .map(|s| s.batch_latency_ms)
+            .collect::<Vec<_>>();
+        StateProbeMetrics {
+            proof_latency_ms: mean(&proofs),
+            proof_p95_ms: p95(&proofs),
+            storage_batch_latency_ms: mean(&batches),
+            storage_batch_p95_ms: p95(&batches),
+            slots: self.slots,
+            gas_correlation: correlation(
+                &proofs,
+                &samples.iter().map(|s| s.gas_per_second).collect::<Vec<_>>(),
+            ),
+            samples: samples.len(),
+            failures: state.failures,
+        }
+    }
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+fn p95(values: &[f64]) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let index = (sorted.len() * 95).div_ceil(100).checked_sub(1)?;
+    sorted.get(index).copied()
+}
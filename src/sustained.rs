@@ -0,0 +1,102 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// Tracks the best throughput sustained over a sliding interval, e.g. the best 60-second average
+/// of a load test.
+#[derive(Debug, Clone)]
+pub struct SustainedMax {
+    interval: Duration,
+    /// The recent blocks (time, transactions, gas), starting with the last one at or before the
+    /// beginning of the interval.
+    blocks: VecDeque<(Instant, Option<usize>, u64)>,
+    best_gas_per_second: Option<f64>,
+    /// Unknown once a block without its transactions was recorded.
+    best_transactions_per_second: Option<f64>,
+    transactions_known: bool,
+}
+
+/// The best throughput sustained over an interval.
+#[derive(Debug, Clone, Serialize)]
+pub struct SustainedPeak {
+    pub interval_s: f64,
+    /// Unknown until the live blocks spanned the interval.
+    pub gas_per_second: Option<f64>,
+    pub transactions_per_second: Option<f64>,
+}
+
+impl SustainedMax {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            blocks: VecDeque::new(),
+            best_gas_per_second: None,
+            best_transactions_per_second: None,
+            transactions_known: true,
+        }
+    }
+
+    /// Record a live block counted at `timestamp`.
+    pub fn record(&mut self, timestamp: Instant, transactions: Option<usize>, gas_used: u64) {
+        self.transactions_known &= transactions.is_some();
+        self.blocks.push_back((timestamp, transactions, gas_used));
+        let Some(start) = timestamp.checked_sub(self.interval) else {
+            return;
+        };
+        while self
+            .blocks
+            .get(1)
+            .is_some_and(|(time, _, _)| *time <= start)
+        {
+            self.blocks.pop_front();
+        }
+        let Some(&(first, _, _)) = self.blocks.front().filter(|(time, _, _)| *time <= start) else {
+            return;
+        };
+        // Like the window rates, the first block's throughput was produced before the interval.
+        let seconds = (timestamp - first).as_secs_f64();
+        let gas = self
+            .blocks
+            .iter()
+            .skip(1)
+            .map(|(_, _, gas)| gas)
+            .sum::<u64>();
+        let gas_per_second = gas as f64 / seconds;
+        self.best_gas_per_second = Some(
+            self.best_gas_per_second
+                .map_or(gas_per_second, |best| best.max(gas_per_second)),
+        );
+        let transactions = self
+            .blocks
+            .iter()
+            .skip(1)
+            .map(|(_, transactions, _)| *transactions)
+            .sum::<Option<usize>>();
+        self.best_transactions_per_second = match transactions {
+            Some(transactions) if self.transactions_known => {
+                let tps = transactions as f64 / seconds;
+                Some(
+                    self.best_transactions_per_second
+                        .map_or(tps, |best| best.max(tps)),
+                )
+            }
+            _ => None,
+        };
+    }
+
+    /// Get the length of the tracked interval.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn metrics(&self) -> SustainedPeak {
+        SustainedPeak {
+            interval_s: self.interval.as_secs_f64(),
+            gas_per_second: self.best_gas_per_second,
+            transactions_per_second: self.best_transactions_per_second,
+        }
+    }
+}
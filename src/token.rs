@@ -0,0 +1,116 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+    time::Instant,
+};
+
+use alloy::{
+    network::{AnyTransactionReceipt, ReceiptResponse},
+    primitives::{b256, Address, B256},
+};
+use serde::Serialize;
+
+/// The topic of the `Transfer` event, whose signature ERC-20 and ERC-721 share.
+const TRANSFER: B256 = b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+
+/// Follows the throughput of an ERC-20 or ERC-721 token, e.g. during its launch.
+pub struct TokenWatch {
+    token: Address,
+    blocks: Mutex<VecDeque<Block>>,
+}
+
+/// What happened to the token in a block.
+struct Block {
+    /// When the block was recorded.
+    recorded: Instant,
+    transfers: usize,
+    /// The senders and recipients of the transfers, without the zero address of mints and burns.
+    holders: Vec<Address>,
+    gas_used: u64,
+}
+
+/// The activity of the watched token in the current window.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenMetrics {
+    pub token: Address,
+    pub transfers: usize,
+    /// Transfers per second, counting the blocks after the oldest like the window-wide rates.
+    pub transfers_per_second: Option<f64>,
+    /// Distinct senders and recipients of the transfers.
+    pub unique_holders: usize,
+    /// Gas used by the transactions calling the token or emitting its events.
+    pub gas_used: u64,
+}
+
+impl TokenWatch {
+    pub fn new(token: Address) -> Self {
+        Self {
+            token,
+            blocks: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Record the token's transfers in a block, keeping the last `window` blocks.
+    pub fn observe(&self, receipts: &[AnyTransactionReceipt], window: usize) {
+        let mut block = Block {
+            recorded: Instant::now(),
+            transfers: 0,
+            holders: Vec::new(),
+            gas_used: 0,
+        };
+        for receipt in receipts {
+            let logs = receipt
+                .inner
+                .inner
+                .logs()
+                .iter()
+                .filter(|log| log.address() == self.token)
+                .collect::<Vec<_>>();
+            if logs.is_empty() && receipt.to() != Some(self.token) {
+                continue;
+            }
+            block.gas_used += receipt.gas_used;
+            for log in logs {
+                let topics = log.topics();
+                if topics.first() != Some(&TRANSFER) || topics.len() < 3 {
+                    continue;
+                }
+                block.transfers += 1;
+                block.holders.extend(
+                    topics[1..3]
+                        .iter()
+                        .map(|topic| Address::from_word(*topic))
+                        .filter(|holder| !holder.is_zero()),
+                );
+            }
+        }
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks.push_back(block);
+        while blocks.len() > window {
+            blocks.pop_front();
+        }
+    }
+
+    /// Sum up the activity of the token in the recorded blocks.
+    pub fn metrics(&self) -> TokenMetrics {
+        let blocks = self.blocks.lock().unwrap();
+        let transfers_per_second = match (blocks.front(), blocks.back()) {
+            (Some(first), Some(last)) if last.recorded > first.recorded => {
+                let transfers = blocks.iter().skip(1).map(|b| b.transfers).sum::<usize>();
+                Some(transfers as f64 / (last.recorded - first.recorded).as_secs_f64())
+            }
+            _ => None,
+        };
+        TokenMetrics {
+            token: self.token,
+            transfers: blocks.iter().map(|b| b.transfers).sum(),
+            transfers_per_second,
+            unique_holders: blocks
+                .iter()
+                .flat_map(|b| &b.holders)
+                .collect::<HashSet<_>>()
+                .len(),
+            gas_used: blocks.iter().map(|b| b.gas_used).sum(),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use alloy::{
+    network::AnyTransactionReceipt,
+    primitives::{keccak256, B256},
+};
+use eyre::Result;
+use serde::Serialize;
+
+/// Well-known events, named without a names file.
+const KNOWN_EVENTS: &[&str] = &[
+    "Transfer(address,address,uint256)",
+    "Approval(address,address,uint256)",
+    "Deposit(address,uint256)",
+    "Withdrawal(address,uint256)",
+    "Sync(uint112,uint112)",
+    "Swap(address,uint256,uint256,uint256,uint256,address)",
+    "Swap(address,address,int256,int256,uint160,uint128,int24)",
+    "TransferSingle(address,address,address,uint256,uint256)",
+    "OwnershipTransferred(address,address)",
+];
+
+/// Ranks the event signatures (topic0) by how often the window's logs carry them, showing which
+/// protocols dominate the on-chain activity during a spike.
+pub struct TopicLeaderboard {
+    size: usize,
+    names: HashMap<B256, String>,
+}
+
+/// An event signature on the leaderboard.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicRank {
+    pub topic0: B256,
+    /// The event name, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub logs: usize,
+    pub logs_per_second: f64,
+    /// The share of the window's logs.
+    pub share_percentage: f64,
+}
+
+impl TopicLeaderboard {
+    /// Create a leaderboard of the `size` most frequent signatures, naming them from a JSON file
+    /// mapping topic0 to a name (e.g. `{"0xddf2…b3ef": "Transfer"}`) besides the well-known ones.
+    pub fn new(size: usize, names: Option<&Path>) -> Result<Self> {
+        let mut known = KNOWN_EVENTS
+            .iter()
+            .map(|signature| {
+                let name = signature.split('(').next().unwrap_or(signature);
+                (keccak256(signature), name.to_string())
+            })
+            .collect::<HashMap<_, _>>();
+        if let Some(path) = names {
+            let names: HashMap<B256, String> = serde_json::from_slice(&fs::read(path)?)?;
+            known.extend(names);
+        }
+        Ok(Self { size, names: known })
+    }
+
+    /// Rank the signatures of the logs in the receipts, emitted over `time_window` seconds.
+    pub fn rank<'a>(
+        &self,
+        receipts: impl IntoIterator<Item = &'a [AnyTransactionReceipt]>,
+        time_window: f64,
+    ) -> Vec<TopicRank> {
+        let mut counts = HashMap::<B256, usize>::new();
+        let mut total = 0;
+        for log in receipts
+            .into_iter()
+            .flatten()
+            .flat_map(|r| r.inner.inner.logs())
+        {
+            total += 1;
+            if let Some(topic0) = log.topics().first() {
+                *counts.entry(*topic0).or_default() += 1;
+            }
+        }
+        let mut ranks = counts.into_iter().collect::<Vec<_>>();
+        // Break ties by the topic, so the order is stable across snapshots.
+        ranks.sort_unstable_by(|(a, a_logs), (b, b_logs)| b_logs.cmp(a_logs).then(a.cmp(b)));
+        ranks
+            .into_iter()
+            .take(self.size)
+            .map(|(topic0, logs)| TopicRank {
+                topic0,
+                name: self.names.get(&topic0).cloned(),
+                logs,
+                logs_per_second: logs as f64 / time_window,
+                share_percentage: 100.0 * logs as f64 / total as f64,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy::{
+    consensus::Transaction, network::TransactionResponse, primitives::B256, providers::Provider,
+    rpc::types::BlockTransactionsKind,
+};
+use clap::Args;
+use eyre::Result;
+use futures_util::StreamExt;
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader},
+    sync::mpsc,
+};
+
+use crate::{
+    fetch::{header_block, FetchConfig, Fetcher},
+    proxy::{self, Proxy},
+    rpc::{RpcClient, RpcConfig},
+};
+
+/// Watch new blocks for the given transactions and report when and where they are included.
+#[derive(Args, Debug)]
+pub struct TrackArgs {
+    /// The WebSocket endpoint of the node.
+    #[arg(short, long, default_value = "ws://localhost:8546")]
+    endpoint: String,
+
+    /// Reach the endpoint through a SOCKS5 or HTTP proxy, as with the main command.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<Proxy>,
+
+    /// The file listing the transaction hashes, one per line, or `-` to read them from stdin as
+    /// they are sent. A line may give the send time in Unix milliseconds after the hash
+    /// (`<hash> <ms>`); otherwise the time the line is read counts as the send time.
+    #[arg(long, value_name = "PATH")]
+    hashes: String,
+
+    /// Give up on transactions not included within this many seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    timeout: u64,
+}
+
+/// The inclusion of a tracked transaction, printed as a JSON line.
+#[derive(Debug, Serialize)]
+struct Inclusion {
+    hash: B256,
+    block_number: u64,
+    /// The index of the transaction in the block.
+    position: usize,
+    /// The position as a percentile of the block, 0 for the first and 100 for the last
+    /// transaction.
+    position_percentile: f64,
+    /// The mini-block the transaction was included in, estimated from its position as the blocks
+    /// do not say where their mini-blocks end.
+    mini_block: u64,
+    /// The priority fee per gas paid above the base fee, in wei.
+    priority_fee_per_gas: Option<u128>,
+    block_timestamp: u64,
+    /// From sending the transaction until its block was received.
+    latency_ms: f64,
+}
+
+/// Where a transaction of a recent block was included.
+struct Included {
+    block_number: u64,
+    position: usize,
+    transactions: usize,
+    mini_blocks: u64,
+    priority_fee_per_gas: Option<u128>,
+    block_timestamp: u64,
+    received_at: SystemTime,
+}
+
+/// Track the inclusion of the listed transactions until all are included or timed out.
+pub async fn run(args: TrackArgs) -> Result<()> {
+    let provider = proxy::provider(args.endpoint, args.proxy).await?;
+    let rpc = Arc::new(RpcClient::new(provider, RpcConfig::default()));
+    let fetcher = Fetcher::new(
+        rpc.clone(),
+        FetchConfig {
+            headers_only: false,
+            // The full transactions tell their priority fees.
+            transactions: BlockTransactionsKind::Full,
+            receipts: false,
+            count_transactions: false,
+        },
+    );
+    let mut blocks = rpc.provider().subscribe_blocks().await?.into_stream();
+
+    let (sender, mut hashes) = mpsc::unbounded_channel();
+    let source = args.hashes;
+    tokio::spawn(async move {
+        let result = if source == "-" {
+            read_hashes(BufReader::new(tokio::io::stdin()), sender).await
+        } else {
+            match tokio::fs::File::open(&source).await {
+                Ok(file) => read_hashes(BufReader::new(file), sender).await,
+                Err(err) => Err(err.into()),
+            }
+        };
+        if let Err(err) = result {
+            eprintln!("Failed to read the transaction hashes from {source}: {err}");
+        }
+    });
+
+    let timeout = Duration::from_secs(args.timeout);
+    let mut pending = HashMap::<B256, SystemTime>::new();
+    // The transactions of the recent blocks, as a hash may be read after its inclusion.
+    let mut recent = HashMap::<B256, Included>::new();
+    let mut recent_blocks = VecDeque::<(SystemTime, Vec<B256>)>::new();
+    let (mut tracked, mut inclusions, mut timed_out) = (0, Vec::new(), 0);
+    let mut reading = true;
+    let mut expiry = tokio::time::interval(Duration::from_secs(1));
+
+    while reading || !pending.is_empty() {
+        tokio::select! {
+            hash = hashes.recv(), if reading => match hash {
+                Some((hash, sent)) => {
+                    tracked += 1;
+                    match recent.get(&hash) {
+                        Some(included) => inclusions.push((sent, report(hash, sent, included)?)),
+                        None => {
+                            pending.insert(hash, sent);
+                        }
+                    }
+                }
+                None => reading = false,
+            },
+            header = blocks.next() => {
+                let Some(header) = header else {
+                    eprintln!("Block subscription ended");
+                    break;
+                };
+                let received_at = SystemTime::now();
+                let number = header.number;
+                let fetched = match fetcher.subscribed(header_block(header)).await {
+                    Ok(Some(fetched)) => fetched,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        eprintln!("Failed to get block {number}: {err}");
+                        continue;
+                    }
+                };
+                let block = fetched.block;
+                let base_fee = block.header.base_fee_per_gas.unwrap_or_default();
+                let mini_blocks = block.header.extra_data.first().map_or(1, |&count| count.max(1));
+                let transactions = block.transactions.as_transactions().unwrap_or_default();
+                let block_hashes = transactions.iter().map(|tx| tx.tx_hash()).collect::<Vec<_>>();
+                for (position, tx) in transactions.iter().enumerate() {
+                    let hash = tx.tx_hash();
+                    let included = Included {
+                        block_number: block.header.number,
+                        position,
+                        transactions: transactions.len(),
+                        mini_blocks: mini_blocks as u64,
+                        priority_fee_per_gas: tx.effective_tip_per_gas(base_fee),
+                        block_timestamp: block.header.timestamp,
+                        received_at,
+                    };
+                    if let Some(sent) = pending.remove(&hash) {
+                        inclusions.push((sent, report(hash, sent, &included)?));
+                    }
+                    recent.insert(hash, included);
+                }
+                recent_blocks.push_back((received_at, block_hashes));
+            }
+            _ = expiry.tick() => {
+                let now = SystemTime::now();
+                let expired = |time: &SystemTime| {
+                    now.duration_since(*time).is_ok_and(|age| age > timeout)
+                };
+                let before = pending.len();
+                pending.retain(|_, sent| !expired(sent));
+                timed_out += before - pending.len();
+                while recent_blocks.front().is_some_and(|(received_at, _)| expired(received_at)) {
+                    if let Some((_, block_hashes)) = recent_blocks.pop_front() {
+                        for hash in block_hashes {
+                            recent.remove(&hash);
+                        }
+                    }
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    let mut latencies = inclusions
+        .iter()
+        .map(|(_, inclusion)| inclusion.latency_ms)
+        .collect::<Vec<_>>();
+    eprintln!(
+        "Included {} of {tracked} transactions, {timed_out} timed out, {} pending{}{}",
+        latencies.len(),
+        pending.len(),
+        summary(&mut latencies),
+        ordering(&inclusions)
+    );
+    Ok(())
+}
+
+/// Read `<hash> [<sent ms>]` lines, sending every hash with its send time.
+async fn read_hashes(
+    reader: impl AsyncBufRead + Unpin,
+    sender: mpsc::UnboundedSender<(B256, SystemTime)>,
+) -> Result<()> {
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await? {
+        let mut fields = line.split_whitespace();
+        let Some(hash) = fields.next() else {
+            continue;
+        };
+        let hash = match hash.parse::<B256>() {
+            Ok(hash) => hash,
+            Err(err) => {
+                eprintln!("Skipping invalid transaction hash `{hash}`: {err}");
+                continue;
+            }
+        };
+        let sent = fields
+            .next()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .map_or_else(SystemTime::now, |ms| UNIX_EPOCH + Duration::from_millis(ms));
+        if sender.send((hash, sent)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Print the inclusion of a transaction.
+fn report(hash: B256, sent: SystemTime, included: &Included) -> Result<Inclusion> {
+    // A send time after the block was received, e.g. from a skewed clock, counts as zero.
+    let latency_ms = included
+        .received_at
+        .duration_since(sent)
+        .unwrap_or_default()
+        .as_secs_f64()
+        * 1000.0;
+    let inclusion = Inclusion {
+        hash,
+        block_number: included.block_number,
+        position: included.position,
+        position_percentile: match included.transactions {
+            0 | 1 => 0.0,
+            transactions => included.position as f64 / (transactions - 1) as f64 * 100.0,
+        },
+        mini_block: (included.position as u64 * included.mini_blocks)
+            / included.transactions.max(1) as u64,
+        priority_fee_per_gas: included.priority_fee_per_gas,
+        block_timestamp: included.block_timestamp,
+        latency_ms,
+    };
+    println!("{}", serde_json::to_string(&inclusion)?);
+    Ok(inclusion)
+}
+
+/// Format where the tracked transactions were placed in their blocks, and how often the pairs in
+/// a block kept the order they were sent in or the order of their priority fees, telling whether
+/// the sequencer orders by arrival or by fee.
+fn ordering(inclusions: &[(SystemTime, Inclusion)]) -> String {
+    if inclusions.is_empty() {
+        return String::new();
+    }
+    let mean_percentile = inclusions
+        .iter()
+        .map(|(_, inclusion)| inclusion.position_percentile)
+        .sum::<f64>()
+        / inclusions.len() as f64;
+    let mut line = format!(", mean position p{mean_percentile:.0}");
+    let (mut arrival, mut fee) = ((0, 0), (0, 0));
+    for (i, (sent, a)) in inclusions.iter().enumerate() {
+        for (other_sent, b) in &inclusions[i + 1..] {
+            if a.block_number != b.block_number {
+                continue;
+            }
+            let earlier = a.position < b.position;
+            if sent != other_sent {
+                arrival.0 += usize::from((sent < other_sent) == earlier);
+                arrival.1 += 1;
+            }
+            if let (Some(tip), Some(other_tip)) = (a.priority_fee_per_gas, b.priority_fee_per_gas) {
+                if tip != other_tip {
+                    fee.0 += usize::from((tip > other_tip) == earlier);
+                    fee.1 += 1;
+                }
+            }
+        }
+    }
+    for (name, (kept, pairs)) in [("arrival", arrival), ("fee", fee)] {
+        if pairs > 0 {
+            line.push_str(&format!(
+                ", in {name} order: {:.0}% of {pairs} pairs",
+                kept as f64 / pairs as f64 * 100.0
+            ));
+        }
+    }
+    line
+}
+
+/// Format the latency percentiles of the included transactions.
+fn summary(latencies: &mut [f64]) -> String {
+    if latencies.is_empty() {
+        return String::new();
+    }
+    latencies.sort_by(f64::total_cmp);
+    let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p).round() as usize];
+    format!(
+        ", latency p50 {:.1} ms, p90 {:.1} ms, p99 {:.1} ms, max {:.1} ms",
+        percentile(0.5),
+        percentile(0.9),
+        percentile(0.99),
+        latencies[latencies.len() - 1]
+    )
+}
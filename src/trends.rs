@@ -0,0 +1,379 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use eyre::{eyre, Result, WrapErr};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::sink;
+
+/// The width and height of a chart in pixels, and the margin around its plot area.
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 240.0;
+const CHART_MARGIN: f64 = 48.0;
+
+/// The colors of the node versions in the charts, repeated if there are more versions.
+const COLORS: [&str; 8] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#17becf",
+];
+
+/// Aggregate the recorded runs in a directory into a static HTML site showing the long-term
+/// trends of the throughput and latency across node versions and dates.
+#[derive(Args, Debug)]
+pub struct TrendsArgs {
+    /// The directory searched (recursively) for JSON and CSV recordings.
+    runs: PathBuf,
+
+    /// The directory the site is written to.
+    #[arg(long, value_name = "DIR", default_value = "trends")]
+    out: PathBuf,
+}
+
+/// The metrics charted per run, as `(field, title, unit)`.
+const METRICS: [(&str, &str, &str); 4] = [
+    ("transactions_per_second", "TPS", "tx/s"),
+    ("gas_per_second", "Gas", "gas/s"),
+    ("mini_block_interval_ms", "Mini-block interval", "ms"),
+    ("fetch_latency_ms", "Fetch latency", "ms"),
+];
+
+/// The medians of the metrics over the snapshots of a run.
+#[derive(Debug, Serialize)]
+struct Run {
+    run_id: String,
+    /// The time of the first snapshot (RFC 3339).
+    started: String,
+    node_version: Option<String>,
+    snapshots: usize,
+    first_block: Option<u64>,
+    last_block: Option<u64>,
+    /// The median of every metric of [`METRICS`].
+    medians: BTreeMap<&'static str, f64>,
+}
+
+/// The snapshots of a run as read from its recordings.
+#[derive(Default)]
+struct Recorded {
+    started: Option<String>,
+    node_version: Option<String>,
+    /// The metrics of every recorded block; a run recorded to several files counts once.
+    blocks: BTreeMap<u64, BTreeMap<&'static str, f64>>,
+}
+
+/// Read all runs and write the site.
+pub fn run(args: TrendsArgs) -> Result<()> {
+    let mut files = Vec::new();
+    collect(&args.runs, &mut files)?;
+    files.sort();
+    let mut recorded = BTreeMap::<String, Recorded>::new();
+    for path in &files {
+        if let Err(err) = read(path, &mut recorded) {
+            eprintln!("Skipping {}: {err:#}", path.display());
+        }
+    }
+    let mut runs = recorded
+        .into_iter()
+        .filter_map(|(run_id, recorded)| summarize(run_id, recorded))
+        .collect::<Vec<_>>();
+    if runs.is_empty() {
+        return Err(eyre!("no recorded runs in {}", args.runs.display()));
+    }
+    runs.sort_by(|a, b| a.started.cmp(&b.started));
+
+    fs::create_dir_all(&args.out)?;
+    fs::write(
+        args.out.join("runs.json"),
+        serde_json::to_vec_pretty(&runs)?,
+    )?;
+    fs::write(args.out.join("index.html"), page(&runs))?;
+    eprintln!(
+        "Wrote the trends of {} runs from {} recordings to {}",
+        runs.len(),
+        files.len(),
+        args.out.join("index.html").display()
+    );
+    Ok(())
+}
+
+/// Find the JSON and CSV recordings below a directory, including rotated ones.
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).wrap_err_with(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect(&path, files)?;
+        } else if matches!(
+            sink::recording_extension(&path),
+            Some("json" | "jsonl" | "csv")
+        ) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Add the snapshots of a recording to their runs.
+fn read(path: &Path, runs: &mut BTreeMap<String, Recorded>) -> Result<()> {
+    let text = fs::read_to_string(path)?;
+    let fallback = path.display().to_string();
+    let snapshots: Box<dyn Iterator<Item = Map<String, Value>>> =
+        if sink::recording_extension(path) == Some("csv") {
+            let mut lines = text.lines();
+            let columns = lines
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .map(String::from)
+                .collect::<Vec<_>>();
+            Box::new(lines.map(move |line| {
+                columns
+                    .iter()
+                    .zip(line.split(','))
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(column, value)| {
+                        let value = value
+                            .parse::<f64>()
+                            .ok()
+                            .and_then(|v| serde_json::Number::from_f64(v).map(Value::Number))
+                            .unwrap_or_else(|| Value::String(value.to_string()));
+                        (column.clone(), value)
+                    })
+                    .collect()
+            }))
+        } else {
+            Box::new(
+                text.lines()
+                    .filter_map(|line| serde_json::from_str::<Map<String, Value>>(line).ok()),
+            )
+        };
+    // Events interleaved with the snapshots have no block number.
+    for snapshot in snapshots.filter(|s| s.contains_key("block_number")) {
+        let run_id = snapshot
+            .get("run_id")
+            .and_then(Value::as_str)
+            .unwrap_or(&fallback)
+            .to_string();
+        let run = runs.entry(run_id).or_default();
+        if let Some(timestamp) = snapshot.get("timestamp").and_then(Value::as_str) {
+            if run
+                .started
+                .as_deref()
+                .is_none_or(|started| timestamp < started)
+            {
+                run.started = Some(timestamp.to_string());
+            }
+        }
+        if let Some(version) = snapshot.get("node_version").and_then(Value::as_str) {
+            run.node_version = Some(version.to_string());
+        }
+        let Some(block) = snapshot.get("block_number").and_then(Value::as_f64) else {
+            continue;
+        };
+        run.blocks.entry(block as u64).or_insert_with(|| {
+            METRICS
+                .iter()
+                .filter_map(|(field, _, _)| Some((*field, snapshot.get(*field)?.as_f64()?)))
+                .collect()
+        });
+    }
+    Ok(())
+}
+
+/// Reduce the snapshots of a run to its medians, skipping runs without any.
+fn summarize(run_id: String, recorded: Recorded) -> Option<Run> {
+    let medians = METRICS
+        .iter()
+        .filter_map(|(field, _, _)| {
+            let mut values = recorded
+                .blocks
+                .values()
+                .filter_map(|metrics| metrics.get(field).copied())
+                .collect::<Vec<_>>();
+            values.sort_by(f64::total_cmp);
+            Some((*field, *values.get(values.len() / 2)?))
+        })
+        .collect::<BTreeMap<_, _>>();
+    (!recorded.blocks.is_empty()).then(|| Run {
+        run_id,
+        started: recorded.started.unwrap_or_default(),
+        node_version: recorded.node_version,
+        snapshots: recorded.blocks.len(),
+        first_block: recorded.blocks.keys().next().copied(),
+        last_block: recorded.blocks.keys().next_back().copied(),
+        medians,
+    })
+}
+
+/// Render the site: a chart per metric with the runs in time order, colored by node version,
+/// and a table of all runs.
+fn page(runs: &[Run]) -> String {
+    let mut versions = runs.iter().map(version).collect::<Vec<_>>();
+    versions.sort();
+    versions.dedup();
+    let color = |run: &Run| {
+        let index = versions
+            .iter()
+            .position(|v| *v == version(run))
+            .unwrap_or(0);
+        COLORS[index % COLORS.len()]
+    };
+
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>telescope trends</title>\n<style>\
+         body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}\
+         td,th{border:1px solid #ccc;padding:4px 8px;text-align:right}\
+         .legend span{margin-right:1.5em}</style>\n</head>\n<body>\n\
+         <h1>telescope trends</h1>\n",
+    );
+    let _ = writeln!(
+        html,
+        "<p>{} runs from {} to {}</p>",
+        runs.len(),
+        escape(date(&runs[0].started)),
+        escape(date(&runs[runs.len() - 1].started))
+    );
+    html.push_str("<p class=\"legend\">");
+    for (index, version) in versions.iter().enumerate() {
+        let _ = write!(
+            html,
+            "<span style=\"color:{}\">&#9679; {}</span>",
+            COLORS[index % COLORS.len()],
+            escape(version)
+        );
+    }
+    html.push_str("</p>\n");
+
+    for (field, title, unit) in METRICS {
+        let points = runs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, run)| Some((index, *run.medians.get(field)?, run)))
+            .collect::<Vec<_>>();
+        if points.is_empty() {
+            continue;
+        }
+        let _ = writeln!(html, "<h2>{title} ({unit}, median per run)</h2>");
+        html.push_str(&chart(&points, runs.len(), &color));
+    }
+
+    html.push_str(
+        "<h2>Runs</h2>\n<table>\n<tr><th>Started</th><th>Node version</th><th>Run</th>\
+         <th>Snapshots</th><th>Blocks</th>",
+    );
+    for (_, title, unit) in METRICS {
+        let _ = write!(html, "<th>{title} ({unit})</th>");
+    }
+    html.push_str("</tr>\n");
+    for run in runs.iter().rev() {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}&ndash;{}</td>",
+            escape(&run.started),
+            escape(version(run)),
+            escape(&run.run_id),
+            run.snapshots,
+            run.first_block.unwrap_or_default(),
+            run.last_block.unwrap_or_default(),
+        );
+        for (field, _, _) in METRICS {
+            match run.medians.get(field) {
+                Some(value) => {
+                    let _ = write!(html, "<td>{value:.1}</td>");
+                }
+                None => html.push_str("<td></td>"),
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Draw the medians of a metric as an SVG line chart over the runs.
+fn chart(
+    points: &[(usize, f64, &Run)],
+    runs: usize,
+    color: &impl Fn(&Run) -> &'static str,
+) -> String {
+    let max = points.iter().map(|(_, v, _)| *v).fold(0.0, f64::max);
+    let max = if max > 0.0 { max * 1.1 } else { 1.0 };
+    let (width, height) = (
+        CHART_WIDTH - 2.0 * CHART_MARGIN,
+        CHART_HEIGHT - 2.0 * CHART_MARGIN,
+    );
+    let x = |index: usize| CHART_MARGIN + width * (index as f64 + 0.5) / runs as f64;
+    let y = |value: f64| CHART_MARGIN + height * (1.0 - value / max);
+
+    let mut svg = format!(
+        "<svg width=\"{CHART_WIDTH}\" height=\"{CHART_HEIGHT}\" \
+         xmlns=\"http://www.w3.org/2000/svg\">\n\
+         <line x1=\"{m}\" y1=\"{bottom}\" x2=\"{right}\" y2=\"{bottom}\" stroke=\"#999\"/>\n\
+         <line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{bottom}\" stroke=\"#999\"/>\n\
+         <text x=\"4\" y=\"{top}\" font-size=\"11\">{max:.1}</text>\n\
+         <text x=\"4\" y=\"{bottom}\" font-size=\"11\">0</text>\n",
+        m = CHART_MARGIN,
+        top = CHART_MARGIN + 4.0,
+        bottom = CHART_MARGIN + height,
+        right = CHART_MARGIN + width,
+    );
+    let line = points
+        .iter()
+        .map(|(index, value, _)| format!("{:.1},{:.1}", x(*index), y(*value)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let _ = writeln!(
+        svg,
+        "<polyline points=\"{line}\" fill=\"none\" stroke=\"#bbb\"/>"
+    );
+    for (index, value, run) in points {
+        let _ = writeln!(
+            svg,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{}\">\
+             <title>{} {}: {value:.1}</title></circle>",
+            x(*index),
+            y(*value),
+            color(run),
+            escape(date(&run.started)),
+            escape(version(run)),
+        );
+    }
+    if let (Some((_, _, first)), Some((_, _, last))) = (points.first(), points.last()) {
+        let _ = writeln!(
+            svg,
+            "<text x=\"{m}\" y=\"{below}\" font-size=\"11\">{}</text>\n\
+             <text x=\"{right}\" y=\"{below}\" font-size=\"11\" text-anchor=\"end\">{}</text>",
+            escape(date(&first.started)),
+            escape(date(&last.started)),
+            m = CHART_MARGIN,
+            right = CHART_MARGIN + width,
+            below = CHART_MARGIN + height + 16.0,
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Get the node version of a run, or `unknown` for recordings from before it was recorded.
+fn version(run: &Run) -> &str {
+    run.node_version.as_deref().unwrap_or("unknown")
+}
+
+/// Get the date of an RFC 3339 timestamp.
+fn date(timestamp: &str) -> &str {
+    timestamp.get(..10).unwrap_or(timestamp)
+}
+
+/// Escape text for HTML and XML.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::VecDeque,
+    io::{stdin, stdout, Read, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use eyre::Result;
+use tokio::sync::mpsc;
+
+use crate::{
+    fees::FeeMetrics,
+    journal::Journal,
+    measurement::{truncate_chars, Measurement, Metrics},
+};
+
+/// Upper bounds (in milliseconds) of the interval buckets shown as heatmap rows.
+const BUCKETS_MS: [f64; 10] = [
+    5.0,
+    10.0,
+    20.0,
+    50.0,
+    100.0,
+    200.0,
+    500.0,
+    1000.0,
+    2000.0,
+    f64::INFINITY,
+];
+
+/// 256-color palette ramp used to color heatmap cells, from few to many samples.
+const PALETTE: [u8; 8] = [22, 28, 34, 70, 142, 178, 208, 196];
+
+/// Width of the bucket labels on the left of the heatmap.
+const LABEL_WIDTH: usize = 9;
+
+/// Number of recent events shown in the events panel.
+const EVENTS: usize = 5;
+
+/// The levels of the fee trend sparkline, from the lowest to the highest.
+pub const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Width of the bar comparing the TPS with its target.
+const TARGET_BAR_WIDTH: usize = 40;
+
+/// The hotkeys shown at the bottom of the screen.
+const HELP: &str = "p pause  +/- window  h heatmap  r reset  s save snapshot  a annotate  q quit";
+
+/// The advertised performance the charts are drawn against.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Targets {
+    pub interval_ms: Option<f64>,
+    pub transactions_per_second: Option<f64>,
+}
+
+/// A full-screen terminal UI showing the metrics and a mini-block interval heatmap.
+pub struct Tui {
+    stdout: Stdout,
+    heatmap: Heatmap,
+    targets: Targets,
+    journal: Journal,
+    /// The terminal settings to restore, if the terminal was switched to unbuffered input.
+    termios: Option<libc::termios>,
+    /// The metrics of the last draw, repainted on hotkeys.
+    metrics: Option<Metrics>,
+    /// Keep showing the last metrics instead of repainting on new blocks.
+    paused: bool,
+    show_heatmap: bool,
+    /// A message about the last hotkey action.
+    status: Option<String>,
+}
+
+impl Tui {
+    /// Switch the terminal to the alternate screen and create the UI.
+    pub fn new(heatmap_span: Duration, targets: Targets, journal: Journal) -> Result<Self> {
+        let mut stdout = stdout();
+        // Enter the alternate screen and hide the cursor.
+        write!(stdout, "\x1b[?1049h\x1b[?25l")?;
+        stdout.flush()?;
+        Ok(Self {
+            stdout,
+            heatmap: Heatmap::new(heatmap_span, targets.interval_ms),
+            targets,
+            journal,
+            termios: enter_cbreak_mode(),
+            metrics: None,
+            paused: false,
+            show_heatmap: true,
+            status: None,
+        })
+    }
+
+    /// Read the pressed keys on a background thread.
+    pub fn keys(&self) -> mpsc::UnboundedReceiver<u8> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            for key in stdin().lock().bytes() {
+                let Ok(key) = key else { break };
+                if tx.send(key).is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    /// Pause or resume repainting on new blocks; the measurement keeps recording.
+    #[inline]
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    #[inline]
+    pub fn toggle_heatmap(&mut self) {
+        self.show_heatmap = !self.show_heatmap;
+    }
+
+    /// Forget the recorded heatmap samples.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.heatmap.samples.clear();
+    }
+
+    /// Show a message about the last action.
+    #[inline]
+    pub fn set_status(&mut self, status: impl Into<String>) {
+        self.status = Some(status.into());
+    }
+
+    /// Record the interval of the newest block.
+    #[inline]
+    pub fn record(&mut self, measurement: &Measurement) {
+        if let (Some(interval), Some(last)) = (measurement.last_interval(), measurement.last()) {
+            let mini_blocks = last.mini_blocks().max(1) as u32;
+            self.heatmap.record(last.timestamp, interval / mini_blocks);
+        }
+    }
+
+    /// Show new metrics, unless paused.
+    pub fn draw(&mut self, metrics: &Metrics) -> Result<()> {
+        if self.paused {
+            return Ok(());
+        }
+        self.metrics = Some(metrics.clone());
+        self.repaint()
+    }
+
+    /// Repaint the whole screen with the last metrics.
+    pub fn repaint(&mut self) -> Result<()> {
+        let (width, _) = terminal_size();
+        let mut frame = String::new();
+        // Move the cursor home and clear the screen.
+        frame.push_str("\x1b[H\x1b[2J");
+        frame.push_str(&format!(
+            "\x1b[1mtelescope\x1b[0m  {}{}\r\n\r\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            if self.paused {
+                "  \x1b[7m PAUSED \x1b[0m"
+            } else {
+                ""
+            }
+        ));
+        if let Some(metrics) = &self.metrics {
+            frame.push_str(&metrics.summary_within(width));
+            frame.push_str("\r\n");
+            let mut totals = metrics.totals_line();
+            truncate_chars(&mut totals, width);
+            frame.push_str(&totals);
+            if let (Some(target), Some(tps)) = (
+                self.targets.transactions_per_second,
+                metrics.transactions_per_second,
+            ) {
+                frame.push_str("\r\n");
+                frame.push_str(&target_bar(tps, target));
+            }
+            if let Some(fees) = &metrics.fees {
+                frame.push_str("\r\n");
+                frame.push_str(&fee_trend(fees, width));
+            }
+        }
+        frame.push_str("\r\n\r\n");
+        if self.show_heatmap {
+            frame.push_str(&self.heatmap.render(width));
+            frame.push_str("\r\n");
+        }
+        let events = self.journal.recent(EVENTS);
+        if !events.is_empty() {
+            frame.push_str("Events\r\n");
+            for entry in events {
+                let mut line = entry.line();
+                truncate_chars(&mut line, width);
+                frame.push_str(&format!("{}{line}\x1b[0m\r\n", entry.color()));
+            }
+            frame.push_str("\r\n");
+        }
+        if let Some(status) = &self.status {
+            frame.push_str(status);
+            frame.push_str("\r\n");
+        }
+        frame.push_str(&format!("\x1b[2m{HELP}\x1b[0m"));
+        write!(self.stdout, "{frame}")?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        // Show the cursor and leave the alternate screen.
+        let _ = write!(self.stdout, "\x1b[?25h\x1b[?1049l");
+        let _ = self.stdout.flush();
+        if let Some(termios) = &self.termios {
+            // SAFETY: `termios` was filled in by `tcgetattr`.
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios) };
+        }
+    }
+}
+
+/// Interval samples of the last `span`, rendered with time on the x-axis and the interval
+/// bucket on the y-axis.
+struct Heatmap {
+    span: Duration,
+    /// The interval drawn as a line across the empty cells of its bucket.
+    target_ms: Option<f64>,
+    samples: VecDeque<(Instant, Duration)>,
+}
+
+impl Heatmap {
+    fn new(span: Duration, target_ms: Option<f64>) -> Self {
+        Self {
+            span,
+            target_ms,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record an interval sample and forget the ones that fell out of the span.
+    #[inline]
+    fn record(&mut self, timestamp: Instant, interval: Duration) {
+        self.samples.push_back((timestamp, interval));
+        while let Some((oldest, _)) = self.samples.front() {
+            if timestamp.duration_since(*oldest) <= self.span {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Render the heatmap for a terminal of the given width.
+    fn render(&self, width: usize) -> String {
+        let columns = width.saturating_sub(LABEL_WIDTH).max(1);
+        let column_span = self.span.as_secs_f64() / columns as f64;
+        let now = Instant::now();
+
+        let mut counts = vec![[0u32; BUCKETS_MS.len()]; columns];
+        for (timestamp, interval) in &self.samples {
+            let age = now.duration_since(*timestamp).as_secs_f64();
+            let Some(column) = columns.checked_sub(1 + (age / column_span) as usize) else {
+                continue;
+            };
+            let interval_ms = interval.as_secs_f64() * 1000.0;
+            let bucket = BUCKETS_MS
+                .iter()
+                .position(|bound| interval_ms < *bound)
+                .unwrap_or(BUCKETS_MS.len() - 1);
+            counts[column][bucket] += 1;
+        }
+        let max = counts.iter().flatten().copied().max().unwrap_or(0).max(1) as f64;
+
+        let target_bucket = self
+            .target_ms
+            .map(|target| BUCKETS_MS.iter().position(|bound| target <= *bound));
+        let mut out = String::from("Mini-block interval heatmap");
+        if let Some(target) = self.target_ms {
+            out.push_str(&format!(" (\x1b[33m─\x1b[0m target {target} ms)"));
+        }
+        out.push_str("\r\n");
+        for (bucket, bound) in BUCKETS_MS.iter().enumerate().rev() {
+            let label = if bound.is_finite() {
+                format!("<{}ms", bound)
+            } else {
+                format!(">{}ms", BUCKETS_MS[bucket - 1])
+            };
+            out.push_str(&format!("{label:>width$} ", width = LABEL_WIDTH - 1));
+            let on_target = target_bucket == Some(Some(bucket));
+            for column in &counts {
+                match column[bucket] {
+                    0 if on_target => out.push_str("\x1b[33m─\x1b[0m"),
+                    0 => out.push(' '),
+                    count => {
+                        // Log scale, so a handful of stutters stay visible next to the bulk.
+                        let level = (count as f64).ln_1p() / max.ln_1p();
+                        let color =
+                            PALETTE[((level * (PALETTE.len() - 1) as f64).round()) as usize];
+                        out.push_str(&format!("\x1b[48;5;{color}m \x1b[0m"));
+                    }
+                }
+            }
+            out.push_str("\r\n");
+        }
+        let span_label = format!("-{}s", self.span.as_secs());
+        out.push_str(&format!(
+            "{:LABEL_WIDTH$}{span_label}{:>pad$}\r\n",
+            "",
+            "now",
+            pad = columns.saturating_sub(span_label.len()),
+        ));
+        out
+    }
+}
+
+/// Draw the TPS as a bar against its target, e.g. `TPS   52.0% of 100000 [████──────]`.
+fn target_bar(tps: f64, target: f64) -> String {
+    let ratio = if target > 0.0 { tps / target } else { 0.0 };
+    let filled = ((ratio.min(1.0) * TARGET_BAR_WIDTH as f64).round()) as usize;
+    // Green once the target is reached.
+    let color = if ratio >= 1.0 { 32 } else { 33 };
+    format!(
+        "TPS {:>6.1}% of {target} [\x1b[{color}m{}\x1b[0m{}]",
+        ratio * 100.0,
+        "█".repeat(filled),
+        "─".repeat(TARGET_BAR_WIDTH - filled),
+    )
+}
+
+/// Draw the base fee trajectory of the fee history as a sparkline with the priority fee
+/// percentiles, e.g. `Base fee  0.007 gwei ▁▂▄█  p10 0.001  p50 0.002  p90 0.005`.
+fn fee_trend(fees: &FeeMetrics, width: usize) -> String {
+    let (low, high) = fees
+        .trajectory_gwei
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(low, high), &fee| {
+            (low.min(fee), high.max(fee))
+        });
+    let sparkline = fees
+        .trajectory_gwei
+        .iter()
+        .map(|&fee| {
+            let level = if high > low {
+                (fee - low) / (high - low) * (SPARKS.len() - 1) as f64
+            } else {
+                0.0
+            };
+            SPARKS[level.round() as usize]
+        })
+        .collect::<String>();
+    let mut line = format!("Base fee {:>9.3} gwei {sparkline}", fees.base_fee_gwei);
+    for (percentile, tip) in &fees.reward_gwei {
+        line.push_str(&format!("  {percentile} {tip:.3}"));
+    }
+    line.chars().take(width).collect()
+}
+
+/// Deliver key presses immediately and without echo, returning the settings to restore.
+///
+/// Signals stay enabled, so Ctrl-C still stops telescope.
+fn enter_cbreak_mode() -> Option<libc::termios> {
+    // SAFETY: `termios` is plain old data and only read after `tcgetattr` filled it in.
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    // SAFETY: `tcgetattr` and `tcsetattr` only access the provided `termios`.
+    unsafe {
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return None;
+        }
+        let original = termios;
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+            return None;
+        }
+        Some(original)
+    }
+}
+
+/// Get the size (columns, rows) of the terminal, falling back to 80x24.
+pub fn terminal_size() -> (usize, usize) {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: `TIOCGWINSZ` only writes into the provided `winsize`.
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if ok && size.ws_col > 0 {
+        (size.ws_col as usize, size.ws_row as usize)
+    } else {
+        (80, 24)
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alloy::{network::AnyRpcHeader, primitives::U64, providers::Provider};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    journal::{Journal, Severity},
+    rpc::{ErrorResponse, RpcClient},
+};
+
+/// The base fee rules a block is checked against: a constant base fee, and EIP-1559 with the
+/// elasticity and change denominator of Ethereum and of the OP Stack before and after Canyon.
+const RULES: [BaseFeeRule; 4] = [
+    BaseFeeRule::Constant,
+    BaseFeeRule::Eip1559 {
+        elasticity: 2,
+        denominator: 8,
+    },
+    BaseFeeRule::Eip1559 {
+        elasticity: 6,
+        denominator: 50,
+    },
+    BaseFeeRule::Eip1559 {
+        elasticity: 6,
+        denominator: 250,
+    },
+];
+
+/// Watches for network upgrades: header fields appearing or disappearing, the base fee
+/// following another rule, and the chain configuration reported by `eth_config` changing.
+///
+/// Every change is logged with the block it was observed at, so performance shifts can be
+/// attributed to upgrades.
+pub struct UpgradeWatch {
+    journal: Journal,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    parent: Option<AnyRpcHeader>,
+    fields: Option<BTreeSet<String>>,
+    /// The base fee rule the recent blocks follow, once a block told the rules apart.
+    rule: Option<BaseFeeRule>,
+    /// Whether the blocks stopped following the identified rule.
+    rule_lost: bool,
+    chain_id: Option<u64>,
+    /// The `current` fork of `eth_config`, if the node serves it.
+    fork: Option<Value>,
+    next_fork_time: Option<u64>,
+    last_change: Option<u64>,
+}
+
+/// How a block's base fee follows from its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BaseFeeRule {
+    Constant,
+    Eip1559 { elasticity: u64, denominator: u64 },
+}
+
+/// The observed chain configuration.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpgradeMetrics {
+    pub chain_id: Option<u64>,
+    /// The base fee rule the recent blocks follow, if identified.
+    pub base_fee_rule: Option<String>,
+    /// Number of fields of the latest header.
+    pub header_fields: usize,
+    /// The activation time of the next fork reported by `eth_config`.
+    pub next_fork_time: Option<u64>,
+    /// The block of the last observed change.
+    pub last_change_block: Option<u64>,
+}
+
+impl UpgradeWatch {
+    /// Start polling the chain ID and `eth_config` every `interval`.
+    pub fn spawn(rpc: Arc<RpcClient>, interval: Duration, journal: Journal) -> Self {
+        let state = Arc::new(Mutex::new(State::default()));
+        let (polled, log) = (state.clone(), journal.clone());
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // Stop asking for `eth_config` once the node refused it.
+            let mut config = true;
+            loop {
+                ticker.tick().await;
+                let chain_id = rpc
+                    .request("eth_chainId", |provider| async move {
+                        provider.get_chain_id().await
+                    })
+                    .await
+                    .ok();
+                // A node without `eth_config` leaves the fork unknown.
+                let fork = if config {
+                    match rpc
+                        .request("eth_config", |provider| async move {
+                            provider
+                                .raw_request::<_, Option<Value>>("eth_config".into(), ())
+                                .await
+                        })
+                        .await
+                    {
+                        Ok(fork) => {
+                            config = fork.is_some();
+                            fork
+                        }
+                        Err(err) => {
+                            config = !err.is::<ErrorResponse>();
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                let mut state = polled.lock().unwrap();
+                let block = state.parent.as_ref().map_or(0, |parent| parent.number);
+                if let Some(chain_id) = chain_id {
+                    if state.chain_id.is_some_and(|previous| previous != chain_id) {
+                        log.log(
+                            Severity::Crit,
+                            "upgrade",
+                            format!("Chain ID changed to {chain_id} at block {block}"),
+                        );
+                        state.last_change = Some(block);
+                    }
+                    state.chain_id = Some(chain_id);
+                }
+                if let Some(fork) = fork {
+                    let current = fork.get("current").cloned();
+                    if state.fork.is_some() && state.fork != current {
+                        log.log(
+                            Severity::Warn,
+                            "upgrade",
+                            format!("Chain configuration changed at block {block}"),
+                        );
+                        state.last_change = Some(block);
+                    }
+                    state.next_fork_time = fork
+                        .pointer("/next/activationTime")
+                        .and_then(|time| serde_json::from_value::<U64>(time.clone()).ok())
+                        .map(|time| time.to());
+                    state.fork = current;
+                }
+            }
+        });
+        Self { journal, state }
+    }
+
+    /// Compare a recorded header with its parent.
+    pub fn observe(&self, header: &AnyRpcHeader) {
+        let mut alerts = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            let fields: BTreeSet<String> = serde_json::to_value(header)
+                .ok()
+                .and_then(|header| Some(header.as_object()?.keys().cloned().collect()))
+                .unwrap_or_default();
+            if let Some(previous) = &state.fields {
+                for field in fields.difference(previous) {
+                    alerts.push(format!(
+                        "Header field `{field}` appeared at block {}",
+                        header.number
+                    ));
+                }
+                for field in previous.difference(&fields) {
+                    alerts.push(format!(
+                        "Header field `{field}` disappeared at block {}",
+                        header.number
+                    ));
+                }
+            }
+            state.fields = Some(fields);
+
+            let rules = state
+                .parent
+                .as_ref()
+                .filter(|parent| parent.number + 1 == header.number)
+                .and_then(|parent| consistent_rules(parent, header));
+            if let Some(rules) = rules {
+                match state.rule {
+                    Some(rule) if rules.contains(&rule) => {}
+                    Some(rule) => {
+                        alerts.push(format!(
+                            "Base fee at block {} no longer follows the {rule} rule",
+                            header.number
+                        ));
+                        state.rule = None;
+                        state.rule_lost = true;
+                    }
+                    None => {}
+                }
+                if let (None, [rule]) = (state.rule, rules.as_slice()) {
+                    if std::mem::take(&mut state.rule_lost) {
+                        alerts.push(format!(
+                            "Base fee follows the {rule} rule from block {}",
+                            header.number
+                        ));
+                    }
+                    state.rule = Some(*rule);
+                }
+            }
+            if !alerts.is_empty() {
+                state.last_change = Some(header.number);
+            }
+            state.parent = Some(header.clone());
+        }
+        for alert in alerts {
+            self.journal.log(Severity::Warn, "upgrade", alert);
+        }
+    }
+
+    pub fn metrics(&self) -> UpgradeMetrics {
+        let state = self.state.lock().unwrap();
+        UpgradeMetrics {
+            chain_id: state.chain_id,
+            base_fee_rule: state.rule.map(|rule| rule.to_string()),
+            header_fields: state.fields.as_ref().map_or(0, BTreeSet::len),
+            next_fork_time: state.next_fork_time,
+            last_change_block: state.last_change,
+        }
+    }
+}
+
+/// Get the base fee rules the header's base fee is consistent with, or `None` if either block
+/// has no base fee.
+fn consistent_rules(parent: &AnyRpcHeader, header: &AnyRpcHeader) -> Option<Vec<BaseFeeRule>> {
+    let (parent_base_fee, base_fee) = (parent.base_fee_per_gas?, header.base_fee_per_gas?);
+    Some(
+        RULES
+            .into_iter()
+            .filter(|rule| rule.next_base_fee(parent, parent_base_fee) == base_fee)
+            .collect(),
+    )
+}
+
+impl BaseFeeRule {
+    /// Calculate the base fee of the child of `parent`.
+    fn next_base_fee(&self, parent: &AnyRpcHeader, base_fee: u64) -> u64 {
+        let Self::Eip1559 {
+            elasticity,
+            denominator,
+        } = *self
+        else {
+            return base_fee;
+        };
+        let target = (parent.gas_limit / elasticity) as u128;
+        let (base_fee, gas_used) = (base_fee as u128, parent.gas_used as u128);
+        if target == 0 || gas_used == target {
+            base_fee as u64
+        } else if gas_used > target {
+            let delta = (base_fee * (gas_used - target) / target / denominator as u128).max(1);
+            (base_fee + delta) as u64
+        } else {
+            let delta = base_fee * (target - gas_used) / target / denominator as u128;
+            (base_fee - delta) as u64
+        }
+    }
+}
+
+impl fmt::Display for BaseFeeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Constant => write!(f, "constant"),
+            Self::Eip1559 {
+                elasticity,
+                denominator,
+            } => write!(
+                f,
+                "EIP-1559 (elasticity {elasticity}, denominator {denominator})"
+            ),
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use std::{env, path::PathBuf, str::FromStr, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+use eyre::{eyre, Context, Result};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+
+use crate::journal::{Journal, Severity};
+
+/// Where the recordings are uploaded, given as `s3://<bucket>[/<prefix>]` or
+/// `gs://<bucket>[/<prefix>]`.
+#[derive(Debug, Clone)]
+pub struct UploadTarget {
+    store: Store,
+    bucket: String,
+    prefix: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Store {
+    /// Signed with the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and optional
+    /// `AWS_SESSION_TOKEN`, in `AWS_REGION`, at `AWS_ENDPOINT_URL` if set (e.g. MinIO).
+    S3,
+    /// Authorized with the `GOOGLE_OAUTH_ACCESS_TOKEN`, e.g. from `gcloud auth print-access-token`.
+    Gcs,
+}
+
+impl FromStr for UploadTarget {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (store, path) = if let Some(path) = s.strip_prefix("s3://") {
+            (Store::S3, path)
+        } else if let Some(path) = s.strip_prefix("gs://") {
+            (Store::Gcs, path)
+        } else {
+            return Err(eyre!(
+                "invalid upload target `{s}`, expected s3://<bucket>/<prefix> or gs://<bucket>/<prefix>"
+            ));
+        };
+        let (bucket, prefix) = path.split_once('/').unwrap_or((path, ""));
+        if bucket.is_empty() {
+            return Err(eyre!("invalid upload target `{s}`, the bucket is missing"));
+        }
+        Ok(Self {
+            store,
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+}
+
+/// Uploads the completed recordings and reports of the run in the background, so they outlive
+/// an ephemeral machine.
+pub struct Uploader {
+    target: UploadTarget,
+    run_id: String,
+    http: reqwest::Client,
+    pending: Mutex<Vec<JoinHandle<()>>>,
+    journal: Journal,
+}
+
+impl Uploader {
+    pub fn new(target: UploadTarget, run_id: String, journal: Journal) -> Self {
+        Self {
+            target,
+            run_id,
+            http: reqwest::Client::new(),
+            pending: Mutex::new(Vec::new()),
+            journal,
+        }
+    }
+
+    /// Upload a file as `<prefix>/<run id>/<file name>`.
+    pub fn upload(&self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        let Some(name) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            return;
+        };
+        let key = [self.target.prefix.as_str(), &self.run_id, &name]
+            .iter()
+            .filter(|segment| !segment.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("/");
+        let (http, target, journal) =
+            (self.http.clone(), self.target.clone(), self.journal.clone());
+        let handle = tokio::spawn(async move {
+            let uploaded = match tokio::fs::read(&path).await {
+                Ok(body) => put(&http, &target, &key, body).await,
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = uploaded {
+                journal.log(
+                    Severity::Warn,
+                    "upload",
+                    format!("Failed to upload {}: {err:#}", path.display()),
+                );
+            }
+        });
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|handle| !handle.is_finished());
+        pending.push(handle);
+    }
+
+    /// Check the credentials by writing an empty `<prefix>/.telescope-check` object.
+    pub async fn check(&self) -> Result<()> {
+        let key = [self.target.prefix.as_str(), ".telescope-check"]
+            .iter()
+            .filter(|segment| !segment.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("/");
+        put(&self.http, &self.target, &key, Vec::new()).await
+    }
+
+    /// Wait for the started uploads to complete.
+    pub async fn finish(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        for handle in pending {
+            let _ = handle.await;
+        }
+    }
+}
+
+async fn put(
+    http: &reqwest::Client,
+    target: &UploadTarget,
+    key: &str,
+    body: Vec<u8>,
+) -> Result<()> {
+    let request = match target.store {
+        Store::S3 => sign_s3(http, target, key, body)?,
+        Store::Gcs => {
+            let token = env::var("GOOGLE_OAUTH_ACCESS_TOKEN")
+                .context("GOOGLE_OAUTH_ACCESS_TOKEN is not set")?;
+            http.put(format!(
+                "https://storage.googleapis.com/{}/{}",
+                target.bucket,
+                encode_path(key)
+            ))
+            .bearer_auth(token)
+            .body(body)
+        }
+    };
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// Build a `PutObject` request signed with AWS Signature Version 4.
+fn sign_s3(
+    http: &reqwest::Client,
+    target: &UploadTarget,
+    key: &str,
+    body: Vec<u8>,
+) -> Result<reqwest::RequestBuilder> {
+    let access_key = env::var("AWS_ACCESS_KEY_ID").context("AWS_ACCESS_KEY_ID is not set")?;
+    let secret_key =
+        env::var("AWS_SECRET_ACCESS_KEY").context("AWS_SECRET_ACCESS_KEY is not set")?;
+    let session_token = env::var("AWS_SESSION_TOKEN").ok();
+    let region = env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+    // A custom endpoint is addressed path-style, AWS itself virtual-hosted-style.
+    let url = match env::var("AWS_ENDPOINT_URL") {
+        Ok(endpoint) => format!(
+            "{}/{}/{}",
+            endpoint.trim_end_matches('/'),
+            target.bucket,
+            encode_path(key)
+        ),
+        Err(_) => format!(
+            "https://{}.s3.{region}.amazonaws.com/{}",
+            target.bucket,
+            encode_path(key)
+        ),
+    };
+    let url = reqwest::Url::parse(&url)?;
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return Err(eyre!("invalid S3 endpoint {url}")),
+    };
+
+    let now = Utc::now();
+    let payload_hash = hex::encode(Sha256::digest(&body));
+    let mut headers = vec![
+        ("host", host),
+        ("x-amz-content-sha256", payload_hash.clone()),
+        ("x-amz-date", now.format("%Y%m%dT%H%M%SZ").to_string()),
+    ];
+    if let Some(token) = session_token {
+        headers.push(("x-amz-security-token", token));
+    }
+    let Signature {
+        signed_headers,
+        scope,
+        signature,
+        ..
+    } = sign(
+        url.path(),
+        &headers,
+        &payload_hash,
+        now,
+        &region,
+        &secret_key,
+    );
+
+    let mut request = http.put(url).body(body).header(
+        "authorization",
+        format!(
+            "AWS4-HMAC-SHA256 Credential={access_key}/{scope}, SignedHeaders={signed_headers}, \
+             Signature={signature}"
+        ),
+    );
+    for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+        request = request.header(name, value);
+    }
+    Ok(request)
+}
+
+/// The AWS Signature Version 4 of a `PUT` request to S3.
+struct Signature {
+    signed_headers: String,
+    scope: String,
+    signature: String,
+}
+
+/// Sign a `PUT` of `path` with the given headers, sorted by their lowercase names.
+fn sign(
+    path: &str,
+    headers: &[(&str, String)],
+    payload_hash: &str,
+    now: DateTime<Utc>,
+    region: &str,
+    secret_key: &str,
+) -> Signature {
+    let (date, time) = (
+        now.format("%Y%m%d").to_string(),
+        now.format("%Y%m%dT%H%M%SZ").to_string(),
+    );
+    let signed_headers = signed_headers(headers);
+    let canonical_request = canonical_request(path, headers, payload_hash);
+    let scope = format!("{date}/{region}/s3/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{time}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+    let signing_key = [region, "s3", "aws4_request"].iter().fold(
+        hmac(format!("AWS4{secret_key}").as_bytes(), date.as_bytes()),
+        |key, part| hmac(&key, part.as_bytes()),
+    );
+    Signature {
+        signature: hex::encode(hmac(&signing_key, string_to_sign.as_bytes())),
+        signed_headers,
+        scope,
+    }
+}
+
+/// List the names of the signed headers.
+fn signed_headers(headers: &[(&str, String)]) -> String {
+    headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+/// Build the canonical form of a `PUT` of `path` which is signed.
+fn canonical_request(path: &str, headers: &[(&str, String)], payload_hash: &str) -> String {
+    let canonical_headers = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect::<String>();
+    format!(
+        "PUT\n{path}\n\n{canonical_headers}\n{}\n{payload_hash}",
+        signed_headers(headers)
+    )
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode an object key, keeping the unreserved characters and the separators.
+fn encode_path(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    /// The `PUT` object example of the AWS Signature Version 4 documentation for S3.
+    #[test]
+    fn sign_matches_the_aws_example() {
+        let body = b"Welcome to Amazon S3.";
+        let payload_hash = hex::encode(Sha256::digest(body));
+        assert_eq!(
+            payload_hash,
+            "44ce7dd67c959e0d3524ffac1771dfbba87d2b6b4b4e99e42034a8b803f8b072"
+        );
+        let headers = [
+            ("date", "Fri, 24 May 2013 00:00:00 GMT".to_string()),
+            ("host", "examplebucket.s3.amazonaws.com".to_string()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", "20130524T000000Z".to_string()),
+            ("x-amz-storage-class", "REDUCED_REDUNDANCY".to_string()),
+        ];
+        let path = format!("/{}", encode_path("test$file.text"));
+        assert_eq!(
+            canonical_request(&path, &headers, &payload_hash),
+            "PUT\n\
+             /test%24file.text\n\
+             \n\
+             date:Fri, 24 May 2013 00:00:00 GMT\n\
+             host:examplebucket.s3.amazonaws.com\n\
+             x-amz-content-sha256:44ce7dd67c959e0d3524ffac1771dfbba87d2b6b4b4e99e42034a8b803f8b072\n\
+             x-amz-date:20130524T000000Z\n\
+             x-amz-storage-class:REDUCED_REDUNDANCY\n\
+             \n\
+             date;host;x-amz-content-sha256;x-amz-date;x-amz-storage-class\n\
+             44ce7dd67c959e0d3524ffac1771dfbba87d2b6b4b4e99e42034a8b803f8b072"
+        );
+        let signature = sign(
+            &path,
+            &headers,
+            &payload_hash,
+            Utc.with_ymd_and_hms(2013, 5, 24, 0, 0, 0).unwrap(),
+            "us-east-1",
+            "wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY",
+        );
+        assert_eq!(signature.scope, "20130524/us-east-1/s3/aws4_request");
+        assert_eq!(
+            signature.signature,
+            "98ad721746da40c64f1a55b78f14c238d841ea1380cd77a1b5971af0ece108bd"
+        );
+    }
+
+    #[test]
+    fn encode_path_keeps_unreserved_characters_and_separators() {
+        assert_eq!(
+            encode_path("runs/2024-01-01_a.b~c/run.csv"),
+            "runs/2024-01-01_a.b~c/run.csv"
+        );
+        assert_eq!(encode_path("a b+c$d"), "a%20b%2Bc%24d");
+        assert_eq!(encode_path("é"), "%C3%A9");
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use crate::{
+    journal::{Journal, Severity},
+    measurement::Measurement,
+};
+
+/// The largest window suggested or set, bounding the memory of the buffer.
+const MAX_WINDOW: u64 = 100_000;
+
+/// How far the automatic window may be off its target size before it is resized, so jitter of
+/// the block rate does not resize it on every block.
+const TOLERANCE: f64 = 0.2;
+
+/// The weight of the newest window in the smoothed block interval.
+const SMOOTHING: f64 = 0.1;
+
+/// Checks that the window spans enough time for stable rates at the observed block rate, warning
+/// or resizing it when it does not.
+pub struct WindowSizer {
+    /// The shortest span the window should have.
+    min_span: Duration,
+    /// The span the window is resized to, if resizing automatically.
+    target: Option<Duration>,
+    journal: Journal,
+    /// The block interval in seconds, smoothed over the observed windows.
+    interval: Option<f64>,
+    /// Whether the undersized window was already logged.
+    warned: bool,
+}
+
+impl WindowSizer {
+    pub fn new(min_span: Duration, target: Option<Duration>, journal: Journal) -> Self {
+        Self {
+            min_span,
+            target,
+            journal,
+            interval: None,
+            warned: false,
+        }
+    }
+
+    /// Check the span of a full window, resizing it to the target span if given and logging a
+    /// suggestion otherwise.
+    pub fn observe(&mut self, measurement: &mut Measurement) {
+        let window = measurement.window_size();
+        // A window still filling up, e.g. after growing, would underestimate its span.
+        if (measurement.buffer_len() as u64) < window {
+            return;
+        }
+        let Some(span) = measurement.window_span() else {
+            return;
+        };
+        let latest = span.as_secs_f64() / (window - 1) as f64;
+        let interval = self.interval.map_or(latest, |interval| {
+            interval + SMOOTHING * (latest - interval)
+        });
+        self.interval = Some(interval);
+        let blocks_for = |span: Duration| {
+            ((span.as_secs_f64() / interval).ceil() as u64 + 1).clamp(2, MAX_WINDOW)
+        };
+
+        if let Some(target) = self.target {
+            let resized = blocks_for(target);
+            let off = resized.abs_diff(window) as f64 / window as f64;
+            if off > TOLERANCE {
+                measurement.set_window_size(resized);
+                self.journal.log(
+                    Severity::Info,
+                    "window",
+                    format!(
+                        "Window resized from {window} to {resized} blocks to span {:.1} s at \
+                         {:.0} ms blocks",
+                        target.as_secs_f64(),
+                        interval * 1000.0
+                    ),
+                );
+            }
+            return;
+        }
+
+        if span >= self.min_span {
+            self.warned = false;
+            return;
+        }
+        if !self.warned {
+            self.warned = true;
+            self.journal.log(
+                Severity::Warn,
+                "window",
+                format!(
+                    "Window of {window} blocks spans only {:.2} s at {:.0} ms blocks, too short \
+                     for stable rates; --window {} spans {:.1} s, or --auto-window keeps the span",
+                    span.as_secs_f64(),
+                    interval * 1000.0,
+                    blocks_for(self.min_span),
+                    self.min_span.as_secs_f64()
+                ),
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::BTreeMap, fmt};
+
+use alloy::{
+    consensus::Transaction,
+    network::{AnyRpcBlock, AnyTransactionReceipt},
+    sol,
+    sol_types::SolCall,
+};
+use serde::Serialize;
+
+sol! {
+    // ERC-20.
+    function transfer(address to, uint256 amount);
+    function transferFrom(address from, address to, uint256 amount);
+    function approve(address spender, uint256 amount);
+
+    // ERC-721 and ERC-1155.
+    function safeTransferFrom(address from, address to, uint256 id);
+    function safeTransferFrom(address from, address to, uint256 id, bytes data);
+    function safeTransferFrom(address from, address to, uint256 id, uint256 amount, bytes data);
+    function safeBatchTransferFrom(address from, address to, uint256[] ids, uint256[] amounts, bytes data);
+    function setApprovalForAll(address operator, bool approved);
+
+    // Uniswap V2 router and pair.
+    function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline);
+    function swapTokensForExactTokens(uint256 amountOut, uint256 amountInMax, address[] path, address to, uint256 deadline);
+    function swapExactETHForTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline);
+    function swapTokensForExactETH(uint256 amountOut, uint256 amountInMax, address[] path, address to, uint256 deadline);
+    function swapExactTokensForETH(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline);
+    function swapETHForExactTokens(uint256 amountOut, address[] path, address to, uint256 deadline);
+    function swap(uint256 amount0Out, uint256 amount1Out, address to, bytes data);
+
+    // Uniswap V3 pool and universal router.
+    function swap(address recipient, bool zeroForOne, int256 amountSpecified, uint160 sqrtPriceLimitX96, bytes data);
+    function execute(bytes commands, bytes[] inputs);
+    function execute(bytes commands, bytes[] inputs, uint256 deadline);
+}
+
+/// What a transaction does, guessed from its recipient and the selector of its calldata.
+///
+/// Calls through multicalls, aggregators and account abstraction count as [`Category::Other`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// A plain value transfer without calldata.
+    Transfer,
+    Erc20,
+    Swap,
+    Nft,
+    Deploy,
+    Other,
+}
+
+impl Category {
+    const ALL: [Self; 6] = [
+        Self::Transfer,
+        Self::Erc20,
+        Self::Swap,
+        Self::Nft,
+        Self::Deploy,
+        Self::Other,
+    ];
+
+    /// Classify a transaction.
+    pub fn of(tx: &impl Transaction) -> Self {
+        if tx.kind().is_create() {
+            return Self::Deploy;
+        }
+        let input = tx.input();
+        let Some(selector) = input.get(..4) else {
+            return if input.is_empty() {
+                Self::Transfer
+            } else {
+                Self::Other
+            };
+        };
+        match <[u8; 4]>::try_from(selector).unwrap_or_default() {
+            transferCall::SELECTOR | transferFromCall::SELECTOR | approveCall::SELECTOR => {
+                Self::Erc20
+            }
+            safeTransferFrom_0Call::SELECTOR
+            | safeTransferFrom_1Call::SELECTOR
+            | safeTransferFrom_2Call::SELECTOR
+            | safeBatchTransferFromCall::SELECTOR
+            | setApprovalForAllCall::SELECTOR => Self::Nft,
+            swapExactTokensForTokensCall::SELECTOR
+            | swapTokensForExactTokensCall::SELECTOR
+            | swapExactETHForTokensCall::SELECTOR
+            | swapTokensForExactETHCall::SELECTOR
+            | swapExactTokensForETHCall::SELECTOR
+            | swapETHForExactTokensCall::SELECTOR
+            | swap_0Call::SELECTOR
+            | swap_1Call::SELECTOR
+            | execute_0Call::SELECTOR
+            | execute_1Call::SELECTOR => Self::Swap,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Transfer => "transfer",
+            Self::Erc20 => "erc20",
+            Self::Swap => "swap",
+            Self::Nft => "nft",
+            Self::Deploy => "deploy",
+            Self::Other => "other",
+        })
+    }
+}
+
+/// The transactions and gas of every category in a block.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockWorkload {
+    /// The transactions and gas per category, in the order of [`Category::ALL`].
+    categories: [(usize, u64); 6],
+}
+
+/// The share of a category in the window's throughput.
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryShare {
+    pub transactions_per_second: f64,
+    pub transaction_percentage: f64,
+    pub gas_per_second: f64,
+    pub gas_percentage: f64,
+}
+
+impl BlockWorkload {
+    /// Classify the block's transactions, known only when full transactions and receipts were
+    /// fetched.
+    pub fn new(block: &AnyRpcBlock, receipts: Option<&[AnyTransactionReceipt]>) -> Option<Self> {
+        let txs = block.transactions.as_transactions()?;
+        let receipts = receipts?;
+        let mut workload = Self::default();
+        for (tx, receipt) in txs.iter().zip(receipts) {
+            let index = Category::of(tx) as usize;
+            workload.categories[index].0 += 1;
+            workload.categories[index].1 += receipt.gas_used;
+        }
+        Some(workload)
+    }
+}
+
+/// Break the throughput of the blocks down by category; the window spans `time_window` seconds.
+pub fn shares(
+    blocks: impl IntoIterator<Item = BlockWorkload>,
+    time_window: f64,
+) -> BTreeMap<Category, CategoryShare> {
+    let mut total = BlockWorkload::default();
+    for block in blocks {
+        for (sum, (transactions, gas)) in total.categories.iter_mut().zip(block.categories) {
+            sum.0 += transactions;
+            sum.1 += gas;
+        }
+    }
+    let transactions = total.categories.iter().map(|c| c.0).sum::<usize>().max(1) as f64;
+    let gas = total.categories.iter().map(|c| c.1).sum::<u64>().max(1) as f64;
+    Category::ALL
+        .into_iter()
+        .zip(total.categories)
+        .filter(|(_, (count, _))| *count > 0)
+        .map(|(category, (count, gas_used))| {
+            let share = CategoryShare {
+                transactions_per_second: count as f64 / time_window,
+                transaction_percentage: 100.0 * count as f64 / transactions,
+                gas_per_second: gas_used as f64 / time_window,
+                gas_percentage: 100.0 * gas_used as f64 / gas,
+            };
+            (category, share)
+        })
+        .collect()
+}